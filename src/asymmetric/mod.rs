@@ -1,2 +1,5 @@
 pub mod diffie_hellman;
 pub mod srp;
+pub mod srp6a;
+pub mod srp_groups;
+pub mod srp_service;
@@ -1,10 +1,23 @@
+// The simplified SRP from challenges 36 to 38: k = 3, no identity in x and
+// an HMAC of the salt as the only proof. The attacks in set 5 depend on
+// those shortcuts. For the real protocol see srp6a.
 use rand::{self, Rng};
 use num_bigint::{BigInt, Sign::Plus};
-use openssl::{sha::sha256, sign::Signer, hash::MessageDigest};
-use openssl::pkey::PKey;
-use openssl::memcmp;
+use crate::hashing::hmac::{hmac, Hmac};
+use crate::hashing::kdf::{HashKdf, Kdf};
+use crate::hashing::sha256::{sha256, Sha256, SHA256_LEN_BYTES};
 use super::diffie_hellman::DiffieHellmanContext;
 use num_bigint::RandBigInt;
+use num_traits::Zero;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SrpError {
+    // The other side's public key was 0 mod N, which would make the shared
+    // secret something anyone can compute
+    InvalidPublicKey,
+    // The other side couldn't prove it has the same key
+    BadProof
+}
 
 fn a_b_to_u(a: &BigInt, b: &BigInt) -> BigInt {
     let mut to_hash = a.to_bytes_be().1;
@@ -15,6 +28,11 @@ fn a_b_to_u(a: &BigInt, b: &BigInt) -> BigInt {
     hashed
 }
 
+// x from the password. With the default HashKdf this is sha256(salt || pw)
+pub(crate) fn password_to_x(kdf: &dyn Kdf, salt: &BigInt, pw: &[u8]) -> BigInt {
+    BigInt::from_bytes_be(Plus, &kdf.derive(pw, &salt.to_bytes_be().1, SHA256_LEN_BYTES))
+}
+
 pub struct SrpServer {
     k: BigInt,
     salt: BigInt,
@@ -25,18 +43,30 @@ pub struct SrpServer {
     c_k: Option<Vec<u8>>,
     dh: DiffieHellmanContext,
     u: Option<BigInt>,
+    check_public_keys: bool
+}
+
+// A public key that is 0 mod N forces the shared secret to 0
+fn check_public_key(key: &BigInt, n: &BigInt) -> Result<(), SrpError> {
+    if (key % n).is_zero() {
+        Err(SrpError::InvalidPublicKey)
+    } else {
+        Ok(())
+    }
 }
 
 impl SrpServer {
     pub fn new(pw: &[u8]) -> SrpServer {
+        SrpServer::with_kdf(pw, HashKdf::<Sha256>::new())
+    }
+
+    // The client has to use the same KDF to get in
+    pub fn with_kdf<K: Kdf>(pw: &[u8], kdf: K) -> SrpServer {
         let dh = DiffieHellmanContext::nist();
         let k: BigInt = From::from(3);
 
         let salt: BigInt = From::from(rand::thread_rng().gen::<u64>());
-        let mut to_hash = salt.to_bytes_be().1;
-        to_hash.extend_from_slice(pw);
-        let x_h = sha256(&to_hash);
-        let x = BigInt::from_bytes_be(Plus, &x_h);
+        let x = password_to_x(&kdf, &salt, pw);
         let v = dh.g.modpow(&x, &dh.p);
 
         SrpServer {
@@ -49,11 +79,40 @@ impl SrpServer {
             c_k: None,
             dh: dh,
             u: None,
+            check_public_keys: true
         }
     }
 
+    // Accepts A = 0, N, 2N, ... and with it a client that knows nothing but
+    // the shared secret 0. Only here so challenge 37 has something to break
+    pub fn insecure_skip_public_key_check(mut self) -> SrpServer {
+        self.check_public_keys = false;
+        self
+    }
+
+    fn validate(&self, pubkey: &BigInt) -> Result<(), SrpError> {
+        if self.check_public_keys {
+            check_public_key(pubkey, &self.dh.p)
+        } else {
+            Ok(())
+        }
+    }
+
+    // Forgets the previous handshake, so a rejected one can't leave its key
+    // behind for is_ok to accept
+    fn reset_session(&mut self) {
+        self.c_a = None;
+        self.c_b = None;
+        self.c_s = None;
+        self.c_k = None;
+        self.u = None;
+    }
+
     // Returns (salt, B)
-    pub fn initial_req(&mut self, pubkey: &BigInt) -> Result<(BigInt, BigInt), ()> {
+    pub fn initial_req(&mut self, pubkey: &BigInt) -> Result<(BigInt, BigInt), SrpError> {
+        self.reset_session();
+        self.validate(pubkey)?;
+
         self.c_a = Some(pubkey.clone());
         self.c_b = Some(&self.k * &self.v + &self.dh.public_key);
 
@@ -70,20 +129,23 @@ impl SrpServer {
         Ok((self.salt.clone(), self.c_b.clone().unwrap()))
     }
 
+    // Always false if the client never got a key agreed
     pub fn is_ok(&self, content: &[u8]) -> bool {
-        let pkey = PKey::hmac(self.c_k.as_ref().unwrap()).unwrap();
-        let mut signer = Signer::new(
-            MessageDigest::sha256(),
-            &pkey
-        ).unwrap();
-        signer.update(&self.salt.to_bytes_be().1).unwrap();
-        let hmac = signer.sign_to_vec().unwrap();
+        let key = match &self.c_k {
+            Some(key) => key,
+            None => return false
+        };
 
-        memcmp::eq(&hmac, content)
+        let mut mac = Hmac::<Sha256>::new(key);
+        mac.update(&self.salt.to_bytes_be().1);
+        mac.verify(content)
     }
 
     // Returns (salt, B, u)
-    pub fn variant_initial_req(&mut self, pubkey: &BigInt) -> Result<(BigInt, BigInt, BigInt), ()> {
+    pub fn variant_initial_req(&mut self, pubkey: &BigInt) -> Result<(BigInt, BigInt, BigInt), SrpError> {
+        self.reset_session();
+        self.validate(pubkey)?;
+
         self.u = Some(BigInt::from(rand::thread_rng().gen::<u128>()));
 
         let s = self.dh.make_session_key(&(pubkey * self.v.modpow(self.u.as_ref().unwrap(), &self.dh.p)));
@@ -102,7 +164,8 @@ pub struct SrpClient {
     u: Option<BigInt>,
     priv_key: BigInt,
     k: BigInt,
-    c_k: Option<Vec<u8>>
+    c_k: Option<Vec<u8>>,
+    kdf: Box<dyn Kdf>
 }
 
 impl SrpClient {
@@ -121,19 +184,24 @@ impl SrpClient {
             u: None,
             priv_key: private_key,
             k: From::from(3),
-            c_k: None
+            c_k: None,
+            kdf: Box::new(HashKdf::<Sha256>::new())
         }
     }
 
-    pub fn set_salt_and_pkey(&mut self, salt: &BigInt, pkey_b: &BigInt, pw: &[u8]) {
+    pub fn with_kdf<K: Kdf + 'static>(mut self, kdf: K) -> SrpClient {
+        self.kdf = Box::new(kdf);
+        self
+    }
+
+    pub fn set_salt_and_pkey(&mut self, salt: &BigInt, pkey_b: &BigInt, pw: &[u8]) -> Result<(), SrpError> {
+        check_public_key(pkey_b, &self.dh.p)?;
+
         self.salt = Some(salt.clone());
         self.c_b = Some(pkey_b.clone());
         self.u = Some(a_b_to_u(&self.dh.public_key, pkey_b));
 
-        let mut to_hash = salt.to_bytes_be().1;
-        to_hash.extend_from_slice(pw);
-        let x_h = sha256(&to_hash);
-        let x = BigInt::from_bytes_be(Plus, &x_h);
+        let x = password_to_x(self.kdf.as_ref(), salt, pw);
         let s: BigInt = (
             &(pkey_b - &self.k * self.dh.g.modpow(&x, &self.dh.p))
         ).modpow(
@@ -143,33 +211,31 @@ impl SrpClient {
 
         let to_hash = s.to_bytes_be().1;
         self.c_k = Some(sha256(&to_hash).to_vec());
+        Ok(())
     }
 
-    pub fn set_salt_and_pkey_variant(&mut self, salt: &BigInt, pkey_b: &BigInt, pw: &[u8], u: &BigInt) {
+    pub fn set_salt_and_pkey_variant(&mut self, salt: &BigInt, pkey_b: &BigInt, pw: &[u8], u: &BigInt) -> Result<(), SrpError> {
+        check_public_key(pkey_b, &self.dh.p)?;
+
         self.salt = Some(salt.clone());
-        let mut to_hash = salt.to_bytes_be().1;
-        to_hash.extend_from_slice(pw);
-        let x_h = sha256(&to_hash);
-        let x = BigInt::from_bytes_be(Plus, &x_h);
+        let x = password_to_x(self.kdf.as_ref(), salt, pw);
 
         let s = pkey_b.modpow(&(&self.priv_key + u * x), &self.dh.p);
         let to_hash = s.to_bytes_be().1;
 
         self.c_k = Some(sha256(&to_hash).to_vec());
+        Ok(())
     }
 
     pub fn get_hmac(&self) -> Vec<u8> {
-        let pkey = PKey::hmac(self.c_k.as_ref().unwrap()).unwrap();
-        let mut signer = Signer::new(
-            MessageDigest::sha256(),
-            &pkey
-        ).unwrap();
-        signer.update(&self.salt.as_ref().unwrap().to_bytes_be().1).unwrap();
-        let hmac = signer.sign_to_vec().unwrap();
-        hmac
+        hmac::<Sha256>(
+            self.c_k.as_ref().unwrap(),
+            &self.salt.as_ref().unwrap().to_bytes_be().1
+        )
     }
 
-    // Allow the attacker to set the state of the client
+    // Allow the attacker to set the state of the client. Stands in for a
+    // client that sent a bad A and so knows the key without the password
     pub fn attacker_set_shared_key(&mut self, key: Option<Vec<u8>>, salt: &BigInt) {
         self.c_k = key;
         self.salt = Some(salt.clone());
@@ -179,6 +245,7 @@ impl SrpClient {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::hashing::kdf::Pbkdf2;
 
     #[test]
     fn test_srp_happy_path() {
@@ -188,7 +255,89 @@ mod tests {
         let mut client = SrpClient::new();
         let res = server.initial_req(&client.dh.public_key).unwrap();
 
-        client.set_salt_and_pkey(&res.0, &res.1, password);
+        client.set_salt_and_pkey(&res.0, &res.1, password).unwrap();
+        assert!(server.is_ok(&client.get_hmac()));
+    }
+
+    #[test]
+    fn test_srp_with_pbkdf2() {
+        let password = b"hunter2";
+        let kdf = || Pbkdf2::<Sha256>::new(1000);
+        let mut server = SrpServer::with_kdf(password, kdf());
+
+        let mut client = SrpClient::new().with_kdf(kdf());
+        let res = server.initial_req(&client.dh.public_key).unwrap();
+        client.set_salt_and_pkey(&res.0, &res.1, password).unwrap();
+        assert!(server.is_ok(&client.get_hmac()));
+
+        // Getting the KDF wrong is as good as getting the password wrong
+        let mut client = SrpClient::new();
+        let res = server.initial_req(&client.dh.public_key).unwrap();
+        client.set_salt_and_pkey(&res.0, &res.1, password).unwrap();
+        assert!(!server.is_ok(&client.get_hmac()));
+    }
+
+    #[test]
+    fn test_server_rejects_zero_public_keys() {
+        let mut server = SrpServer::new(b"hunter2");
+        let n = DiffieHellmanContext::nist().p;
+
+        for i in 0..5 {
+            let a_pub = &n * BigInt::from(i);
+            assert_eq!(server.initial_req(&a_pub), Err(SrpError::InvalidPublicKey));
+            assert_eq!(server.variant_initial_req(&a_pub), Err(SrpError::InvalidPublicKey));
+        }
+
+        // And nobody gets in after that, whatever they send
+        let mut client = SrpClient::new();
+        client.attacker_set_shared_key(Some(sha256(&[0u8])), &server.salt);
+        assert!(!server.is_ok(&client.get_hmac()));
+    }
+
+    #[test]
+    fn test_rejected_handshake_forgets_previous_key() {
+        let password = b"hunter2";
+        let mut server = SrpServer::new(password);
+
+        let mut client = SrpClient::new();
+        let res = server.initial_req(&client.dh.public_key).unwrap();
+        client.set_salt_and_pkey(&res.0, &res.1, password).unwrap();
+        let old_hmac = client.get_hmac();
+        assert!(server.is_ok(&old_hmac));
+
+        assert_eq!(server.initial_req(&BigInt::zero()), Err(SrpError::InvalidPublicKey));
+        assert!(!server.is_ok(&old_hmac));
+
+        // Same for the variant from challenge 38
+        let res = server.initial_req(&client.dh.public_key).unwrap();
+        client.set_salt_and_pkey(&res.0, &res.1, password).unwrap();
+        assert!(server.is_ok(&client.get_hmac()));
+        assert_eq!(server.variant_initial_req(&BigInt::zero()), Err(SrpError::InvalidPublicKey));
+        assert!(!server.is_ok(&client.get_hmac()));
+    }
+
+    #[test]
+    fn test_insecure_server_accepts_zero_public_keys() {
+        let mut server = SrpServer::new(b"hunter2").insecure_skip_public_key_check();
+        let (salt, _) = server.initial_req(&BigInt::zero()).unwrap();
+
+        let mut client = SrpClient::new();
+        client.attacker_set_shared_key(Some(sha256(&[0u8])), &salt);
         assert!(server.is_ok(&client.get_hmac()));
     }
+
+    #[test]
+    fn test_client_rejects_zero_public_keys() {
+        let mut client = SrpClient::new();
+        let n = client.dh.p.clone();
+
+        assert_eq!(
+            client.set_salt_and_pkey(&BigInt::from(1), &n, b"hunter2"),
+            Err(SrpError::InvalidPublicKey)
+        );
+        assert_eq!(
+            client.set_salt_and_pkey_variant(&BigInt::from(1), &BigInt::zero(), b"hunter2", &BigInt::from(1)),
+            Err(SrpError::InvalidPublicKey)
+        );
+    }
 }
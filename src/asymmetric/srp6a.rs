@@ -0,0 +1,448 @@
+// SRP-6a as specified in RFC 5054, with the M1/M2 proofs from RFC 2945:
+//
+//   k = H(N || PAD(g))          x = H(s || H(I || ":" || P))
+//   v = g^x                     u = H(PAD(A) || PAD(B))
+//   A = g^a                     B = k*v + g^b
+//   client S = (B - k*g^x)^(a + u*x)
+//   server S = (A * v^u)^b
+//   K = H(S)
+//   M1 = H(H(N) ^ H(g) || H(I) || s || A || B || K)
+//   M2 = H(A || M1 || K)
+//
+// Everything is mod N. The server only ever stores (I, s, v).
+use std::marker::PhantomData;
+
+use num_bigint::{BigInt, RandBigInt, Sign::Plus};
+use num_traits::Zero;
+
+use crate::asymmetric::srp::SrpError;
+use crate::asymmetric::srp_groups::SrpGroup;
+use crate::hashing::hasher::Hasher;
+use crate::hashing::hmac::constant_time_eq;
+
+// Size of the private exponents a and b
+const PRIVATE_KEY_BITS: u64 = 256;
+const SALT_LEN: usize = 16;
+
+fn to_bytes(x: &BigInt) -> Vec<u8> {
+    x.to_bytes_be().1
+}
+
+fn hash<H: Hasher>(parts: &[&[u8]]) -> Vec<u8> {
+    let mut hasher = H::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize()
+}
+
+fn hash_to_int<H: Hasher>(parts: &[&[u8]]) -> BigInt {
+    BigInt::from_bytes_be(Plus, &hash::<H>(parts))
+}
+
+// What the server keeps instead of the password
+#[derive(Clone, Debug, PartialEq)]
+pub struct SrpVerifier {
+    pub username: Vec<u8>,
+    pub salt: Vec<u8>,
+    pub v: BigInt
+}
+
+// The group and hash both sides agreed on
+pub struct Srp6a<H> {
+    group: SrpGroup,
+    hash: PhantomData<H>
+}
+
+// Not derived, that would want H: Clone
+impl<H> Clone for Srp6a<H> {
+    fn clone(&self) -> Srp6a<H> {
+        Srp6a { group: self.group.clone(), hash: PhantomData }
+    }
+}
+
+impl<H: Hasher> Srp6a<H> {
+    pub fn new(group: SrpGroup) -> Srp6a<H> {
+        Srp6a { group, hash: PhantomData }
+    }
+
+    pub fn group(&self) -> &SrpGroup {
+        &self.group
+    }
+
+    // PAD() from the RFC: left pad with zeros to the length of N. Only for
+    // values already reduced mod N, public keys get range checked first
+    fn pad(&self, x: &BigInt) -> Vec<u8> {
+        let bytes = to_bytes(x);
+        assert!(bytes.len() <= self.group.pad_len(), "PAD() of a value longer than N");
+        let mut res = vec![0u8; self.group.pad_len() - bytes.len()];
+        res.extend(bytes);
+        res
+    }
+
+    pub fn k(&self) -> BigInt {
+        hash_to_int::<H>(&[&to_bytes(&self.group.n), &self.pad(&self.group.g)])
+    }
+
+    pub fn x(&self, salt: &[u8], username: &[u8], password: &[u8]) -> BigInt {
+        let inner = hash::<H>(&[username, b":", password]);
+        hash_to_int::<H>(&[salt, &inner])
+    }
+
+    pub fn u(&self, a_pub: &BigInt, b_pub: &BigInt) -> BigInt {
+        hash_to_int::<H>(&[&self.pad(a_pub), &self.pad(b_pub)])
+    }
+
+    pub fn verifier_with_salt(&self, username: &[u8], password: &[u8], salt: &[u8]) -> SrpVerifier {
+        let x = self.x(salt, username, password);
+
+        SrpVerifier {
+            username: username.to_vec(),
+            salt: salt.to_vec(),
+            v: self.group.g.modpow(&x, &self.group.n)
+        }
+    }
+
+    // Registers a user under a fresh random salt
+    pub fn verifier(&self, username: &[u8], password: &[u8]) -> SrpVerifier {
+        let salt = crate::rng::vec::rand_len(SALT_LEN);
+        self.verifier_with_salt(username, password, &salt)
+    }
+
+    // RFC 5054 only asks for key % N != 0, but anything outside (0, N)
+    // wasn't computed mod N in the first place and would break PAD()
+    pub fn is_valid_public_key(&self, key: &BigInt) -> bool {
+        key > &BigInt::zero() && key < &self.group.n
+    }
+
+    fn random_private_key(&self) -> BigInt {
+        rand::thread_rng().gen_bigint_range(
+            &BigInt::from(1),
+            &(BigInt::from(1) << PRIVATE_KEY_BITS as usize)
+        )
+    }
+
+    fn client_premaster(&self, a: &BigInt, x: &BigInt, u: &BigInt, b_pub: &BigInt) -> BigInt {
+        let n = &self.group.n;
+        let base = (b_pub - self.k() * self.group.g.modpow(x, n)) % n;
+        // % keeps the sign of the left side
+        let base = if base < BigInt::zero() { base + n } else { base };
+        base.modpow(&(a + u * x), n)
+    }
+
+    fn server_premaster(&self, b: &BigInt, v: &BigInt, u: &BigInt, a_pub: &BigInt) -> BigInt {
+        let n = &self.group.n;
+        (a_pub * v.modpow(u, n) % n).modpow(b, n)
+    }
+
+    fn client_proof(&self, username: &[u8], salt: &[u8], a_pub: &BigInt, b_pub: &BigInt, key: &[u8]) -> Vec<u8> {
+        let h_n = hash::<H>(&[&to_bytes(&self.group.n)]);
+        let h_g = hash::<H>(&[&to_bytes(&self.group.g)]);
+        let h_n_xor_h_g: Vec<u8> = h_n.iter().zip(h_g.iter()).map(|(x, y)| x ^ y).collect();
+
+        hash::<H>(&[
+            &h_n_xor_h_g,
+            &hash::<H>(&[username]),
+            salt,
+            &to_bytes(a_pub),
+            &to_bytes(b_pub),
+            key
+        ])
+    }
+
+    fn server_proof(&self, a_pub: &BigInt, m1: &[u8], key: &[u8]) -> Vec<u8> {
+        hash::<H>(&[&to_bytes(a_pub), m1, key])
+    }
+}
+
+pub struct Srp6aClient<H> {
+    srp: Srp6a<H>,
+    username: Vec<u8>,
+    a: BigInt,
+    a_pub: BigInt,
+    // Set once we've seen the server's challenge
+    m1: Option<Vec<u8>>,
+    key: Option<Vec<u8>>
+}
+
+impl<H: Hasher> Srp6aClient<H> {
+    pub fn new(srp: &Srp6a<H>, username: &[u8]) -> Srp6aClient<H> {
+        Srp6aClient::with_private_key(srp, username, &srp.random_private_key())
+    }
+
+    // Only for reproducing test vectors, a has to be random
+    pub fn with_private_key(srp: &Srp6a<H>, username: &[u8], a: &BigInt) -> Srp6aClient<H> {
+        Srp6aClient {
+            srp: srp.clone(),
+            username: username.to_vec(),
+            a: a.clone(),
+            a_pub: srp.group.g.modpow(a, &srp.group.n),
+            m1: None,
+            key: None
+        }
+    }
+
+    // A, sent along with the username
+    pub fn public_key(&self) -> &BigInt {
+        &self.a_pub
+    }
+
+    // Takes the salt and B from the server and returns M1
+    pub fn process_challenge(&mut self, password: &[u8], salt: &[u8], b_pub: &BigInt) -> Result<Vec<u8>, SrpError> {
+        if !self.srp.is_valid_public_key(b_pub) {
+            return Err(SrpError::InvalidPublicKey);
+        }
+
+        let u = self.srp.u(&self.a_pub, b_pub);
+        if u.is_zero() {
+            return Err(SrpError::InvalidPublicKey);
+        }
+
+        let x = self.srp.x(salt, &self.username, password);
+        let s = self.srp.client_premaster(&self.a, &x, &u, b_pub);
+        let key = hash::<H>(&[&to_bytes(&s)]);
+
+        let m1 = self.srp.client_proof(&self.username, salt, &self.a_pub, b_pub, &key);
+        self.m1 = Some(m1.clone());
+        self.key = Some(key);
+
+        Ok(m1)
+    }
+
+    // Checks M2. Until this passes we don't know we're talking to someone
+    // who has the verifier
+    pub fn verify_server(&self, m2: &[u8]) -> Result<&[u8], SrpError> {
+        let (m1, key) = match (&self.m1, &self.key) {
+            (Some(m1), Some(key)) => (m1, key),
+            _ => return Err(SrpError::BadProof)
+        };
+
+        if constant_time_eq(&self.srp.server_proof(&self.a_pub, m1, key), m2) {
+            Ok(key)
+        } else {
+            Err(SrpError::BadProof)
+        }
+    }
+}
+
+pub struct Srp6aServer<H> {
+    srp: Srp6a<H>,
+    verifier: SrpVerifier,
+    b: BigInt,
+    b_pub: BigInt,
+    key: Option<Vec<u8>>
+}
+
+impl<H: Hasher> Srp6aServer<H> {
+    pub fn new(srp: &Srp6a<H>, verifier: SrpVerifier) -> Srp6aServer<H> {
+        Srp6aServer::with_private_key(srp, verifier, &srp.random_private_key())
+    }
+
+    // Only for reproducing test vectors, b has to be random
+    pub fn with_private_key(srp: &Srp6a<H>, verifier: SrpVerifier, b: &BigInt) -> Srp6aServer<H> {
+        let n = &srp.group.n;
+        let b_pub = (srp.k() * &verifier.v + srp.group.g.modpow(b, n)) % n;
+
+        Srp6aServer {
+            srp: srp.clone(),
+            verifier,
+            b: b.clone(),
+            b_pub,
+            key: None
+        }
+    }
+
+    // (salt, B)
+    pub fn challenge(&self) -> (&[u8], &BigInt) {
+        (&self.verifier.salt, &self.b_pub)
+    }
+
+    // Checks the client's A and M1. Returns M2 to send back
+    pub fn verify_client(&mut self, a_pub: &BigInt, m1: &[u8]) -> Result<Vec<u8>, SrpError> {
+        if !self.srp.is_valid_public_key(a_pub) {
+            return Err(SrpError::InvalidPublicKey);
+        }
+
+        let u = self.srp.u(a_pub, &self.b_pub);
+        let s = self.srp.server_premaster(&self.b, &self.verifier.v, &u, a_pub);
+        let key = hash::<H>(&[&to_bytes(&s)]);
+
+        let expected = self.srp.client_proof(
+            &self.verifier.username,
+            &self.verifier.salt,
+            a_pub,
+            &self.b_pub,
+            &key
+        );
+
+        if !constant_time_eq(&expected, m1) {
+            return Err(SrpError::BadProof);
+        }
+
+        let m2 = self.srp.server_proof(a_pub, m1, &key);
+        self.key = Some(key);
+        Ok(m2)
+    }
+
+    // Only there once the client has proven itself
+    pub fn session_key(&self) -> Option<&[u8]> {
+        self.key.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashing::sha1::Sha1;
+    use crate::hashing::sha256::Sha256;
+
+    fn from_hex(s: &str) -> BigInt {
+        BigInt::parse_bytes(s.replace(' ', "").as_bytes(), 16).unwrap()
+    }
+
+    // RFC 5054 appendix B
+    #[test]
+    fn test_rfc5054_vectors() {
+        let srp = Srp6a::<Sha1>::new(SrpGroup::rfc5054_1024());
+        let salt = hex::decode("BEB25379D1A8581EB5A727673A2441EE").unwrap();
+        let a = from_hex("60975527 035CF2AD 1989806F 0407210B C81EDC04 E2762A56 AFD529DD DA2D4393");
+        let b = from_hex("E487CB59 D31AC550 471E81F0 0F6928E0 1DDA08E9 74A004F4 9E61F5D1 05284D20");
+
+        assert_eq!(srp.k(), from_hex("7556AA04 5AEF2CDD 07ABAF0F 665C3E81 8913186F"));
+
+        let x = srp.x(&salt, b"alice", b"password123");
+        assert_eq!(x, from_hex("94B7555A ABE9127C C58CCF49 93DB6CF8 4D16C124"));
+
+        let verifier = srp.verifier_with_salt(b"alice", b"password123", &salt);
+        assert_eq!(verifier.v, from_hex(
+            "7E273DE8 696FFC4F 4E337D05 B4B375BE B0DDE156 9E8FA00A 9886D812
+             9BADA1F1 822223CA 1A605B53 0E379BA4 729FDC59 F105B478 7E5186F5
+             C671085A 1447B52A 48CF1970 B4FB6F84 00BBF4CE BFBB1681 52E08AB5
+             EA53D15C 1AFF87B2 B9DA6E04 E058AD51 CC72BFC9 033B564E 26480D78
+             E955A5E2 9E7AB245 DB2BE315 E2099AFB".replace('\n', "").as_str()
+        ));
+
+        let client = Srp6aClient::with_private_key(&srp, b"alice", &a);
+        assert_eq!(client.public_key(), &from_hex(
+            "61D5E490 F6F1B795 47B0704C 436F523D D0E560F0 C64115BB 72557EC4
+             4352E890 3211C046 92272D8B 2D1A5358 A2CF1B6E 0BFCF99F 921530EC
+             8E393561 79EAE45E 42BA92AE ACED8251 71E1E8B9 AF6D9C03 E1327F44
+             BE087EF0 6530E69F 66615261 EEF54073 CA11CF58 58F0EDFD FE15EFEA
+             B349EF5D 76988A36 72FAC47B 0769447B".replace('\n', "").as_str()
+        ));
+
+        let server = Srp6aServer::with_private_key(&srp, verifier.clone(), &b);
+        let b_pub = server.challenge().1.clone();
+        assert_eq!(b_pub, from_hex(
+            "BD0C6151 2C692C0C B6D041FA 01BB152D 4916A1E7 7AF46AE1 05393011
+             BAF38964 DC46A067 0DD125B9 5A981652 236F99D9 B681CBF8 7837EC99
+             6C6DA044 53728610 D0C6DDB5 8B318885 D7D82C7F 8DEB75CE 7BD4FBAA
+             37089E6F 9C6059F3 88838E7A 00030B33 1EB76840 910440B1 B27AAEAE
+             EB4012B7 D7665238 A8E3FB00 4B117B58".replace('\n', "").as_str()
+        ));
+
+        let u = srp.u(client.public_key(), &b_pub);
+        assert_eq!(u, from_hex("CE38B959 3487DA98 554ED47D 70A7AE5F 462EF019"));
+
+        let premaster = from_hex(
+            "B0DC82BA BCF30674 AE450C02 87745E79 90A3381F 63B387AA F271A10D
+             233861E3 59B48220 F7C4693C 9AE12B0A 6F67809F 0876E2D0 13800D6C
+             41BB59B6 D5979B5C 00A172B4 A2A5903A 0BDCAF8A 709585EB 2AFAFA8F
+             3499B200 210DCC1F 10EB3394 3CD67FC8 8A2F39A4 BE5BEC4E C0A3212D
+             C346D7E4 74B29EDE 8A469FFE CA686E5A".replace('\n', "").as_str()
+        );
+        assert_eq!(srp.client_premaster(&a, &x, &u, &b_pub), premaster);
+        assert_eq!(srp.server_premaster(&b, &verifier.v, &u, client.public_key()), premaster);
+    }
+
+    fn handshake<H: Hasher>(group: SrpGroup) {
+        let srp = Srp6a::<H>::new(group);
+        let verifier = srp.verifier(b"alice", b"correct horse battery staple");
+
+        let mut client = Srp6aClient::new(&srp, b"alice");
+        let mut server = Srp6aServer::new(&srp, verifier);
+
+        let (salt, b_pub) = server.challenge();
+        let (salt, b_pub) = (salt.to_vec(), b_pub.clone());
+        let m1 = client.process_challenge(b"correct horse battery staple", &salt, &b_pub).unwrap();
+        let m2 = server.verify_client(client.public_key(), &m1).unwrap();
+
+        assert_eq!(client.verify_server(&m2).unwrap(), server.session_key().unwrap());
+    }
+
+    #[test]
+    fn test_handshake() {
+        handshake::<Sha1>(SrpGroup::rfc5054_1024());
+        handshake::<Sha256>(SrpGroup::rfc5054_2048());
+        // g = 5
+        handshake::<Sha256>(SrpGroup::rfc5054_3072());
+    }
+
+    #[test]
+    fn test_wrong_password() {
+        let srp = Srp6a::<Sha256>::new(SrpGroup::rfc5054_2048());
+        let verifier = srp.verifier(b"alice", b"hunter2");
+
+        let mut client = Srp6aClient::new(&srp, b"alice");
+        let mut server = Srp6aServer::new(&srp, verifier);
+
+        let (salt, b_pub) = (server.challenge().0.to_vec(), server.challenge().1.clone());
+        let m1 = client.process_challenge(b"hunter3", &salt, &b_pub).unwrap();
+
+        assert_eq!(server.verify_client(client.public_key(), &m1), Err(SrpError::BadProof));
+        assert_eq!(server.session_key(), None);
+    }
+
+    #[test]
+    fn test_fake_server() {
+        // Someone without the verifier can't come up with M2
+        let srp = Srp6a::<Sha256>::new(SrpGroup::rfc5054_2048());
+        let real = srp.verifier(b"alice", b"hunter2");
+        let fake = srp.verifier_with_salt(b"alice", b"guess", &real.salt);
+
+        let mut client = Srp6aClient::new(&srp, b"alice");
+        let mut server = Srp6aServer::new(&srp, fake);
+
+        let (salt, b_pub) = (server.challenge().0.to_vec(), server.challenge().1.clone());
+        let m1 = client.process_challenge(b"hunter2", &salt, &b_pub).unwrap();
+
+        // The fake server can't check M1 either, so it just makes up an M2
+        assert!(server.verify_client(client.public_key(), &m1).is_err());
+        assert_eq!(client.verify_server(&[0u8; 32]), Err(SrpError::BadProof));
+    }
+
+    #[test]
+    fn test_rejects_zero_public_keys() {
+        let srp = Srp6a::<Sha256>::new(SrpGroup::rfc5054_2048());
+        let n = srp.group().n.clone();
+
+        let mut server = Srp6aServer::new(&srp, srp.verifier(b"alice", b"hunter2"));
+        for a_pub in [BigInt::zero(), n.clone(), &n * 2].iter() {
+            assert_eq!(server.verify_client(a_pub, &[0u8; 32]), Err(SrpError::InvalidPublicKey));
+        }
+
+        let mut client = Srp6aClient::new(&srp, b"alice");
+        assert_eq!(
+            client.process_challenge(b"hunter2", b"salt", &n),
+            Err(SrpError::InvalidPublicKey)
+        );
+    }
+
+    #[test]
+    fn test_rejects_oversized_public_keys() {
+        // Not 0 mod N, but longer than N so PAD() can't handle them
+        let srp = Srp6a::<Sha256>::new(SrpGroup::rfc5054_2048());
+        let n = srp.group().n.clone();
+
+        let mut server = Srp6aServer::new(&srp, srp.verifier(b"alice", b"hunter2"));
+        for a_pub in [&n * 256, (BigInt::from(1) << 2048) + 1, &n + 1, -BigInt::from(1)].iter() {
+            assert_eq!(server.verify_client(a_pub, &[0u8; 32]), Err(SrpError::InvalidPublicKey));
+        }
+
+        let mut client = Srp6aClient::new(&srp, b"alice");
+        assert_eq!(
+            client.process_challenge(b"hunter2", b"salt", &(&n * 256)),
+            Err(SrpError::InvalidPublicKey)
+        );
+    }
+}
@@ -0,0 +1,272 @@
+// Async client for `SrpService`. `login` does the whole handshake;
+// `start_login` and `finish_login` split it in two for tests that want to
+// get in between.
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Request, StatusCode};
+use num_bigint::{BigInt, Sign::Plus};
+use serde::de::DeserializeOwned;
+
+use super::*;
+use crate::asymmetric::srp::SrpError;
+use crate::asymmetric::srp6a::{Srp6a, Srp6aClient};
+use crate::hashing::hasher::Hasher;
+
+#[derive(Debug)]
+pub enum ClientError {
+    Http(hyper::Error),
+    // The server's error status and message
+    Status(StatusCode, String),
+    // Not the JSON we were expecting
+    BadResponse,
+    // The server couldn't prove it knows our verifier
+    Srp(SrpError)
+}
+
+impl From<hyper::Error> for ClientError {
+    fn from(e: hyper::Error) -> ClientError {
+        ClientError::Http(e)
+    }
+}
+
+impl ClientError {
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            ClientError::Status(status, _) => Some(*status),
+            _ => None
+        }
+    }
+}
+
+// Sent M1's worth of work, waiting to send it
+pub struct PendingLogin<H> {
+    pub session: String,
+    client: Srp6aClient<H>,
+    m1: Vec<u8>
+}
+
+#[derive(Clone, Debug)]
+pub struct Login {
+    pub session: String,
+    pub key: Vec<u8>
+}
+
+pub struct SrpHttpClient<H> {
+    base_url: String,
+    client: Client<HttpConnector>,
+    srp: Srp6a<H>
+}
+
+impl<H: Hasher> SrpHttpClient<H> {
+    // `srp` has to match what the server was started with
+    pub fn new(base_url: &str, srp: Srp6a<H>) -> SrpHttpClient<H> {
+        SrpHttpClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: Client::new(),
+            srp
+        }
+    }
+
+    pub async fn register(&self, username: &str, password: &[u8]) -> Result<(), ClientError> {
+        let verifier = self.srp.verifier(username.as_bytes(), password);
+        let req = RegisterRequest {
+            username: username.to_string(),
+            salt: hex::encode(&verifier.salt),
+            verifier: hex::encode(verifier.v.to_bytes_be().1)
+        };
+
+        self.post::<_, Empty>("/register", &req).await.map(|_| ())
+    }
+
+    pub async fn start_login(&self, username: &str, password: &[u8]) -> Result<PendingLogin<H>, ClientError> {
+        let mut client = Srp6aClient::new(&self.srp, username.as_bytes());
+        let req = StartRequest {
+            username: username.to_string(),
+            a: hex::encode(client.public_key().to_bytes_be().1)
+        };
+        let res: StartResponse = self.post("/login/start", &req).await?;
+
+        let (salt, b_pub) = match (hex::decode(&res.salt), hex::decode(&res.b)) {
+            (Ok(salt), Ok(b)) => (salt, BigInt::from_bytes_be(Plus, &b)),
+            _ => return Err(ClientError::BadResponse)
+        };
+        let m1 = client.process_challenge(password, &salt, &b_pub).map_err(ClientError::Srp)?;
+
+        Ok(PendingLogin { session: res.session, client, m1 })
+    }
+
+    // Only returns the key once the server has proved itself with M2
+    pub async fn finish_login(&self, pending: PendingLogin<H>) -> Result<Login, ClientError> {
+        let req = FinishRequest {
+            session: pending.session.clone(),
+            m1: hex::encode(&pending.m1)
+        };
+        let res: FinishResponse = self.post("/login/finish", &req).await?;
+
+        let m2 = hex::decode(&res.m2).map_err(|_| ClientError::BadResponse)?;
+        let key = pending.client.verify_server(&m2).map_err(ClientError::Srp)?;
+
+        Ok(Login { session: pending.session, key: key.to_vec() })
+    }
+
+    pub async fn login(&self, username: &str, password: &[u8]) -> Result<Login, ClientError> {
+        let pending = self.start_login(username, password).await?;
+        self.finish_login(pending).await
+    }
+
+    pub async fn whoami(&self, session: &str) -> Result<String, ClientError> {
+        let req = SessionRequest { session: session.to_string() };
+        let res: WhoamiResponse = self.post("/whoami", &req).await?;
+        Ok(res.username)
+    }
+
+    async fn post<Req, Res>(&self, path: &str, body: &Req) -> Result<Res, ClientError>
+    where Req: Serialize, Res: DeserializeOwned {
+        let request = Request::post(format!("{}{}", self.base_url, path))
+            .header("content-type", "application/json")
+            .body(Body::from(serde_json::to_vec(body).unwrap()))
+            .unwrap();
+
+        let response = self.client.request(request).await?;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+
+        if status.is_success() {
+            serde_json::from_slice(&body).map_err(|_| ClientError::BadResponse)
+        } else {
+            let message = serde_json::from_slice::<ErrorResponse>(&body)
+                .map(|e| e.error)
+                .unwrap_or_default();
+            Err(ClientError::Status(status, message))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::Duration;
+    use crate::asymmetric::srp_groups::SrpGroup;
+    use crate::asymmetric::srp_service::registry::UserRegistry;
+    use crate::asymmetric::srp_service::server::SrpService;
+    use crate::hashing::sha256::Sha256;
+
+    fn srp() -> Srp6a<Sha256> {
+        Srp6a::new(SrpGroup::rfc5054_1024())
+    }
+
+    fn status_of<T>(res: Result<T, ClientError>) -> StatusCode {
+        match res {
+            Err(e) => e.status().expect("Expected an HTTP error status"),
+            Ok(_) => panic!("Expected an error")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_and_login() {
+        let server = SrpService::new(srp(), UserRegistry::in_memory()).serve();
+        let client = SrpHttpClient::new(&server.url(), srp());
+
+        client.register("alice", b"hunter2").await.unwrap();
+        let login = client.login("alice", b"hunter2").await.unwrap();
+
+        assert_eq!(client.whoami(&login.session).await.unwrap(), "alice");
+        assert_eq!(login.key.len(), 32);
+
+        // Logging in again gets a new session and key
+        let again = client.login("alice", b"hunter2").await.unwrap();
+        assert_ne!(again.session, login.session);
+        assert_ne!(again.key, login.key);
+    }
+
+    #[tokio::test]
+    async fn test_error_statuses() {
+        let server = SrpService::new(srp(), UserRegistry::in_memory()).serve();
+        let client = SrpHttpClient::new(&server.url(), srp());
+
+        client.register("alice", b"hunter2").await.unwrap();
+        assert_eq!(status_of(client.register("alice", b"other").await), StatusCode::CONFLICT);
+        assert_eq!(status_of(client.login("bob", b"hunter2").await), StatusCode::NOT_FOUND);
+        assert_eq!(status_of(client.login("alice", b"password").await), StatusCode::UNAUTHORIZED);
+        assert_eq!(status_of(client.whoami("00").await), StatusCode::NOT_FOUND);
+
+        // Half way through isn't logged in
+        let pending = client.start_login("alice", b"hunter2").await.unwrap();
+        assert_eq!(status_of(client.whoami(&pending.session).await), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_public_key() {
+        let server = SrpService::new(srp(), UserRegistry::in_memory()).serve();
+        let client = SrpHttpClient::new(&server.url(), srp());
+        client.register("alice", b"hunter2").await.unwrap();
+
+        // Not 0 mod N, but too long to PAD()
+        let a: BigInt = &srp().group().n * 256;
+        let req = StartRequest { username: "alice".to_string(), a: hex::encode(a.to_bytes_be().1) };
+        let res = client.post::<_, StartResponse>("/login/start", &req).await;
+        assert_eq!(status_of(res), StatusCode::BAD_REQUEST);
+
+        // And the server is still up
+        assert!(client.login("alice", b"hunter2").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_challenge_expires() {
+        let server = SrpService::new(srp(), UserRegistry::in_memory())
+            .with_challenge_ttl(Duration::from_millis(20))
+            .serve();
+        let client = SrpHttpClient::new(&server.url(), srp());
+        client.register("alice", b"hunter2").await.unwrap();
+
+        let pending = client.start_login("alice", b"hunter2").await.unwrap();
+        tokio::time::delay_for(Duration::from_millis(50)).await;
+
+        assert_eq!(status_of(client.finish_login(pending).await), StatusCode::GONE);
+    }
+
+    #[tokio::test]
+    async fn test_session_expires() {
+        let server = SrpService::new(srp(), UserRegistry::in_memory())
+            .with_session_ttl(Duration::from_millis(20))
+            .serve();
+        let client = SrpHttpClient::new(&server.url(), srp());
+        client.register("alice", b"hunter2").await.unwrap();
+
+        let login = client.login("alice", b"hunter2").await.unwrap();
+        tokio::time::delay_for(Duration::from_millis(50)).await;
+
+        assert_eq!(status_of(client.whoami(&login.session).await), StatusCode::GONE);
+    }
+
+    #[tokio::test]
+    async fn test_users_survive_restart() {
+        let path = std::env::temp_dir().join(format!(
+            "srp-service-{}.json",
+            hex::encode(crate::rng::vec::rand_len(8))
+        ));
+
+        {
+            let server = SrpService::new(srp(), UserRegistry::open(&path).unwrap()).serve();
+            let client = SrpHttpClient::new(&server.url(), srp());
+            client.register("alice", b"hunter2").await.unwrap();
+        }
+
+        let server = SrpService::new(srp(), UserRegistry::open(&path).unwrap()).serve();
+        let client = SrpHttpClient::new(&server.url(), srp());
+        let res = client.login("alice", b"hunter2").await;
+        fs::remove_file(&path).unwrap();
+
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_group_mismatch() {
+        let server = SrpService::new(srp(), UserRegistry::in_memory()).serve();
+        let client = SrpHttpClient::new(&server.url(), srp());
+        client.register("alice", b"hunter2").await.unwrap();
+
+        let other = SrpHttpClient::new(&server.url(), Srp6a::<Sha256>::new(SrpGroup::rfc5054_2048()));
+        assert!(other.login("alice", b"hunter2").await.is_err());
+    }
+}
@@ -0,0 +1,79 @@
+// A small SRP-6a login service for standing up a local auth server in
+// integration tests:
+//
+// let srp = Srp6a::<Sha256>::new(SrpGroup::rfc5054_2048());
+// let server = SrpService::new(srp.clone(), UserRegistry::open("users.json")?).serve();
+// let client = SrpHttpClient::new(&server.url(), srp);
+// client.register("alice", b"hunter2").await?;
+// let login = client.login("alice", b"hunter2").await?;
+//
+// Everything is a JSON POST, with byte strings and numbers hex encoded.
+// Errors come back as {"error": "..."} with a status code saying what
+// went wrong:
+//
+//   /register      {username, salt, verifier}  -> 201 {}
+//                  409 username taken
+//   /login/start   {username, a}               -> 200 {session, salt, b}
+//                  404 unknown user, 400 bad A
+//   /login/finish  {session, m1}               -> 200 {m2}
+//                  401 wrong password, 404 unknown session, 410 expired,
+//                  409 already finished
+//   /whoami        {session}                   -> 200 {username}
+//                  409 not logged in yet, 404/410 as above
+//
+// The password never leaves the client, registration sends the verifier.
+pub mod client;
+pub mod registry;
+pub mod server;
+pub mod session;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct RegisterRequest {
+    username: String,
+    salt: String,
+    verifier: String
+}
+
+#[derive(Serialize, Deserialize)]
+struct Empty {}
+
+#[derive(Serialize, Deserialize)]
+struct StartRequest {
+    username: String,
+    a: String
+}
+
+#[derive(Serialize, Deserialize)]
+struct StartResponse {
+    session: String,
+    salt: String,
+    b: String
+}
+
+#[derive(Serialize, Deserialize)]
+struct FinishRequest {
+    session: String,
+    m1: String
+}
+
+#[derive(Serialize, Deserialize)]
+struct FinishResponse {
+    m2: String
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionRequest {
+    session: String
+}
+
+#[derive(Serialize, Deserialize)]
+struct WhoamiResponse {
+    username: String
+}
+
+#[derive(Serialize, Deserialize)]
+struct ErrorResponse {
+    error: String
+}
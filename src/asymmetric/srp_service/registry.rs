@@ -0,0 +1,185 @@
+// Usernames to (salt, verifier). With a backing file, the whole registry is
+// written out as JSON after every change, so a restarted server still knows
+// everyone who registered:
+//
+// { "alice": { "salt": "<hex>", "verifier": "<hex>" }, ... }
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use num_bigint::{BigInt, Sign::Plus};
+use serde::{Deserialize, Serialize};
+
+use crate::asymmetric::srp6a::SrpVerifier;
+
+#[derive(Debug)]
+pub enum RegistryError {
+    UserExists,
+    Io(io::Error)
+}
+
+impl From<io::Error> for RegistryError {
+    fn from(e: io::Error) -> RegistryError {
+        RegistryError::Io(e)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredUser {
+    salt: String,
+    verifier: String
+}
+
+pub struct UserRegistry {
+    path: Option<PathBuf>,
+    users: HashMap<String, SrpVerifier>
+}
+
+impl UserRegistry {
+    // Forgets everything when dropped
+    pub fn in_memory() -> UserRegistry {
+        UserRegistry { path: None, users: HashMap::new() }
+    }
+
+    // Loads `path` if it exists, otherwise starts empty and creates it on the
+    // first registration
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<UserRegistry> {
+        let path = path.as_ref().to_path_buf();
+        let users = if path.exists() {
+            UserRegistry::load(&path)?
+        } else {
+            HashMap::new()
+        };
+
+        Ok(UserRegistry { path: Some(path), users })
+    }
+
+    fn load(path: &Path) -> io::Result<HashMap<String, SrpVerifier>> {
+        let stored: HashMap<String, StoredUser> = serde_json::from_slice(&fs::read(path)?)?;
+
+        stored.into_iter()
+            .map(|(username, user)| {
+                let salt = hex::decode(&user.salt);
+                let v = hex::decode(&user.verifier);
+                match (salt, v) {
+                    (Ok(salt), Ok(v)) => Ok((username.clone(), SrpVerifier {
+                        username: username.into_bytes(),
+                        salt,
+                        v: BigInt::from_bytes_be(Plus, &v)
+                    })),
+                    _ => Err(io::Error::new(io::ErrorKind::InvalidData, "Bad hex in registry"))
+                }
+            })
+            .collect()
+    }
+
+    // Writes to a temporary file and renames it over the old one, so a crash
+    // halfway through can't leave a truncated registry behind
+    fn save(&self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(())
+        };
+
+        let stored: HashMap<&String, StoredUser> = self.users.iter()
+            .map(|(username, verifier)| (username, StoredUser {
+                salt: hex::encode(&verifier.salt),
+                verifier: hex::encode(verifier.v.to_bytes_be().1)
+            }))
+            .collect();
+
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(&stored)?)?;
+        fs::rename(&tmp, path)
+    }
+
+    // Usernames have to be valid UTF-8 so they can be JSON keys
+    pub fn register(&mut self, verifier: SrpVerifier) -> Result<(), RegistryError> {
+        let username = String::from_utf8(verifier.username.clone())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Username isn't UTF-8"))?;
+
+        if self.users.contains_key(&username) {
+            return Err(RegistryError::UserExists);
+        }
+
+        self.users.insert(username.clone(), verifier);
+        if let Err(e) = self.save() {
+            // Don't keep a user we couldn't persist
+            self.users.remove(&username);
+            return Err(e.into());
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, username: &str) -> Option<&SrpVerifier> {
+        self.users.get(username)
+    }
+
+    pub fn len(&self) -> usize {
+        self.users.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asymmetric::srp6a::Srp6a;
+    use crate::asymmetric::srp_groups::SrpGroup;
+    use crate::hashing::sha256::Sha256;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let suffix = hex::encode(crate::rng::vec::rand_len(8));
+        std::env::temp_dir().join(format!("{}-{}.json", name, suffix))
+    }
+
+    #[test]
+    fn test_register_twice() {
+        let srp = Srp6a::<Sha256>::new(SrpGroup::rfc5054_1024());
+        let mut registry = UserRegistry::in_memory();
+
+        registry.register(srp.verifier(b"alice", b"hunter2")).unwrap();
+        match registry.register(srp.verifier(b"alice", b"password")) {
+            Err(RegistryError::UserExists) => {},
+            other => panic!("Expected UserExists, got {:?}", other)
+        }
+        assert_eq!(registry.len(), 1);
+    }
+
+    #[test]
+    fn test_survives_reopening() {
+        let srp = Srp6a::<Sha256>::new(SrpGroup::rfc5054_1024());
+        let path = temp_path("srp-registry");
+        let alice = srp.verifier(b"alice", b"hunter2");
+        let bob = srp.verifier(b"bob", b"correct horse");
+
+        {
+            let mut registry = UserRegistry::open(&path).unwrap();
+            assert!(registry.is_empty());
+            registry.register(alice.clone()).unwrap();
+            registry.register(bob.clone()).unwrap();
+        }
+
+        let registry = UserRegistry::open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(registry.get("alice"), Some(&alice));
+        assert_eq!(registry.get("bob"), Some(&bob));
+        assert_eq!(registry.get("carol"), None);
+    }
+
+    #[test]
+    fn test_corrupt_file() {
+        let path = temp_path("srp-registry");
+        fs::write(&path, br#"{"alice": {"salt": "zz", "verifier": "00"}}"#).unwrap();
+
+        let res = UserRegistry::open(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(res.is_err());
+    }
+}
@@ -0,0 +1,264 @@
+// The HTTP side of the service. Like `OracleServer`, it runs on its own
+// thread and runtime on an ephemeral localhost port and shuts down when
+// dropped.
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{mpsc, Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use num_bigint::{BigInt, Sign::Plus};
+use serde::de::DeserializeOwned;
+use tokio::sync::oneshot;
+use warp::http::StatusCode;
+use warp::Filter;
+
+use super::*;
+use super::registry::{RegistryError, UserRegistry};
+use super::session::{SessionError, Sessions};
+use crate::asymmetric::srp::SrpError;
+use crate::asymmetric::srp6a::{Srp6a, Srp6aServer, SrpVerifier};
+use crate::hashing::hasher::Hasher;
+use crate::oracles::http::new_runtime;
+
+const DEFAULT_CHALLENGE_TTL: Duration = Duration::from_secs(60);
+const DEFAULT_SESSION_TTL: Duration = Duration::from_secs(60 * 60);
+// Comfortably more than an 8192-bit verifier in hex
+const MAX_BODY_LEN: u64 = 16 * 1024;
+
+type Reply = warp::reply::WithStatus<warp::reply::Json>;
+
+fn reply<T: Serialize>(body: &T, status: StatusCode) -> Reply {
+    warp::reply::with_status(warp::reply::json(body), status)
+}
+
+fn error(message: &str, status: StatusCode) -> Reply {
+    reply(&ErrorResponse { error: message.to_string() }, status)
+}
+
+fn bad_request(message: &str) -> Reply {
+    error(message, StatusCode::BAD_REQUEST)
+}
+
+fn session_error(e: SessionError) -> Reply {
+    match e {
+        SessionError::Unknown => error("Unknown session", StatusCode::NOT_FOUND),
+        SessionError::Expired => error("Session expired", StatusCode::GONE),
+        SessionError::WrongState => error("Session is in the wrong state", StatusCode::CONFLICT),
+        SessionError::Srp(SrpError::BadProof) => error("Bad proof", StatusCode::UNAUTHORIZED),
+        SessionError::Srp(SrpError::InvalidPublicKey) => bad_request("Invalid public key")
+    }
+}
+
+async fn handle_rejection(err: warp::Rejection) -> Result<Reply, Infallible> {
+    if err.is_not_found() {
+        Ok(error("Not found", StatusCode::NOT_FOUND))
+    } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
+        Ok(error("Method not allowed", StatusCode::METHOD_NOT_ALLOWED))
+    } else {
+        Ok(bad_request("Malformed request"))
+    }
+}
+
+fn json_body<T>() -> impl Filter<Extract = (T,), Error = warp::Rejection> + Copy
+where T: DeserializeOwned + Send {
+    warp::body::content_length_limit(MAX_BODY_LEN).and(warp::body::json())
+}
+
+fn decode_int(s: &str) -> Option<BigInt> {
+    hex::decode(s).ok().map(|bytes| BigInt::from_bytes_be(Plus, &bytes))
+}
+
+fn encode_int(x: &BigInt) -> String {
+    hex::encode(x.to_bytes_be().1)
+}
+
+// A handler that panicked mid-update poisons the lock. We'd rather keep
+// serving whatever state it left behind than fail every request after it
+fn lock<H>(state: &Mutex<State<H>>) -> MutexGuard<'_, State<H>> {
+    state.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+struct State<H> {
+    srp: Srp6a<H>,
+    registry: UserRegistry,
+    sessions: Sessions<H>
+}
+
+impl<H: Hasher> State<H> {
+    fn register(&mut self, req: RegisterRequest) -> Reply {
+        let (salt, v) = match (hex::decode(&req.salt), decode_int(&req.verifier)) {
+            (Ok(salt), Some(v)) => (salt, v),
+            _ => return bad_request("Malformed salt or verifier")
+        };
+        if req.username.is_empty() {
+            return bad_request("Empty username");
+        }
+
+        let verifier = SrpVerifier { username: req.username.into_bytes(), salt, v };
+        match self.registry.register(verifier) {
+            Ok(()) => reply(&Empty {}, StatusCode::CREATED),
+            Err(RegistryError::UserExists) => error("Username taken", StatusCode::CONFLICT),
+            Err(RegistryError::Io(_)) => error("Couldn't save user", StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+
+    // Unlike RFC 5054's suggestion we don't fake a salt for unknown users,
+    // so this does tell you who has an account
+    fn start_login(&mut self, req: StartRequest) -> Reply {
+        let a_pub = match decode_int(&req.a) {
+            Some(a_pub) => a_pub,
+            None => return bad_request("Malformed A")
+        };
+        // Checked again with the proof, but no point handing out a session
+        if !self.srp.is_valid_public_key(&a_pub) {
+            return bad_request("Invalid public key");
+        }
+
+        let verifier = match self.registry.get(&req.username) {
+            Some(verifier) => verifier.clone(),
+            None => return error("Unknown user", StatusCode::NOT_FOUND)
+        };
+
+        let server = Srp6aServer::new(&self.srp, verifier);
+        let (salt, b_pub) = server.challenge();
+        let (salt, b) = (hex::encode(salt), encode_int(b_pub));
+        let session = self.sessions.start(&req.username, server, a_pub);
+
+        reply(&StartResponse { session, salt, b }, StatusCode::OK)
+    }
+
+    fn finish_login(&mut self, req: FinishRequest) -> Reply {
+        let m1 = match hex::decode(&req.m1) {
+            Ok(m1) => m1,
+            Err(_) => return bad_request("Malformed M1")
+        };
+
+        match self.sessions.finish(&req.session, &m1) {
+            Ok(m2) => reply(&FinishResponse { m2: hex::encode(m2) }, StatusCode::OK),
+            Err(e) => session_error(e)
+        }
+    }
+
+    fn whoami(&mut self, req: SessionRequest) -> Reply {
+        match self.sessions.authenticated(&req.session) {
+            Ok((username, _)) => reply(&WhoamiResponse { username: username.to_string() }, StatusCode::OK),
+            Err(e) => session_error(e)
+        }
+    }
+}
+
+pub struct SrpService<H> {
+    srp: Srp6a<H>,
+    registry: UserRegistry,
+    challenge_ttl: Duration,
+    session_ttl: Duration
+}
+
+impl<H: Hasher + Send + 'static> SrpService<H> {
+    pub fn new(srp: Srp6a<H>, registry: UserRegistry) -> SrpService<H> {
+        SrpService {
+            srp,
+            registry,
+            challenge_ttl: DEFAULT_CHALLENGE_TTL,
+            session_ttl: DEFAULT_SESSION_TTL
+        }
+    }
+
+    // How long a client has between /login/start and /login/finish
+    pub fn with_challenge_ttl(mut self, ttl: Duration) -> SrpService<H> {
+        self.challenge_ttl = ttl;
+        self
+    }
+
+    // How long a login lasts
+    pub fn with_session_ttl(mut self, ttl: Duration) -> SrpService<H> {
+        self.session_ttl = ttl;
+        self
+    }
+
+    pub fn serve(self) -> SrpAuthServer {
+        let state = Arc::new(Mutex::new(State {
+            srp: self.srp,
+            registry: self.registry,
+            sessions: Sessions::new(self.challenge_ttl, self.session_ttl)
+        }));
+
+        // Every handler is quick and synchronous, so one lock is plenty
+        let with_state = warp::any().map(move || state.clone());
+
+        let register = warp::path!("register")
+            .and(json_body())
+            .and(with_state.clone())
+            .map(|req, state: Arc<Mutex<State<H>>>| lock(&state).register(req));
+        let start_login = warp::path!("login" / "start")
+            .and(json_body())
+            .and(with_state.clone())
+            .map(|req, state: Arc<Mutex<State<H>>>| lock(&state).start_login(req));
+        let finish_login = warp::path!("login" / "finish")
+            .and(json_body())
+            .and(with_state.clone())
+            .map(|req, state: Arc<Mutex<State<H>>>| lock(&state).finish_login(req));
+        let whoami = warp::path!("whoami")
+            .and(json_body())
+            .and(with_state)
+            .map(|req, state: Arc<Mutex<State<H>>>| lock(&state).whoami(req));
+
+        let routes = warp::post()
+            .and(register.or(start_login).unify().or(finish_login).unify().or(whoami).unify())
+            .recover(handle_rejection);
+
+        let (addr_tx, addr_rx) = mpsc::channel();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+
+        let thread = thread::spawn(move || {
+            new_runtime().block_on(async move {
+                let (addr, server) = warp::serve(routes)
+                    .bind_with_graceful_shutdown(
+                        ([127, 0, 0, 1], 0),
+                        async {
+                            shutdown_rx.await.ok();
+                        }
+                    );
+
+                addr_tx.send(addr).unwrap();
+                server.await;
+            });
+        });
+
+        SrpAuthServer {
+            addr: addr_rx.recv().unwrap(),
+            shutdown: Some(shutdown_tx),
+            thread: Some(thread)
+        }
+    }
+}
+
+// Shuts the server down when dropped
+pub struct SrpAuthServer {
+    addr: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>
+}
+
+impl SrpAuthServer {
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+}
+
+impl Drop for SrpAuthServer {
+    fn drop(&mut self) {
+        if let Some(tx) = self.shutdown.take() {
+            let _ = tx.send(());
+        }
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,206 @@
+// Login sessions. Each one goes
+//
+//   start -> AwaitingProof --(good M1)--> Authenticated
+//                          --(bad M1)---> gone
+//
+// and expires if it sits in either state for too long. A client only gets
+// one go at the proof, so a session can't be used to guess passwords online.
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use num_bigint::BigInt;
+
+use crate::asymmetric::srp::SrpError;
+use crate::asymmetric::srp6a::Srp6aServer;
+use crate::hashing::hasher::Hasher;
+
+const SESSION_ID_LEN: usize = 16;
+
+#[derive(Debug, PartialEq)]
+pub enum SessionError {
+    // Never existed, or was thrown away after a failed proof or expiry
+    Unknown,
+    Expired,
+    // Proof sent for a session that's already logged in, or the other way
+    // round
+    WrongState,
+    Srp(SrpError)
+}
+
+enum State<H> {
+    AwaitingProof { server: Box<Srp6aServer<H>>, a_pub: BigInt },
+    Authenticated { key: Vec<u8> }
+}
+
+struct Session<H> {
+    username: String,
+    state: State<H>,
+    expires: Instant
+}
+
+pub struct Sessions<H> {
+    sessions: HashMap<String, Session<H>>,
+    // How long the client has to send M1
+    challenge_ttl: Duration,
+    // How long a login lasts once it succeeds
+    session_ttl: Duration
+}
+
+impl<H: Hasher> Sessions<H> {
+    pub fn new(challenge_ttl: Duration, session_ttl: Duration) -> Sessions<H> {
+        Sessions { sessions: HashMap::new(), challenge_ttl, session_ttl }
+    }
+
+    // Returns the new session's id. `server` must already have produced B
+    pub fn start(&mut self, username: &str, server: Srp6aServer<H>, a_pub: BigInt) -> String {
+        self.remove_expired();
+
+        let id = hex::encode(crate::rng::vec::rand_len(SESSION_ID_LEN));
+        self.sessions.insert(id.clone(), Session {
+            username: username.to_string(),
+            state: State::AwaitingProof { server: Box::new(server), a_pub },
+            expires: Instant::now() + self.challenge_ttl
+        });
+
+        id
+    }
+
+    // Checks M1 and returns M2
+    pub fn finish(&mut self, id: &str, m1: &[u8]) -> Result<Vec<u8>, SessionError> {
+        let session_ttl = self.session_ttl;
+        let session = self.live_session(id)?;
+
+        let res = match &mut session.state {
+            State::AwaitingProof { server, a_pub } => server.verify_client(a_pub, m1)
+                .map(|m2| (m2, server.session_key().unwrap().to_vec())),
+            State::Authenticated { .. } => return Err(SessionError::WrongState)
+        };
+
+        match res {
+            Ok((m2, key)) => {
+                session.state = State::Authenticated { key };
+                session.expires = Instant::now() + session_ttl;
+                Ok(m2)
+            },
+            Err(e) => {
+                self.sessions.remove(id);
+                Err(SessionError::Srp(e))
+            }
+        }
+    }
+
+    // The username and session key of a logged in session
+    pub fn authenticated(&mut self, id: &str) -> Result<(&str, &[u8]), SessionError> {
+        let session = self.live_session(id)?;
+
+        match &session.state {
+            State::Authenticated { key } => Ok((&session.username, key)),
+            State::AwaitingProof { .. } => Err(SessionError::WrongState)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    fn live_session(&mut self, id: &str) -> Result<&mut Session<H>, SessionError> {
+        let expired = match self.sessions.get(id) {
+            Some(session) => session.expires <= Instant::now(),
+            None => return Err(SessionError::Unknown)
+        };
+
+        if expired {
+            self.sessions.remove(id);
+            return Err(SessionError::Expired);
+        }
+
+        Ok(self.sessions.get_mut(id).unwrap())
+    }
+
+    fn remove_expired(&mut self) {
+        let now = Instant::now();
+        self.sessions.retain(|_, session| session.expires > now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use crate::asymmetric::srp6a::{Srp6a, Srp6aClient};
+    use crate::asymmetric::srp_groups::SrpGroup;
+    use crate::hashing::sha256::Sha256;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    // Runs the SRP exchange up to the point the client has M1
+    fn start(sessions: &mut Sessions<Sha256>, password: &[u8]) -> (String, Srp6aClient<Sha256>, Vec<u8>) {
+        let srp = Srp6a::<Sha256>::new(SrpGroup::rfc5054_1024());
+        let verifier = srp.verifier(b"alice", b"hunter2");
+        let salt = verifier.salt.clone();
+
+        let mut client = Srp6aClient::new(&srp, b"alice");
+        let server = Srp6aServer::new(&srp, verifier);
+        let b_pub = server.challenge().1.clone();
+        let id = sessions.start("alice", server, client.public_key().clone());
+
+        let m1 = client.process_challenge(password, &salt, &b_pub).unwrap();
+        (id, client, m1)
+    }
+
+    #[test]
+    fn test_login() {
+        let mut sessions = Sessions::new(TTL, TTL);
+        let (id, client, m1) = start(&mut sessions, b"hunter2");
+
+        assert_eq!(sessions.authenticated(&id).unwrap_err(), SessionError::WrongState);
+
+        let m2 = sessions.finish(&id, &m1).unwrap();
+        let key = client.verify_server(&m2).unwrap();
+        assert_eq!(sessions.authenticated(&id).unwrap(), ("alice", key));
+
+        // No second go at the proof
+        assert_eq!(sessions.finish(&id, &m1).unwrap_err(), SessionError::WrongState);
+    }
+
+    #[test]
+    fn test_bad_proof_ends_session() {
+        let mut sessions = Sessions::new(TTL, TTL);
+        let (id, _, m1) = start(&mut sessions, b"password");
+
+        assert_eq!(sessions.finish(&id, &m1).unwrap_err(), SessionError::Srp(SrpError::BadProof));
+        assert_eq!(sessions.finish(&id, &m1).unwrap_err(), SessionError::Unknown);
+        assert!(sessions.is_empty());
+    }
+
+    #[test]
+    fn test_expiry() {
+        // Long enough that the first session is still there when the
+        // second starts
+        let ttl = Duration::from_millis(200);
+        let mut sessions = Sessions::new(ttl, ttl);
+        let (slow, _, slow_m1) = start(&mut sessions, b"hunter2");
+        let (fast, _, fast_m1) = start(&mut sessions, b"hunter2");
+        sessions.finish(&fast, &fast_m1).unwrap();
+
+        thread::sleep(ttl * 2);
+
+        assert_eq!(sessions.finish(&slow, &slow_m1).unwrap_err(), SessionError::Expired);
+        assert_eq!(sessions.authenticated(&fast).unwrap_err(), SessionError::Expired);
+        assert!(sessions.is_empty());
+    }
+
+    #[test]
+    fn test_start_sweeps_expired() {
+        let mut sessions = Sessions::new(Duration::from_millis(20), TTL);
+        start(&mut sessions, b"hunter2");
+        thread::sleep(Duration::from_millis(50));
+
+        start(&mut sessions, b"hunter2");
+        assert_eq!(sessions.len(), 1);
+    }
+}
@@ -0,0 +1,146 @@
+// A deliberately weak Merkle-Damgard hash for the set 7 collision attacks.
+// The compression function encrypts the chaining value with AES under the
+// message block and keeps the top `state_bits` bits:
+//
+//   H' = AES-128(key = M, H || 0...) >> (128 - state_bits)
+//
+// With 16 to 32 bits of state a birthday collision takes a few hundred to a
+// few tens of thousands of calls, so the attacks run in tests. It's a
+// `Compression`, so everything generic over those runs on it.
+use std::convert::TryInto;
+
+use openssl::symm::Cipher;
+
+use crate::hashing::compression::Compression;
+use crate::hashing::hash_padding::HashPaddable;
+use crate::symmetric::aes::{aes_encrypt_nopad, AES_BLOCK_SIZE};
+
+pub const CHEAP_HASH_BLOCK_LEN_BYTES: usize = AES_BLOCK_SIZE;
+const CHEAP_HASH_LEN_FIELD_BYTES: usize = 8;
+const DEFAULT_IV: u32 = 0x6a09e667;
+
+// let f = CheapHash::new(16);
+// let g = CheapHash::new(32).with_iv(0xdeadbeef);
+// assert_ne!(f.hash(b"YELLOW SUBMARINE"), g.hash(b"YELLOW SUBMARINE"));
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CheapHash {
+    state_bits: u32,
+    iv: u32
+}
+
+impl CheapHash {
+    pub fn new(state_bits: u32) -> CheapHash {
+        assert!((16..=32).contains(&state_bits), "State has to be 16 to 32 bits");
+        CheapHash { state_bits, iv: 0 }.with_iv(DEFAULT_IV)
+    }
+
+    // Anything above state_bits is dropped
+    pub fn with_iv(mut self, iv: u32) -> CheapHash {
+        self.iv = iv & self.mask();
+        self
+    }
+
+    pub fn state_bits(&self) -> u32 {
+        self.state_bits
+    }
+
+    pub fn iv(&self) -> u32 {
+        self.iv
+    }
+
+    fn mask(&self) -> u32 {
+        (u64::MAX >> (64 - self.state_bits)) as u32
+    }
+
+    // The state as big endian bytes, just enough of them to hold it
+    pub fn digest(&self, message: &[u8]) -> Vec<u8> {
+        let len = (self.state_bits as usize).div_ceil(8);
+        self.hash(message).to_be_bytes()[4 - len..].to_vec()
+    }
+}
+
+impl Compression for CheapHash {
+    type State = u32;
+
+    fn block_len(&self) -> usize {
+        CHEAP_HASH_BLOCK_LEN_BYTES
+    }
+
+    fn iv(&self) -> u32 {
+        self.iv
+    }
+
+    fn compress(&self, state: u32, block: &[u8]) -> u32 {
+        assert_eq!(block.len(), CHEAP_HASH_BLOCK_LEN_BYTES, "Blocks are 16 bytes");
+
+        let mut plaintext = [0u8; AES_BLOCK_SIZE];
+        plaintext[..4].copy_from_slice(&state.to_be_bytes());
+
+        let ciphertext = aes_encrypt_nopad(block, &plaintext, &[], Cipher::aes_128_ecb());
+        let top = u32::from_be_bytes([ciphertext[0], ciphertext[1], ciphertext[2], ciphertext[3]]);
+        top >> (32 - self.state_bits)
+    }
+
+    fn padding(&self, message_len: usize) -> Vec<u8> {
+        let padded = vec![0u8; message_len].hashpad_with(
+            true,
+            CHEAP_HASH_BLOCK_LEN_BYTES,
+            CHEAP_HASH_LEN_FIELD_BYTES
+        );
+        padded[message_len..].to_vec()
+    }
+
+    fn state_to_bytes(&self, state: u32) -> Vec<u8> {
+        state.to_be_bytes().to_vec()
+    }
+
+    fn state_from_bytes(&self, bytes: &[u8]) -> u32 {
+        u32::from_be_bytes(bytes.try_into().unwrap()) & self.mask()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_size() {
+        for &bits in [16, 20, 24, 31, 32].iter() {
+            let hash = CheapHash::new(bits);
+            for i in 0..50u8 {
+                let h = hash.hash(&[i; 20]);
+                assert!(bits == 32 || h < 1 << bits);
+            }
+            assert_eq!(hash.digest(b"").len(), (bits as usize).div_ceil(8));
+        }
+    }
+
+    #[test]
+    fn test_padding() {
+        let hash = CheapHash::new(16);
+        for len in 0..40 {
+            let padding = hash.padding(len);
+            assert_eq!((len + padding.len()) % CHEAP_HASH_BLOCK_LEN_BYTES, 0);
+            assert_eq!(padding[0], 0x80);
+        }
+
+        // Padding is part of the hash, so these differ
+        assert_ne!(hash.hash(b""), hash.hash(&[0x80]));
+    }
+
+    #[test]
+    fn test_blocks_match_hash() {
+        let hash = CheapHash::new(24).with_iv(0x123456);
+        let message = b"YELLOW SUBMARINE and more".to_vec();
+
+        let mut padded = message.clone();
+        padded.extend(hash.padding(message.len()));
+        assert_eq!(hash.hash_blocks(hash.iv(), &padded), hash.hash(&message));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_too_big() {
+        CheapHash::new(33);
+    }
+}
@@ -0,0 +1,106 @@
+// A Merkle-Damgard hash seen as just its compression function, for the
+// attacks that work on chaining values (multicollisions, second preimages,
+// herding). `Hasher` hides the state, this is all state.
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use crate::hashing::hash_padding::md_padding;
+use crate::hashing::hasher::MdCore;
+
+pub trait Compression {
+    type State: Copy + Eq + Hash + Debug;
+
+    fn block_len(&self) -> usize;
+
+    fn iv(&self) -> Self::State;
+
+    fn compress(&self, state: Self::State, block: &[u8]) -> Self::State;
+
+    // What gets appended to a `message_len` byte message
+    fn padding(&self, message_len: usize) -> Vec<u8>;
+
+    fn state_to_bytes(&self, state: Self::State) -> Vec<u8>;
+
+    fn state_from_bytes(&self, bytes: &[u8]) -> Self::State;
+
+    // Whole blocks from `state`, no padding
+    fn hash_blocks(&self, state: Self::State, blocks: &[u8]) -> Self::State {
+        assert_eq!(blocks.len() % self.block_len(), 0, "Not a whole number of blocks");
+
+        blocks.chunks(self.block_len())
+            .fold(state, |state, block| self.compress(state, block))
+    }
+
+    fn hash(&self, message: &[u8]) -> Self::State {
+        let mut padded = message.to_vec();
+        padded.extend(self.padding(message.len()));
+        self.hash_blocks(self.iv(), &padded)
+    }
+}
+
+// Any of our real hashes, e.g. MdCompression::<Md4Core>::new()
+pub struct MdCompression<C> {
+    core: PhantomData<C>
+}
+
+impl<C: MdCore> MdCompression<C> {
+    pub fn new() -> MdCompression<C> {
+        MdCompression { core: PhantomData }
+    }
+}
+
+impl<C: MdCore> Compression for MdCompression<C> where C::State: Eq + Hash + Debug {
+    type State = C::State;
+
+    fn block_len(&self) -> usize {
+        C::BLOCK_SIZE
+    }
+
+    fn iv(&self) -> C::State {
+        C::initial_state()
+    }
+
+    fn compress(&self, mut state: C::State, block: &[u8]) -> C::State {
+        C::process_block(&mut state, block);
+        state
+    }
+
+    fn padding(&self, message_len: usize) -> Vec<u8> {
+        md_padding(message_len as u64, C::BIG_ENDIAN, C::BLOCK_SIZE, C::LEN_FIELD_BYTES)
+    }
+
+    fn state_to_bytes(&self, state: C::State) -> Vec<u8> {
+        C::state_to_bytes(&state)
+    }
+
+    fn state_from_bytes(&self, bytes: &[u8]) -> C::State {
+        C::state_from_bytes(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashing::md4::{md4, Md4Core};
+    use crate::hashing::sha256::{sha256, Sha256Core};
+
+    #[test]
+    fn test_md_compression_matches_hash() {
+        let md4_c = MdCompression::<Md4Core>::new();
+        let sha256_c = MdCompression::<Sha256Core>::new();
+
+        for len in [0, 1, 55, 56, 64, 200].iter() {
+            let message = crate::rng::vec::rand_len(*len);
+            assert_eq!(md4_c.state_to_bytes(md4_c.hash(&message)), md4(&message));
+            assert_eq!(sha256_c.state_to_bytes(sha256_c.hash(&message)), sha256(&message));
+        }
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let c = MdCompression::<Md4Core>::new();
+        let state = c.hash(b"YELLOW SUBMARINE");
+        assert_eq!(c.state_from_bytes(&c.state_to_bytes(state)), state);
+    }
+}
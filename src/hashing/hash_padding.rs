@@ -1,59 +1,128 @@
 pub const HASH_BLOCK_LEN_BYTES: usize = 64;
+// Width of the message length field for 64 byte block hashes
+pub const HASH_LEN_FIELD_BYTES: usize = 8;
 
 pub trait HashPaddable {
-    fn hashpad(&self, big_endian: bool) -> Vec<u8>;
-}
+    // Merkle-Damgard padding: a 1 bit, zeros, and the message length in bits
+    // in the last `len_field_bytes` bytes of the last `block_len` byte block
+    fn hashpad_with(&self, big_endian: bool, block_len: usize, len_field_bytes: usize) -> Vec<u8>;
+
+    // Same, but only the first `bit_len` bits are message (most significant
+    // bit first within each byte). Bits past that in the last byte are ignored
+    fn hashpad_bits_with(&self, bit_len: u64, big_endian: bool, block_len: usize, len_field_bytes: usize) -> Vec<u8>;
 
-impl HashPaddable for Vec<u8> {
     // big_endian specifies if the length is encoded in big or little endian
     fn hashpad(&self, big_endian: bool) -> Vec<u8> {
-        let mut res = self.to_vec();
-        let byteslength = res.len();
+        self.hashpad_with(big_endian, HASH_BLOCK_LEN_BYTES, HASH_LEN_FIELD_BYTES)
+    }
 
-        // Add 9 bytes for a u64, and byte. The byte=0b10000000 as defined in the spec
-        // and appended to res. The other 8 bytes are for a u64 (length field, see spec)
-        let num_non_zeros = byteslength + 9;
+    fn hashpad_bits(&self, bit_len: u64, big_endian: bool) -> Vec<u8> {
+        self.hashpad_bits_with(bit_len, big_endian, HASH_BLOCK_LEN_BYTES, HASH_LEN_FIELD_BYTES)
+    }
+}
 
-        // 64 bytes is 512 bits
-        let mut num_new_zeros = HASH_BLOCK_LEN_BYTES - (num_non_zeros) % HASH_BLOCK_LEN_BYTES;
-        if num_new_zeros == HASH_BLOCK_LEN_BYTES {
-            num_new_zeros = 0;
-        }
+// Just the padding that goes after a `message_len` byte message. This is
+// also the glue a length extension has to forge between the original
+// message and the appended data.
+pub fn md_padding(message_len: u64, big_endian: bool, block_len: usize, len_field_bytes: usize) -> Vec<u8> {
+    md_padding_bits(message_len * 8, 0, big_endian, block_len, len_field_bytes)
+}
+
+// The padding after the whole bytes of a `message_bits` bit message. When
+// that isn't a multiple of 8 the leftover bits (the top `message_bits % 8`
+// bits of `last_byte`) share the first padding byte with the 1 bit.
+pub fn md_padding_bits(
+    message_bits: u64,
+    last_byte: u8,
+    big_endian: bool,
+    block_len: usize,
+    len_field_bytes: usize
+) -> Vec<u8> {
+    assert!(len_field_bytes <= 16, "Length fields are at most 128 bits");
 
-        res.resize(num_new_zeros + num_non_zeros, 0);
+    // One byte for the 1 bit (and any leftover message bits), then zeros
+    // up to the length field
+    let used = (message_bits / 8 % block_len as u64) as usize;
+    let mut num_new_zeros = block_len - (used + 1 + len_field_bytes) % block_len;
+    if num_new_zeros == block_len {
+        num_new_zeros = 0;
+    }
 
-        res[byteslength] = 0x80;
+    let mut res = vec![0u8; 1 + num_new_zeros + len_field_bytes];
+    let leftover_bits = (message_bits % 8) as u32;
+    let leftover_mask = (0xff00u16 >> leftover_bits) as u8;
+    res[0] = (last_byte & leftover_mask) | (0x80 >> leftover_bits);
 
-        // bitlength is the size of the original message in bits
-        // Called `l` in the spec
-        let bitlength = (byteslength * 8) as u64;
+    // bitlength is the size of the original message in bits
+    // Called `l` in the spec
+    let bitlength = message_bits as u128;
 
-        let eight_from_end = res.len() - 8;
-        res[eight_from_end..].copy_from_slice(
-            &if big_endian {
-                bitlength.to_be_bytes()
-            } else {
-                bitlength.to_le_bytes()
-            }
-        );
+    let len_field_start = res.len() - len_field_bytes;
+    res[len_field_start..].copy_from_slice(
+        &if big_endian {
+            bitlength.to_be_bytes()[16 - len_field_bytes..].to_vec()
+        } else {
+            bitlength.to_le_bytes()[..len_field_bytes].to_vec()
+        }
+    );
 
+    res
+}
+
+impl HashPaddable for Vec<u8> {
+    fn hashpad_with(&self, big_endian: bool, block_len: usize, len_field_bytes: usize) -> Vec<u8> {
+        let mut res = self.to_vec();
+        res.extend(md_padding(self.len() as u64, big_endian, block_len, len_field_bytes));
+        res
+    }
+
+    fn hashpad_bits_with(&self, bit_len: u64, big_endian: bool, block_len: usize, len_field_bytes: usize) -> Vec<u8> {
+        assert!(bit_len <= self.len() as u64 * 8, "Not enough bytes for {} bits", bit_len);
+
+        let whole_bytes = (bit_len / 8) as usize;
+        let last_byte = self.get(whole_bytes).cloned().unwrap_or(0);
+        let mut res = self[..whole_bytes].to_vec();
+        res.extend(md_padding_bits(bit_len, last_byte, big_endian, block_len, len_field_bytes));
         res
     }
 }
 
 impl HashPaddable for &[u8] {
-    fn hashpad(&self, big_endian: bool) -> Vec<u8> {
-        self.to_vec().hashpad(big_endian)
+    fn hashpad_with(&self, big_endian: bool, block_len: usize, len_field_bytes: usize) -> Vec<u8> {
+        self.to_vec().hashpad_with(big_endian, block_len, len_field_bytes)
+    }
+
+    fn hashpad_bits_with(&self, bit_len: u64, big_endian: bool, block_len: usize, len_field_bytes: usize) -> Vec<u8> {
+        self.to_vec().hashpad_bits_with(bit_len, big_endian, block_len, len_field_bytes)
     }
 }
 
 impl HashPaddable for &Vec::<u8> {
-    fn hashpad(&self, big_endian: bool) -> Vec<u8> {
-        self.to_vec().hashpad(big_endian)
+    fn hashpad_with(&self, big_endian: bool, block_len: usize, len_field_bytes: usize) -> Vec<u8> {
+        self.to_vec().hashpad_with(big_endian, block_len, len_field_bytes)
     }
-}
 
+    fn hashpad_bits_with(&self, bit_len: u64, big_endian: bool, block_len: usize, len_field_bytes: usize) -> Vec<u8> {
+        self.to_vec().hashpad_bits_with(bit_len, big_endian, block_len, len_field_bytes)
+    }
+}
 
 pub fn s(n: usize, x: u32) -> u32 {
     (x << n) | (x >> (32 - n))
 }
+
+// Messages the bit-oriented hash tests share, long enough to end on either
+// side of where the length field stops fitting in the last block. Only the
+// first Len bits of each are hashed
+#[cfg(test)]
+pub(crate) mod bit_messages {
+    pub(crate) const MSG_447: &str = "88ba7cf2a82ba2722b4eb132b1a3716023a8db2d20abdc0274ac88de3666e28a\
+        eb408a6b3ed23cfeda40ff992fbc4c03b704d2f272533246";
+    pub(crate) const MSG_449: &str = "83c07dac1118287986d7eed6c8f02dce3eb91a0922e3049da2b1fe2d9a51dc64\
+        392d05514319490f9fb6085b1b7b9d0d4cfcceddb24e720d80";
+    pub(crate) const MSG_511: &str = "abc1a6164af591403aa1c2823e32220e3699f8ee80967f8772657f987e19ff5e\
+        7b3a8dc200903350df74f8c8189cfd3c5055ab640f7662fc93ef3b6e13b60f62";
+    pub(crate) const MSG_1003: &str = "2c724a36e13d83ff6c57b8c61d342bb8cf0c16c1f3a0d83cf6ec7ee93d3e7c4f\
+        04f29499c6c14e46136fc9c48cacbead9fb09df24f3624abdcd0f20bdb6b489ce8a910aa7d517db401bd7e31cddbab\
+        afe790e4635bdbc8eba88ebfda1cd1af7d3215b2e91eb3f55a8bd0fd86660f9e2cae24bcbb1cbb2406e02095ca3b40";
+}
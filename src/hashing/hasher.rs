@@ -0,0 +1,273 @@
+// A streaming interface over all the hashes in this module:
+//
+// let mut h = Sha1::new();
+// h.update(b"YELLOW ");
+// h.update(b"SUBMARINE");
+// let digest = h.finalize();
+//
+// Every one of them is a Merkle-Damgard construction, so the buffering and
+// padding lives in `MdHasher` once and each hash only provides its
+// compression function as an `MdCore`.
+use crate::hashing::hash_padding::{md_padding, md_padding_bits};
+
+pub trait Hasher {
+    // Both in bytes
+    const BLOCK_SIZE: usize;
+    const OUTPUT_SIZE: usize;
+
+    fn new() -> Self;
+
+    fn update(&mut self, data: &[u8]);
+
+    // Pads, returns the digest and leaves the hasher ready for a new message
+    fn finalize(&mut self) -> Vec<u8>;
+
+    // Throws away everything hashed so far
+    fn reset(&mut self);
+
+    // Picks up hashing where `digest` left off, as if `processed_len` bytes
+    // (message and padding, so a whole number of blocks) had already gone in.
+    // This is all a length extension attack needs.
+    fn from_state(digest: &[u8], processed_len: u64) -> Self;
+
+    // The padding `finalize` appends to a `message_len` byte message
+    fn padding(message_len: u64) -> Vec<u8>;
+
+    fn digest(data: &[u8]) -> Vec<u8> where Self: Sized {
+        let mut hasher = Self::new();
+        hasher.update(data);
+        hasher.finalize()
+    }
+}
+
+// What makes one Merkle-Damgard hash different from the next
+pub trait MdCore {
+    type State: Copy;
+
+    const BLOCK_SIZE: usize;
+    const OUTPUT_SIZE: usize;
+    const LEN_FIELD_BYTES: usize;
+    const BIG_ENDIAN: bool;
+
+    fn initial_state() -> Self::State;
+
+    fn process_block(state: &mut Self::State, block: &[u8]);
+
+    // The whole state, before any truncation to OUTPUT_SIZE
+    fn state_to_bytes(state: &Self::State) -> Vec<u8>;
+
+    fn state_from_bytes(bytes: &[u8]) -> Self::State;
+}
+
+pub struct MdHasher<C: MdCore> {
+    state: C::State,
+    buffer: Vec<u8>,
+    // Total bytes hashed, including whatever is sitting in the buffer
+    len: u64
+}
+
+impl<C: MdCore> MdHasher<C> {
+    fn process_buffered_blocks(&mut self) {
+        let full = self.buffer.len() - self.buffer.len() % C::BLOCK_SIZE;
+
+        for block in self.buffer[..full].chunks(C::BLOCK_SIZE) {
+            C::process_block(&mut self.state, block);
+        }
+
+        self.buffer.drain(..full);
+    }
+
+    fn finish_with(&mut self, padding: Vec<u8>) -> Vec<u8> {
+        self.buffer.extend(padding);
+        self.process_buffered_blocks();
+
+        let mut res = C::state_to_bytes(&self.state);
+        res.truncate(C::OUTPUT_SIZE);
+
+        self.reset();
+        res
+    }
+
+    // Like `finalize`, but the message ends with the top `bits` (0 to 7)
+    // bits of `last_byte` after everything passed to `update`
+    pub fn finalize_bits(&mut self, last_byte: u8, bits: u32) -> Vec<u8> {
+        assert!(bits < 8, "Whole bytes go through update");
+        let padding = md_padding_bits(
+            self.len * 8 + bits as u64,
+            last_byte,
+            C::BIG_ENDIAN,
+            C::BLOCK_SIZE,
+            C::LEN_FIELD_BYTES
+        );
+        self.finish_with(padding)
+    }
+
+    // The digest of the first `bit_len` bits of `data`
+    pub fn digest_bits(data: &[u8], bit_len: u64) -> Vec<u8> {
+        assert!(bit_len <= data.len() as u64 * 8, "Not enough bytes for {} bits", bit_len);
+
+        let whole_bytes = (bit_len / 8) as usize;
+        let mut hasher = Self::new();
+        hasher.update(&data[..whole_bytes]);
+        hasher.finalize_bits(data.get(whole_bytes).cloned().unwrap_or(0), (bit_len % 8) as u32)
+    }
+}
+
+impl<C: MdCore> Hasher for MdHasher<C> {
+    const BLOCK_SIZE: usize = C::BLOCK_SIZE;
+    const OUTPUT_SIZE: usize = C::OUTPUT_SIZE;
+
+    fn new() -> Self {
+        MdHasher {
+            state: C::initial_state(),
+            buffer: Vec::with_capacity(C::BLOCK_SIZE),
+            len: 0
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.len += data.len() as u64;
+
+        // Top up a partial block first, then hash straight out of `data`
+        // without copying it
+        if !self.buffer.is_empty() {
+            let take = (C::BLOCK_SIZE - self.buffer.len()).min(data.len());
+            self.buffer.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            self.process_buffered_blocks();
+        }
+
+        let full = data.len() - data.len() % C::BLOCK_SIZE;
+        for block in data[..full].chunks(C::BLOCK_SIZE) {
+            C::process_block(&mut self.state, block);
+        }
+
+        self.buffer.extend_from_slice(&data[full..]);
+    }
+
+    fn finalize(&mut self) -> Vec<u8> {
+        let padding = Self::padding(self.len);
+        self.finish_with(padding)
+    }
+
+    fn reset(&mut self) {
+        self.state = C::initial_state();
+        self.buffer.clear();
+        self.len = 0;
+    }
+
+    fn from_state(digest: &[u8], processed_len: u64) -> Self {
+        // Truncated variants like SHA-224 threw part of the state away
+        assert_eq!(
+            digest.len(),
+            C::state_to_bytes(&C::initial_state()).len(),
+            "Can only resume from an untruncated digest"
+        );
+        assert_eq!(
+            processed_len % C::BLOCK_SIZE as u64, 0,
+            "Must resume on a block boundary"
+        );
+
+        MdHasher {
+            state: C::state_from_bytes(digest),
+            buffer: Vec::with_capacity(C::BLOCK_SIZE),
+            len: processed_len
+        }
+    }
+
+    fn padding(message_len: u64) -> Vec<u8> {
+        md_padding(message_len, C::BIG_ENDIAN, C::BLOCK_SIZE, C::LEN_FIELD_BYTES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashing::md4::Md4;
+    use crate::hashing::md5::Md5;
+    use crate::hashing::sha1::Sha1;
+    use crate::hashing::sha256::{Sha224, Sha256};
+    use crate::hashing::sha512::{Sha384, Sha512};
+    use rand::Rng;
+
+    fn check_chunked<H: Hasher>() {
+        let mut rng = rand::thread_rng();
+        let mut hasher = H::new();
+
+        for _ in 0..50 {
+            let data = crate::rng::vec::rand_len_range(0, 600);
+
+            // Feed it in random sized pieces, including empty ones
+            let mut rest = &data[..];
+            while !rest.is_empty() {
+                let n = rng.gen_range(0, rest.len() + 1);
+                hasher.update(&rest[..n]);
+                rest = &rest[n..];
+            }
+
+            assert_eq!(hasher.finalize(), H::digest(&data));
+        }
+    }
+
+    #[test]
+    fn test_chunked_updates() {
+        check_chunked::<Sha1>();
+        check_chunked::<Md4>();
+        check_chunked::<Md5>();
+        check_chunked::<Sha256>();
+        check_chunked::<Sha224>();
+        check_chunked::<Sha512>();
+        check_chunked::<Sha384>();
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut hasher = Sha1::new();
+        hasher.update(b"garbage");
+        hasher.reset();
+        hasher.update(b"abc");
+        assert_eq!(hasher.finalize(), Sha1::digest(b"abc"));
+    }
+
+    fn check_resume<H: Hasher>() {
+        let message = b"comment1=cooking%20MCs;userdata=foo".to_vec();
+        let extra = b";admin=true";
+
+        let mut glued = message.clone();
+        glued.extend(H::padding(message.len() as u64));
+
+        let mut resumed = H::from_state(&H::digest(&message), glued.len() as u64);
+        resumed.update(extra);
+
+        glued.extend_from_slice(extra);
+        assert_eq!(resumed.finalize(), H::digest(&glued));
+    }
+
+    #[test]
+    fn test_from_state() {
+        check_resume::<Sha1>();
+        check_resume::<Md4>();
+        check_resume::<Md5>();
+        check_resume::<Sha256>();
+        check_resume::<Sha512>();
+    }
+
+    #[test]
+    fn test_finalize_bits() {
+        let data = crate::rng::vec::rand_len(130);
+        let mut hasher = Sha1::new();
+        hasher.update(&data[..70]);
+        hasher.update(&data[70..129]);
+        assert_eq!(hasher.finalize_bits(data[129], 3), Sha1::digest_bits(&data, 129 * 8 + 3));
+
+        // No leftover bits is plain finalize
+        hasher.update(&data);
+        assert_eq!(hasher.finalize_bits(0xff, 0), Sha1::digest(&data));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_from_truncated_state() {
+        Sha224::from_state(&Sha224::digest(b"abc"), 64);
+    }
+}
@@ -1,22 +1,90 @@
-pub fn hmac(key: &[u8], message: &[u8], hash_fn: fn (&Vec<u8>) -> Vec<u8>, blocksize: usize) -> Vec<u8> {
-    let mut new_key = key.to_vec();
+// HMAC from RFC 2104 over any of our hashes:
+//
+// let mut mac = Hmac::<Sha256>::new(key);
+// mac.update(message);
+// let ok = mac.verify(tag);
+use crate::hashing::hasher::Hasher;
 
-    if new_key.len() > blocksize {
-        new_key = hash_fn(&new_key);
+pub struct Hmac<H> {
+    inner: H,
+    i_key_pad: Vec<u8>,
+    o_key_pad: Vec<u8>,
+    tag_len: usize
+}
+
+impl<H: Hasher> Hmac<H> {
+    pub fn new(key: &[u8]) -> Hmac<H> {
+        let mut new_key = key.to_vec();
+
+        if new_key.len() > H::BLOCK_SIZE {
+            new_key = H::digest(&new_key);
+        }
+
+        if new_key.len() < H::BLOCK_SIZE {
+            new_key.resize(H::BLOCK_SIZE, 0);
+        }
+
+        let i_key_pad: Vec<u8> = new_key.iter().map(|&x| x ^ 0x36).collect();
+        let o_key_pad: Vec<u8> = new_key.iter().map(|&x| x ^ 0x5C).collect();
+
+        let mut inner = H::new();
+        inner.update(&i_key_pad);
+
+        Hmac {
+            inner,
+            i_key_pad,
+            o_key_pad,
+            tag_len: H::OUTPUT_SIZE
+        }
+    }
+
+    // Only keep the leftmost `tag_len` bytes of the tag, e.g. HMAC-SHA1-96
+    pub fn with_tag_len(mut self, tag_len: usize) -> Hmac<H> {
+        assert!(tag_len > 0 && tag_len <= H::OUTPUT_SIZE, "Tag length out of range");
+        self.tag_len = tag_len;
+        self
     }
 
-    if new_key.len() < blocksize {
-        new_key.resize(blocksize, 0);
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
     }
 
-    let mut o_key_pad: Vec<u8> = new_key.iter().map(|&x| x ^ 0x5C).collect();
-    let mut i_key_pad: Vec<u8> = new_key.iter().map(|&x| x ^ 0x36).collect();
+    // Returns the tag and starts over for a new message under the same key
+    pub fn finalize(&mut self) -> Vec<u8> {
+        let inner_hash = self.inner.finalize();
+        self.inner.update(&self.i_key_pad);
 
-    i_key_pad.extend_from_slice(message);
+        let mut outer = H::new();
+        outer.update(&self.o_key_pad);
+        outer.update(&inner_hash);
 
-    let res = hash_fn(&i_key_pad);
-    o_key_pad.extend_from_slice(&res);
-    hash_fn(&o_key_pad)
+        let mut tag = outer.finalize();
+        tag.truncate(self.tag_len);
+        tag
+    }
+
+    // A tag of any other length than ours is rejected, otherwise an attacker
+    // could just send a single byte and guess it
+    pub fn verify(&mut self, tag: &[u8]) -> bool {
+        constant_time_eq(&self.finalize(), tag)
+    }
+}
+
+pub fn hmac<H: Hasher>(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<H>::new(key);
+    mac.update(message);
+    mac.finalize()
+}
+
+// Looks at every byte no matter where the first difference is, so the time
+// taken only depends on the length
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let diff = a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y));
+    std::hint::black_box(diff) == 0
 }
 
 #[cfg(test)]
@@ -25,12 +93,15 @@ mod tests {
     use openssl::hash::MessageDigest;
     use openssl::pkey::PKey;
     use openssl::sign::Signer;
-    use crate::hashing::sha1::*;
-    use crate::hashing::hash_padding::HASH_BLOCK_LEN_BYTES;
+    use crate::hashing::md4::Md4;
+    use crate::hashing::md5::Md5;
+    use crate::hashing::sha1::Sha1;
+    use crate::hashing::sha256::{Sha224, Sha256};
+    use crate::hashing::sha512::{Sha384, Sha512, Sha512Trunc224, Sha512Trunc256};
 
-    fn t_hmac_sha1(key: &[u8], message: &[u8]) -> Vec<u8>  {
+    fn t_hmac(key: &[u8], message: &[u8], digest: MessageDigest) -> Vec<u8>  {
         let new_key = PKey::hmac(key).unwrap();
-        let mut signer = Signer::new(MessageDigest::sha1(), &new_key).unwrap();
+        let mut signer = Signer::new(digest, &new_key).unwrap();
         signer.update(message).unwrap();
         signer.sign_to_vec().unwrap()
     }
@@ -41,14 +112,276 @@ mod tests {
             let data = crate::rng::vec::rand_len(i);
 
             let key = crate::rng::vec::rand_len_range(0, 512);
+            // Newer OpenSSL won't make an HMAC key out of nothing. Short keys
+            // get zero padded to a block, so a block of zeros is the same key
+            let reference_key = if key.is_empty() { vec![0u8; Sha1::BLOCK_SIZE] } else { key.clone() };
 
             assert_eq!(
-                t_hmac_sha1(&key, &data),
-                hmac(&key, &data, sha1, HASH_BLOCK_LEN_BYTES),
+                t_hmac(&reference_key, &data, MessageDigest::sha1()),
+                hmac::<Sha1>(&key, &data),
                 "\ndata {}\nkey {}",
                 data.iter().map(|x| format!("{:#04x}, ", x)).collect::<String>(),
                 key.iter().map(|x| format!("{:#04x}, ", x)).collect::<String>()
             );
         }
     }
+
+    #[test]
+    fn test_sha1_hmac_empty_key() {
+        // Python's hmac.new(b"", data, hashlib.sha1)
+        assert_eq!(hex::encode(hmac::<Sha1>(b"", b"")), "fbdb1d1b18aa6c08324b7d64b71fb76370690e1d");
+        assert_eq!(
+            hex::encode(hmac::<Sha1>(b"", b"The quick brown fox jumps over the lazy dog")),
+            "2ba7f707ad5f187c412de3106583c3111d668de8"
+        );
+    }
+
+    #[test]
+    fn test_sha256_hmac() {
+        for i in 0..200 {
+            let data = crate::rng::vec::rand_len(i);
+            let key = crate::rng::vec::rand_len_range(1, 512);
+
+            assert_eq!(
+                t_hmac(&key, &data, MessageDigest::sha256()),
+                hmac::<Sha256>(&key, &data)
+            );
+        }
+    }
+
+    #[test]
+    fn test_sha512_hmac() {
+        // Keys between 64 and 128 bytes are where a wrong block size would show
+        for i in 0..200 {
+            let data = crate::rng::vec::rand_len(i);
+            let key = crate::rng::vec::rand_len_range(1, 256);
+
+            assert_eq!(
+                t_hmac(&key, &data, MessageDigest::sha512()),
+                hmac::<Sha512>(&key, &data)
+            );
+            assert_eq!(
+                t_hmac(&key, &data, MessageDigest::sha384()),
+                hmac::<Sha384>(&key, &data)
+            );
+        }
+    }
+
+    // (key, data) for the seven test cases of RFC 2202 and RFC 4231. Keys are
+    // (byte, length) since most of them are one byte repeated. Test case 4
+    // uses 0x01..=0x19 and is special cased
+    const RFC_2202_MD5: [((u8, usize), &[u8]); 7] = [
+        ((0x0b, 16), b"Hi There"),
+        ((0, 0), b"what do ya want for nothing?"),
+        ((0xaa, 16), &[0xdd; 50]),
+        ((0, 0), &[0xcd; 50]),
+        ((0x0c, 16), b"Test With Truncation"),
+        ((0xaa, 80), b"Test Using Larger Than Block-Size Key - Hash Key First"),
+        ((0xaa, 80), b"Test Using Larger Than Block-Size Key and Larger Than One Block-Size Data")
+    ];
+
+    const RFC_2202_SHA1: [((u8, usize), &[u8]); 7] = [
+        ((0x0b, 20), b"Hi There"),
+        ((0, 0), b"what do ya want for nothing?"),
+        ((0xaa, 20), &[0xdd; 50]),
+        ((0, 0), &[0xcd; 50]),
+        ((0x0c, 20), b"Test With Truncation"),
+        ((0xaa, 80), b"Test Using Larger Than Block-Size Key - Hash Key First"),
+        ((0xaa, 80), b"Test Using Larger Than Block-Size Key and Larger Than One Block-Size Data")
+    ];
+
+    const RFC_4231: [((u8, usize), &[u8]); 7] = [
+        ((0x0b, 20), b"Hi There"),
+        ((0, 0), b"what do ya want for nothing?"),
+        ((0xaa, 20), &[0xdd; 50]),
+        ((0, 0), &[0xcd; 50]),
+        ((0x0c, 20), b"Test With Truncation"),
+        ((0xaa, 131), b"Test Using Larger Than Block-Size Key - Hash Key First"),
+        ((0xaa, 131), b"This is a test using a larger than block-size key and a larger than block-size data. \
+The key needs to be hashed before being used by the HMAC algorithm.")
+    ];
+
+    fn rfc_key(case: usize, (byte, len): (u8, usize)) -> Vec<u8> {
+        match case {
+            1 => b"Jefe".to_vec(),
+            3 => (0x01..=0x19).collect(),
+            _ => vec![byte; len]
+        }
+    }
+
+    // Test case 5 is truncated, and we tell from the length of the expected tag
+    fn check_vectors<H: Hasher>(cases: &[((u8, usize), &[u8]); 7], expected: [&str; 7]) {
+        for (i, ((key, data), tag)) in cases.iter().zip(expected.iter()).enumerate() {
+            let tag = hex::decode(tag).unwrap();
+            let mut mac = Hmac::<H>::new(&rfc_key(i, *key)).with_tag_len(tag.len());
+
+            mac.update(data);
+            assert_eq!(mac.finalize(), tag, "test case {}", i + 1);
+
+            mac.update(data);
+            assert!(mac.verify(&tag), "test case {}", i + 1);
+        }
+    }
+
+    #[test]
+    fn test_rfc_2202() {
+        check_vectors::<Md5>(&RFC_2202_MD5, [
+            "9294727a3638bb1c13f48ef8158bfc9d",
+            "750c783e6ab0b503eaa86e310a5db738",
+            "56be34521d144c88dbb8c733f0e8b3f6",
+            "697eaf0aca3a3aea3a75164746ffaa79",
+            "56461ef2342edc00f9bab995",
+            "6b1ab7fe4bd7bf8f0b62e6ce61b9d0cd",
+            "6f630fad67cda0ee1fb1f562db3aa53e"
+        ]);
+
+        check_vectors::<Sha1>(&RFC_2202_SHA1, [
+            "b617318655057264e28bc0b6fb378c8ef146be00",
+            "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79",
+            "125d7342b9ac11cd91a39af48aa17b4f63f175d3",
+            "4c9007f4026250c6bc8414f9bf50c86c2d7235da",
+            "4c1a03424b55e07fe7f27be1",
+            "aa4ae5e15272d00e95705637ce8a3b55ed402112",
+            "e8e99d0f45237d786d6bbaa7965c7808bbff1a91"
+        ]);
+    }
+
+    #[test]
+    fn test_rfc_4231() {
+        check_vectors::<Sha224>(&RFC_4231, [
+            "896fb1128abbdf196832107cd49df33f47b4b1169912ba4f53684b22",
+            "a30e01098bc6dbbf45690f3a7e9e6d0f8bbea2a39e6148008fd05e44",
+            "7fb3cb3588c6c1f6ffa9694d7d6ad2649365b0c1f65d69d1ec8333ea",
+            "6c11506874013cac6a2abc1bb382627cec6a90d86efc012de7afec5a",
+            "0e2aea68a90c8d37c988bcdb9fca6fa8",
+            "95e9a0db962095adaebe9b2d6f0dbce2d499f112f2d2b7273fa6870e",
+            "3a854166ac5d9f023f54d517d0b39dbd946770db9c2b95c9f6f565d1"
+        ]);
+
+        check_vectors::<Sha256>(&RFC_4231, [
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+            "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+            "a3b6167473100ee06e0c796c2955552b",
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2"
+        ]);
+
+        check_vectors::<Sha384>(&RFC_4231, [
+            "afd03944d84895626b0825f4ab46907f15f9dadbe4101ec682aa034c7cebc59cfaea9ea9076ede7f4af152e8b2fa9cb6",
+            "af45d2e376484031617f78d2b58a6b1b9c7ef464f5a01b47e42ec3736322445e8e2240ca5e69e2c78b3239ecfab21649",
+            "88062608d3e6ad8a0aa2ace014c8a86f0aa635d947ac9febe83ef4e55966144b2a5ab39dc13814b94e3ab6e101a34f27",
+            "3e8a69b7783c25851933ab6290af6ca77a9981480850009cc5577c6e1f573b4e6801dd23c4a7d679ccf8a386c674cffb",
+            "3abf34c3503b2a23a46efc619baef897",
+            "4ece084485813e9088d2c63a041bc5b44f9ef1012a2b588f3cd11f05033ac4c60c2ef6ab4030fe8296248df163f44952",
+            "6617178e941f020d351e2f254e8fd32c602420feb0b8fb9adccebb82461e99c5a678cc31e799176d3860e6110c46523e"
+        ]);
+
+        check_vectors::<Sha512>(&RFC_4231, [
+            "87aa7cdea5ef619d4ff0b4241a1d6cb02379f4e2ce4ec2787ad0b30545e17cdedaa833b7d6b8a702038b274eaea3f4e4be9d914eeb61f1702e696c203a126854",
+            "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737",
+            "fa73b0089d56a284efb0f0756c890be9b1b5dbdd8ee81a3655f83e33b2279d39bf3e848279a722c806b485a47e67c807b946a337bee8942674278859e13292fb",
+            "b0ba465637458c6990e5a8c5f61d4af7e576d97ff94b872de76f8050361ee3dba91ca5c11aa25eb4d679275cc5788063a5f19741120c4f2de2adebeb10a298dd",
+            "415fad6271580a531d4179bc891d87a6",
+            "80b24263c7c1a3ebb71493c1dd7be8b49b46d1f41b4aeec1121b013783f8f3526b56d037e05f2598bd0fd2215d6a1e5295e64f73f63f0aec8b915a985d786598",
+            "e37b6a775dc87dbaa4dfa9f96e5e3ffddebd71f8867289865df5a32d20cdc944b6022cac3c4982b10d5eeb55c3e4de15134676fb6de0446065c97440fa8c6a58"
+        ]);
+    }
+
+    // The RFCs don't cover these, so the expected tags come from Python's hmac
+    // module run on the RFC 4231 inputs
+    #[test]
+    fn test_sha512_t_vectors() {
+        check_vectors::<Sha512Trunc224>(&RFC_4231, [
+            "b244ba01307c0e7a8ccaad13b1067a4cf6b961fe0c6a20bda3d92039",
+            "4a530b31a79ebcce36916546317c45f247d83241dfb818fd37254bde",
+            "db34ea525c2c216ee5a6ccb6608bea870bbef12fd9b96a5109e2b6fc",
+            "c2391863cda465c6828af06ac5d4b72d0b792109952da530e11a0d26",
+            "1df8eae8baeedd4eddfb555ec0ba768f",
+            "29bef8ce88b54d4226c3c7718ea9e32ace2429026f089e38cea9aeda",
+            "82a9619b47af0cea73a8b9741355ce902d807ad87ee9078522a246e1"
+        ]);
+
+        check_vectors::<Sha512Trunc256>(&RFC_4231, [
+            "9f9126c3d9c3c330d760425ca8a217e31feae31bfe70196ff81642b868402eab",
+            "6df7b24630d5ccb2ee335407081a87188c221489768fa2020513b2d593359456",
+            "229006391d66c8ecddf43ba5cf8f83530ef221a4e9401840d1bead5137c8a2ea",
+            "36d60c8aa1d0be856e10804cf836e821e8733cbafeae87630589fd0b9b0a2f4c",
+            "337f526924766971bf72b82ad19c2c82",
+            "87123c45f7c537a404f8f47cdbedda1fc9bec60eeb971982ce7ef10e774e6539",
+            "6ea83f8e7315072c0bdaa33b93a26fc1659974637a9db8a887d06c05a7f35a66"
+        ]);
+    }
+
+    // No published vectors for HMAC-MD4 either, so build it out of the md4
+    // crate instead
+    fn t_hmac_md4(key: &[u8], message: &[u8]) -> Vec<u8> {
+        use md4::{Digest, Md4 as RefMd4};
+
+        let mut key = if key.len() > 64 { RefMd4::digest(key).to_vec() } else { key.to_vec() };
+        key.resize(64, 0);
+
+        let mut inner: Vec<u8> = key.iter().map(|x| x ^ 0x36).collect();
+        inner.extend_from_slice(message);
+        let mut outer: Vec<u8> = key.iter().map(|x| x ^ 0x5c).collect();
+        outer.extend_from_slice(&RefMd4::digest(&inner));
+        RefMd4::digest(&outer).to_vec()
+    }
+
+    #[test]
+    fn test_md4_hmac() {
+        for i in 0..200 {
+            let data = crate::rng::vec::rand_len(i);
+            let key = crate::rng::vec::rand_len_range(0, 256);
+
+            assert_eq!(t_hmac_md4(&key, &data), hmac::<Md4>(&key, &data));
+        }
+    }
+
+    #[test]
+    fn test_streaming_matches_one_shot() {
+        let key = b"YELLOW SUBMARINE";
+        let data = crate::rng::vec::rand_len(300);
+
+        let mut mac = Hmac::<Sha256>::new(key);
+        for chunk in data.chunks(7) {
+            mac.update(chunk);
+        }
+        assert_eq!(mac.finalize(), hmac::<Sha256>(key, &data));
+
+        // Finalizing starts a fresh message under the same key
+        mac.update(b"abc");
+        assert_eq!(mac.finalize(), hmac::<Sha256>(key, b"abc"));
+    }
+
+    #[test]
+    fn test_verify_rejects() {
+        let key = b"YELLOW SUBMARINE";
+        let tag = hmac::<Sha1>(key, b"message");
+        let mut mac = Hmac::<Sha1>::new(key);
+
+        let mut wrong = tag.clone();
+        wrong[19] ^= 1;
+        mac.update(b"message");
+        assert!(!mac.verify(&wrong));
+
+        // Prefixes of the right tag are no good either
+        mac.update(b"message");
+        assert!(!mac.verify(&tag[..10]));
+
+        let mut truncated = Hmac::<Sha1>::new(key).with_tag_len(12);
+        truncated.update(b"message");
+        assert!(truncated.verify(&tag[..12]));
+        truncated.update(b"message");
+        assert!(!truncated.verify(&tag));
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"", b""));
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"ab"));
+    }
 }
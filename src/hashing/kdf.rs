@@ -0,0 +1,275 @@
+// Key derivation on top of our HMAC: PBKDF2 from RFC 8018 for stretching
+// passwords and HKDF from RFC 5869 for turning a shared secret (like a
+// Diffie-Hellman output) into keys.
+//
+// Anything that needs to turn a secret into a key takes a `Kdf`, so the
+// derivation can be swapped out without touching the protocol code.
+use std::marker::PhantomData;
+
+use crate::hashing::hasher::Hasher;
+use crate::hashing::hmac::Hmac;
+
+pub trait Kdf {
+    // Derives `len` bytes of key material from `secret` and `salt`
+    fn derive(&self, secret: &[u8], salt: &[u8], len: usize) -> Vec<u8>;
+}
+
+pub fn pbkdf2<H: Hasher>(password: &[u8], salt: &[u8], iterations: u32, len: usize) -> Vec<u8> {
+    assert!(iterations > 0, "PBKDF2 needs at least one iteration");
+
+    let mut mac = Hmac::<H>::new(password);
+    let mut res = Vec::with_capacity(len);
+    let mut block_index: u32 = 1;
+
+    while res.len() < len {
+        // T_i = U_1 ^ U_2 ^ ... ^ U_c
+        mac.update(salt);
+        mac.update(&block_index.to_be_bytes());
+        let mut u = mac.finalize();
+        let mut t = u.clone();
+
+        for _ in 1..iterations {
+            mac.update(&u);
+            u = mac.finalize();
+            t.iter_mut().zip(u.iter()).for_each(|(x, y)| *x ^= y);
+        }
+
+        res.extend(t);
+        block_index += 1;
+    }
+
+    res.truncate(len);
+    res
+}
+
+// An empty salt works out the same as the RFC's default of HashLen zeros,
+// since HMAC pads the key with zeros anyway
+pub fn hkdf_extract<H: Hasher>(salt: &[u8], ikm: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<H>::new(salt);
+    mac.update(ikm);
+    mac.finalize()
+}
+
+pub fn hkdf_expand<H: Hasher>(prk: &[u8], info: &[u8], len: usize) -> Vec<u8> {
+    assert!(len <= 255 * H::OUTPUT_SIZE, "HKDF can't expand to more than 255 blocks");
+
+    let mut mac = Hmac::<H>::new(prk);
+    let mut res = Vec::with_capacity(len);
+    let mut t = vec![];
+    let mut counter: u8 = 1;
+
+    while res.len() < len {
+        // T(i) = HMAC(PRK, T(i - 1) || info || i)
+        mac.update(&t);
+        mac.update(info);
+        mac.update(&[counter]);
+        t = mac.finalize();
+
+        res.extend_from_slice(&t);
+        counter = counter.wrapping_add(1);
+    }
+
+    res.truncate(len);
+    res
+}
+
+pub fn hkdf<H: Hasher>(salt: &[u8], ikm: &[u8], info: &[u8], len: usize) -> Vec<u8> {
+    hkdf_expand::<H>(&hkdf_extract::<H>(salt, ikm), info, len)
+}
+
+pub struct Pbkdf2<H> {
+    iterations: u32,
+    hash: PhantomData<H>
+}
+
+impl<H: Hasher> Pbkdf2<H> {
+    pub fn new(iterations: u32) -> Pbkdf2<H> {
+        Pbkdf2 { iterations, hash: PhantomData }
+    }
+}
+
+impl<H: Hasher> Kdf for Pbkdf2<H> {
+    fn derive(&self, secret: &[u8], salt: &[u8], len: usize) -> Vec<u8> {
+        pbkdf2::<H>(secret, salt, self.iterations, len)
+    }
+}
+
+pub struct Hkdf<H> {
+    info: Vec<u8>,
+    hash: PhantomData<H>
+}
+
+impl<H: Hasher> Hkdf<H> {
+    // `info` binds the keys to what they're for, e.g. b"session key"
+    pub fn new(info: &[u8]) -> Hkdf<H> {
+        Hkdf { info: info.to_vec(), hash: PhantomData }
+    }
+}
+
+impl<H: Hasher> Kdf for Hkdf<H> {
+    fn derive(&self, secret: &[u8], salt: &[u8], len: usize) -> Vec<u8> {
+        hkdf::<H>(salt, secret, &self.info, len)
+    }
+}
+
+// H(salt || secret), cut down to size. No stretching at all, this is just
+// what the set 5 code did before there were real KDFs
+pub struct HashKdf<H> {
+    hash: PhantomData<H>
+}
+
+impl<H: Hasher> HashKdf<H> {
+    pub fn new() -> HashKdf<H> {
+        HashKdf { hash: PhantomData }
+    }
+}
+
+impl<H: Hasher> Kdf for HashKdf<H> {
+    fn derive(&self, secret: &[u8], salt: &[u8], len: usize) -> Vec<u8> {
+        assert!(len <= H::OUTPUT_SIZE, "A single hash isn't long enough");
+
+        let mut hasher = H::new();
+        hasher.update(salt);
+        hasher.update(secret);
+
+        let mut res = hasher.finalize();
+        res.truncate(len);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashing::sha1::{sha1, Sha1};
+    use crate::hashing::sha256::Sha256;
+
+    // RFC 6070, minus the 16777216 iteration one
+    #[test]
+    fn test_pbkdf2_sha1() {
+        let vectors: [(&[u8], &[u8], u32, &str); 5] = [
+            (b"password", b"salt", 1, "0c60c80f961f0e71f3a9b524af6012062fe037a6"),
+            (b"password", b"salt", 2, "ea6c014dc72d6f8ccd1ed92ace1d41f0d8de8957"),
+            (b"password", b"salt", 4096, "4b007901b765489abead49d926f721d065a429c1"),
+            (
+                b"passwordPASSWORDpassword",
+                b"saltSALTsaltSALTsaltSALTsaltSALTsalt",
+                4096,
+                "3d2eec4fe41c849b80c8d83662c0e44a8b291a964cf2f07038"
+            ),
+            (b"pass\0word", b"sa\0lt", 4096, "56fa6aa75548099dcc37d7f03425e0c3")
+        ];
+
+        for (password, salt, iterations, expected) in vectors.iter() {
+            let expected = hex::decode(expected).unwrap();
+            assert_eq!(pbkdf2::<Sha1>(password, salt, *iterations, expected.len()), expected);
+        }
+    }
+
+    #[test]
+    fn test_pbkdf2_sha256() {
+        let vectors: [(&[u8], &[u8], u32, &str); 3] = [
+            (b"password", b"salt", 1, "120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b"),
+            (b"password", b"salt", 4096, "c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a"),
+            (
+                b"passwordPASSWORDpassword",
+                b"saltSALTsaltSALTsaltSALTsaltSALTsalt",
+                4096,
+                "348c89dbcbd32b2f32d814b8116e84cf2b17347ebc1800181c4e2a1fb8dd53e1c635518c7dac47e9"
+            )
+        ];
+
+        for (password, salt, iterations, expected) in vectors.iter() {
+            let expected = hex::decode(expected).unwrap();
+            assert_eq!(pbkdf2::<Sha256>(password, salt, *iterations, expected.len()), expected);
+        }
+    }
+
+    // RFC 5869 appendix A: (ikm, salt, info, prk, okm)
+    fn check_hkdf<H: Hasher>(ikm: &[u8], salt: &[u8], info: &[u8], prk: &str, okm: &str) {
+        let prk = hex::decode(prk).unwrap();
+        let okm = hex::decode(okm).unwrap();
+
+        assert_eq!(hkdf_extract::<H>(salt, ikm), prk);
+        assert_eq!(hkdf_expand::<H>(&prk, info, okm.len()), okm);
+        assert_eq!(Hkdf::<H>::new(info).derive(ikm, salt, okm.len()), okm);
+    }
+
+    #[test]
+    fn test_hkdf_sha256() {
+        let long_ikm: Vec<u8> = (0x00..=0x4f).collect();
+        let long_salt: Vec<u8> = (0x60..=0xaf).collect();
+        let long_info: Vec<u8> = (0xb0..=0xff).collect();
+
+        check_hkdf::<Sha256>(
+            &[0x0b; 22],
+            &(0x00..=0x0c).collect::<Vec<u8>>(),
+            &(0xf0..=0xf9).collect::<Vec<u8>>(),
+            "077709362c2e32df0ddc3f0dc47bba6390b6c73bb50f9c3122ec844ad7c2b3e5",
+            "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865"
+        );
+        check_hkdf::<Sha256>(
+            &long_ikm,
+            &long_salt,
+            &long_info,
+            "06a6b88c5853361a06104c9ceb35b45cef760014904671014a193f40c15fc244",
+            "b11e398dc80327a1c8e7f78c596a49344f012eda2d4efad8a050cc4c19afa97c\
+             59045a99cac7827271cb41c65e590e09da3275600c2f09b8367793a9aca3db71\
+             cc30c58179ec3e87c14c01d5c1f3434f1d87"
+        );
+        check_hkdf::<Sha256>(
+            &[0x0b; 22],
+            b"",
+            b"",
+            "19ef24a32c717b167f33a91d6f648bdf96596776afdb6377ac434c1c293ccb04",
+            "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d9d201395faa4b61a96c8"
+        );
+    }
+
+    #[test]
+    fn test_hkdf_sha1() {
+        let long_ikm: Vec<u8> = (0x00..=0x4f).collect();
+        let long_salt: Vec<u8> = (0x60..=0xaf).collect();
+        let long_info: Vec<u8> = (0xb0..=0xff).collect();
+
+        check_hkdf::<Sha1>(
+            &[0x0b; 11],
+            &(0x00..=0x0c).collect::<Vec<u8>>(),
+            &(0xf0..=0xf9).collect::<Vec<u8>>(),
+            "9b6c18c432a7bf8f0e71c8eb88f4b30baa2ba243",
+            "085a01ea1b10f36933068b56efa5ad81a4f14b822f5b091568a9cdd4f155fda2c22e422478d305f3f896"
+        );
+        check_hkdf::<Sha1>(
+            &long_ikm,
+            &long_salt,
+            &long_info,
+            "8adae09a2a307059478d309b26c4115a224cfaf6",
+            "0bd770a74d1160f7c9f12cd5912a06ebff6adcae899d92191fe4305673ba2ffe\
+             8fa3f1a4e5ad79f3f334b3b202b2173c486ea37ce3d397ed034c7f9dfeb15c5e\
+             927336d0441f4c4300e2cff0d0900b52d3b4"
+        );
+        check_hkdf::<Sha1>(
+            &[0x0b; 22],
+            b"",
+            b"",
+            "da8c8a73c7fa77288ec6f5e7c297786aa0d32d01",
+            "0ac1af7002b3d761d1e55298da9d0506b9ae52057220a306e07b6b87e8df21d0ea00033de03984d34918"
+        );
+        // Test case 7 leaves the salt out entirely, which is the same as empty
+        check_hkdf::<Sha1>(
+            &[0x0c; 22],
+            b"",
+            b"",
+            "2adccada18779e7c2077ad2eb19d3f3e731385dd",
+            "2c91117204d745f3500d636a62f64f0ab3bae548aa53d423b0d1f27ebba6f5e5673a081d70cce7acfc48"
+        );
+    }
+
+    #[test]
+    fn test_hash_kdf_is_the_old_behaviour() {
+        let mut expected = sha1(b"saltsecret");
+        expected.truncate(16);
+
+        assert_eq!(HashKdf::<Sha1>::new().derive(b"secret", b"salt", 16), expected);
+    }
+}
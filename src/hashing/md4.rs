@@ -1,8 +1,11 @@
 use crate::hashing::hash_padding::*;
+use crate::hashing::hasher::*;
 use std::convert::TryInto;
 use std::num::Wrapping;
 
-const MD4_LEN_BYTES: usize = 16;
+pub const MD4_LEN_BYTES: usize = 16;
+
+pub const MD4_INITIAL_STATE: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
 
 pub fn md4_process_block(h: &mut [u32; MD4_LEN_BYTES / 4], msg_block: &[u8]) {
     if msg_block.len() != HASH_BLOCK_LEN_BYTES {
@@ -76,54 +79,83 @@ pub fn md4_process_block(h: &mut [u32; MD4_LEN_BYTES / 4], msg_block: &[u8]) {
     }
 }
 
-pub fn md4<T: HashPaddable>(data: &T) -> Vec<u8> {
-    let mut h: [u32; MD4_LEN_BYTES / 4] = [
-        0x67452301,
-        0xefcdab89,
-        0x98badcfe,
-        0x10325476
-    ];
+pub struct Md4Core;
 
-    let padded = data.hashpad(false);
+impl MdCore for Md4Core {
+    type State = [u32; 4];
 
-    for i in 0..padded.len() / HASH_BLOCK_LEN_BYTES {
-        md4_process_block(
-            &mut h,
-            &padded[i * HASH_BLOCK_LEN_BYTES..(i + 1) * HASH_BLOCK_LEN_BYTES],
-        );
+    const BLOCK_SIZE: usize = HASH_BLOCK_LEN_BYTES;
+    const OUTPUT_SIZE: usize = MD4_LEN_BYTES;
+    const LEN_FIELD_BYTES: usize = HASH_LEN_FIELD_BYTES;
+    const BIG_ENDIAN: bool = false;
+
+    fn initial_state() -> [u32; 4] {
+        MD4_INITIAL_STATE
+    }
+
+    fn process_block(state: &mut [u32; 4], block: &[u8]) {
+        md4_process_block(state, block)
+    }
+
+    fn state_to_bytes(state: &[u32; 4]) -> Vec<u8> {
+        state.iter().flat_map(|x| x.to_le_bytes().to_vec()).collect()
     }
 
-    h.iter().map(|x| x.to_le_bytes().to_vec()).flatten().collect()
+    fn state_from_bytes(bytes: &[u8]) -> [u32; 4] {
+        let mut h = [0u32; 4];
+        for i in 0..4 {
+            h[i] = u32::from_le_bytes(bytes[i * 4..(i + 1) * 4].try_into().unwrap());
+        }
+        h
+    }
+}
+
+pub type Md4 = MdHasher<Md4Core>;
+
+pub fn md4<T: AsRef<[u8]> + ?Sized>(data: &T) -> Vec<u8> {
+    Md4::digest(data.as_ref())
 }
 
-fn f(x: u32, y: u32, z: u32) -> u32 {
+// MD4 of the first `bit_len` bits of `data`. RFC 1320 reads bytes most
+// significant bit first like SHA-1 does, only the words are little endian
+pub fn md4_bits(data: &[u8], bit_len: u64) -> Vec<u8> {
+    Md4::digest_bits(data, bit_len)
+}
+
+// The round functions and step operations are public so attacks can run
+// MD4 a step at a time (see s7::md4_collision)
+pub const MD4_ROUND2_CONSTANT: u32 = 0x5A827999;
+pub const MD4_ROUND3_CONSTANT: u32 = 0x6ED9EBA1;
+
+pub fn f(x: u32, y: u32, z: u32) -> u32 {
     (x & y) | ((!x) & z)
 }
 
-fn g(x: u32, y: u32, z: u32) -> u32 {
+pub fn g(x: u32, y: u32, z: u32) -> u32 {
     (x & y) | (x & z) | (y & z)
 }
 
-fn h(x: u32, y: u32, z: u32) -> u32 {
+pub fn h(x: u32, y: u32, z: u32) -> u32 {
     x ^ y ^ z
 }
 
-fn ff(a: u32, b: u32, c: u32, d: u32, k: u32, ss: usize) -> u32 {
+pub fn ff(a: u32, b: u32, c: u32, d: u32, k: u32, ss: usize) -> u32 {
     return s(ss, (Wrapping(a) + Wrapping(f(b,c,d)) + Wrapping(k)).0);
 }
 
-fn gg(a: u32, b: u32, c: u32, d: u32, k: u32, ss: usize) -> u32 {
-    return s(ss, (Wrapping(a) + Wrapping(g(b, c, d)) + Wrapping(k) + Wrapping(0x5A827999)).0);
+pub fn gg(a: u32, b: u32, c: u32, d: u32, k: u32, ss: usize) -> u32 {
+    return s(ss, (Wrapping(a) + Wrapping(g(b, c, d)) + Wrapping(k) + Wrapping(MD4_ROUND2_CONSTANT)).0);
 }
 
-fn hh(a: u32, b: u32, c: u32, d: u32, k: u32, ss: usize) -> u32 {
-    return s(ss, (Wrapping(a) + Wrapping(h(b, c, d)) + Wrapping(k) + Wrapping(0x6ED9EBA1)).0);
+pub fn hh(a: u32, b: u32, c: u32, d: u32, k: u32, ss: usize) -> u32 {
+    return s(ss, (Wrapping(a) + Wrapping(h(b, c, d)) + Wrapping(k) + Wrapping(MD4_ROUND3_CONSTANT)).0);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use md4::{Md4, Digest};
+    use crate::hashing::hash_padding::bit_messages::*;
 
     fn t_md4(data: &[u8]) -> Vec<u8> {
         let mut hasher = Md4::new();
@@ -144,4 +176,35 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_md4_bits() {
+        // There are no official bit-oriented MD4 vectors, these are from a
+        // separate implementation (the same one that agrees with the SHA-1
+        // SHAVS vectors). Same messages as the SHA-1 tests
+        let vectors = [
+            (0, "00", "31d6cfe0d16ae931b73c59d7e0c089c0"),
+            (7, "fe", "9e844e02c8d08f19d6016ddb3042d268"),
+            (447, MSG_447, "73513ee1736599ab14b83b3fc1bfb59d"),
+            (448, MSG_449, "61608be90b9a9654e8a737fc572559f9"),
+            (449, MSG_449, "a8439b17ae3dab1b157b859193c344cc"),
+            (511, MSG_511, "6937456663b9955b94797657b6b2e67d"),
+            (512, MSG_1003, "a09c2594477d240e581756a20ea70feb"),
+            (513, MSG_1003, "512a56862760fd970ace7b83452affca"),
+            (1003, MSG_1003, "fc4d29805051991f020ae2d4c9f185fd")
+        ];
+
+        for &(bit_len, msg, md) in vectors.iter() {
+            assert_eq!(hex::encode(md4_bits(&hex::decode(msg).unwrap(), bit_len)), md, "Len = {}", bit_len);
+        }
+    }
+
+    #[test]
+    fn test_md4_bits_whole_bytes() {
+        for i in 0..100 {
+            let data = crate::rng::vec::rand_len(i);
+            assert_eq!(md4_bits(&data, i as u64 * 8), t_md4(&data));
+        }
+    }
+
 }
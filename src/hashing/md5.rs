@@ -0,0 +1,159 @@
+// An implementation of RFC1321 (https://tools.ietf.org/html/rfc1321)
+// Uses the same little endian padding as MD4
+use crate::hashing::hash_padding::*;
+use crate::hashing::hasher::*;
+use std::convert::TryInto;
+use std::num::Wrapping;
+
+pub const MD5_LEN_BYTES: usize = 16;
+
+pub const MD5_INITIAL_STATE: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+
+// floor(abs(sin(i + 1)) * 2^32)
+const T: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee,
+    0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+    0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be,
+    0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+    0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa,
+    0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed,
+    0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+    0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c,
+    0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+    0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05,
+    0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039,
+    0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+    0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1,
+    0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391
+];
+
+const SHIFTS: [usize; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22,
+    5,  9, 14, 20, 5,  9, 14, 20, 5,  9, 14, 20, 5,  9, 14, 20,
+    4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23,
+    6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21
+];
+
+pub fn md5_process_block(h: &mut [u32; MD5_LEN_BYTES / 4], msg_block: &[u8]) {
+    if msg_block.len() != HASH_BLOCK_LEN_BYTES {
+        panic!(
+            "Message length should have been 64 bytes. Was {}",
+            msg_block.len()
+        );
+    }
+
+    let mut x = [0u32; 16];
+    for j in 0..16 {
+        x[j] = u32::from_le_bytes(msg_block[j * 4..j * 4 + 4].try_into().unwrap());
+    }
+
+    let [mut a, mut b, mut c, mut d] = *h;
+
+    for i in 0..64 {
+        // Round function and which word of the block this step uses
+        let (f, k) = match i {
+            0..=15 => (f(b, c, d), i),
+            16..=31 => (g(b, c, d), (5 * i + 1) % 16),
+            32..=47 => (hh(b, c, d), (3 * i + 5) % 16),
+            _ => (ii(b, c, d), (7 * i) % 16)
+        };
+
+        let sum = (Wrapping(a) + Wrapping(f) + Wrapping(T[i]) + Wrapping(x[k])).0;
+        a = d;
+        d = c;
+        c = b;
+        b = (Wrapping(b) + Wrapping(s(SHIFTS[i], sum))).0;
+    }
+
+    h[0] = (Wrapping(h[0]) + Wrapping(a)).0;
+    h[1] = (Wrapping(h[1]) + Wrapping(b)).0;
+    h[2] = (Wrapping(h[2]) + Wrapping(c)).0;
+    h[3] = (Wrapping(h[3]) + Wrapping(d)).0;
+}
+
+pub struct Md5Core;
+
+impl MdCore for Md5Core {
+    type State = [u32; 4];
+
+    const BLOCK_SIZE: usize = HASH_BLOCK_LEN_BYTES;
+    const OUTPUT_SIZE: usize = MD5_LEN_BYTES;
+    const LEN_FIELD_BYTES: usize = HASH_LEN_FIELD_BYTES;
+    const BIG_ENDIAN: bool = false;
+
+    fn initial_state() -> [u32; 4] {
+        MD5_INITIAL_STATE
+    }
+
+    fn process_block(state: &mut [u32; 4], block: &[u8]) {
+        md5_process_block(state, block)
+    }
+
+    fn state_to_bytes(state: &[u32; 4]) -> Vec<u8> {
+        state.iter().flat_map(|x| x.to_le_bytes().to_vec()).collect()
+    }
+
+    fn state_from_bytes(bytes: &[u8]) -> [u32; 4] {
+        let mut h = [0u32; 4];
+        for i in 0..4 {
+            h[i] = u32::from_le_bytes(bytes[i * 4..(i + 1) * 4].try_into().unwrap());
+        }
+        h
+    }
+}
+
+pub type Md5 = MdHasher<Md5Core>;
+
+pub fn md5<T: AsRef<[u8]> + ?Sized>(data: &T) -> Vec<u8> {
+    Md5::digest(data.as_ref())
+}
+
+fn f(x: u32, y: u32, z: u32) -> u32 {
+    (x & y) | ((!x) & z)
+}
+
+fn g(x: u32, y: u32, z: u32) -> u32 {
+    (x & z) | (y & (!z))
+}
+
+fn hh(x: u32, y: u32, z: u32) -> u32 {
+    x ^ y ^ z
+}
+
+fn ii(x: u32, y: u32, z: u32) -> u32 {
+    y ^ (x | (!z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_md5_rfc_vectors() {
+        let vectors = [
+            ("", "d41d8cd98f00b204e9800998ecf8427e"),
+            ("a", "0cc175b9c0f1b6a831c399e269772661"),
+            ("abc", "900150983cd24fb0d6963f7d28e17f72"),
+            ("message digest", "f96b697d7cb7938d525a2f31aaf161d0"),
+            ("abcdefghijklmnopqrstuvwxyz", "c3fcd3d76192e4007dfb496cca67e13b")
+        ];
+
+        for (input, expected) in vectors.iter() {
+            assert_eq!(hex::encode(md5(&input.as_bytes().to_vec())), *expected);
+        }
+    }
+
+    #[test]
+    fn test_md5_random() {
+        for i in 0..500 {
+            let data = crate::rng::vec::rand_len(i);
+
+            assert_eq!(
+                &openssl::hash::hash(openssl::hash::MessageDigest::md5(), &data).unwrap()[..],
+                &md5(&data)[..]
+            );
+        }
+    }
+}
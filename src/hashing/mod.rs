@@ -1,4 +1,11 @@
 pub mod md4;
+pub mod md5;
+pub mod cheap_hash;
+pub mod compression;
 pub mod hash_padding;
+pub mod hasher;
 pub mod hmac;
+pub mod kdf;
 pub mod sha1;
+pub mod sha256;
+pub mod sha512;
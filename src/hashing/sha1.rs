@@ -1,11 +1,14 @@
 // An implementation of RFC3174 (https://tools.ietf.org/rfc/rfc3174.txt)
-// For practicality, only bit lengths that are multiples of 8 are allowed
+// Messages that aren't a whole number of bytes go through `sha1_bits`
 use std::convert::TryInto;
 use std::num::Wrapping;
 use crate::hashing::hash_padding::*;
+use crate::hashing::hasher::*;
 
 pub const SHA1_LEN_BYTES: usize = 20;
 
+pub const SHA1_INITIAL_STATE: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
 pub fn sha1_process_block(h: &mut [u32; SHA1_LEN_BYTES / 4], msg_block: &[u8]) {
     if msg_block.len() != HASH_BLOCK_LEN_BYTES {
         panic!(
@@ -44,21 +47,46 @@ pub fn sha1_process_block(h: &mut [u32; SHA1_LEN_BYTES / 4], msg_block: &[u8]) {
     }
 }
 
-pub fn sha1<T: HashPaddable>(content: &T) -> Vec<u8> {
-    let padded = content.hashpad(true);
-    let mut h = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+pub struct Sha1Core;
 
-    for i in 0..padded.len() / HASH_BLOCK_LEN_BYTES {
-        sha1_process_block(
-            &mut h,
-            &padded[i * HASH_BLOCK_LEN_BYTES..(i + 1) * HASH_BLOCK_LEN_BYTES],
-        );
+impl MdCore for Sha1Core {
+    type State = [u32; 5];
+
+    const BLOCK_SIZE: usize = HASH_BLOCK_LEN_BYTES;
+    const OUTPUT_SIZE: usize = SHA1_LEN_BYTES;
+    const LEN_FIELD_BYTES: usize = HASH_LEN_FIELD_BYTES;
+    const BIG_ENDIAN: bool = true;
+
+    fn initial_state() -> [u32; 5] {
+        SHA1_INITIAL_STATE
     }
 
-    h.to_vec()
-        .iter()
-        .flat_map(|x| x.to_be_bytes().to_vec())
-        .collect()
+    fn process_block(state: &mut [u32; 5], block: &[u8]) {
+        sha1_process_block(state, block)
+    }
+
+    fn state_to_bytes(state: &[u32; 5]) -> Vec<u8> {
+        state.iter().flat_map(|x| x.to_be_bytes().to_vec()).collect()
+    }
+
+    fn state_from_bytes(bytes: &[u8]) -> [u32; 5] {
+        let mut h = [0u32; 5];
+        for i in 0..5 {
+            h[i] = u32::from_be_bytes(bytes[i * 4..(i + 1) * 4].try_into().unwrap());
+        }
+        h
+    }
+}
+
+pub type Sha1 = MdHasher<Sha1Core>;
+
+pub fn sha1<T: AsRef<[u8]> + ?Sized>(content: &T) -> Vec<u8> {
+    Sha1::digest(content.as_ref())
+}
+
+// SHA-1 of the first `bit_len` bits of `content`, most significant bit first
+pub fn sha1_bits(content: &[u8], bit_len: u64) -> Vec<u8> {
+    Sha1::digest_bits(content, bit_len)
 }
 
 fn k(t: usize) -> u32 {
@@ -83,6 +111,7 @@ fn f(t: usize, b: u32, c: u32, d: u32) -> u32 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::hashing::hash_padding::bit_messages::*;
 
     #[test]
     fn test_sha1_padding_1() {
@@ -113,6 +142,115 @@ mod tests {
         assert_eq!(input.hashpad(true), expected_out);
     }
 
+    #[test]
+    fn test_sha1_padding_bits() {
+        // 10011, the rest of the byte isn't part of the message
+        let input: Vec<u8> = vec![0x61, 0x9f];
+        let padded = input.hashpad_bits(13, true);
+
+        assert_eq!(padded.len(), HASH_BLOCK_LEN_BYTES);
+        assert_eq!(padded[..2], [0x61, 0x9c]);
+        assert!(padded[2..56].iter().all(|&b| b == 0));
+        assert_eq!(padded[56..], 13u64.to_be_bytes());
+    }
+
+    #[test]
+    fn test_sha1_bits() {
+        // The first bit-oriented SHAVS vectors (SHA1ShortMsg) and the 5 bit
+        // one from RFC 6234. test_sha1_shavs_files runs all of them
+        let vectors = [
+            (0, "00", "da39a3ee5e6b4b0d3255bfef95601890afd80709"),
+            (1, "00", "bb6b3e18f0115b57925241676f5b1ae88747b08a"),
+            (2, "40", "ec6b39952e1a3ec3ab3507185cf756181c84bbe2"),
+            (5, "98", "29826b003b906e660eff4027ce98af3531ac75ba")
+        ];
+
+        for &(bit_len, msg, md) in vectors.iter() {
+            assert_eq!(hex::encode(sha1_bits(&hex::decode(msg).unwrap(), bit_len)), md, "Len = {}", bit_len);
+        }
+    }
+
+    #[test]
+    fn test_sha1_bits_cross_checked() {
+        // Not official vectors: our own messages ending on either side of
+        // where the length field stops fitting in the last block, with
+        // digests from Perl's Digest::SHA (add_bits) and a separate Python
+        // implementation
+        let vectors = [
+            (7, "fe", "8811f916b110424594f2860a73112f1b3a0f9ff8"),
+            (447, MSG_447, "82ad2718e1ec40cda2c713b07608181630941967"),
+            (448, MSG_449, "cba6579b509b1aea205574ed91d6f3624eb1f663"),
+            (449, MSG_449, "8a436e3ce0a888dada983ba1d724c0fd2dc0ef21"),
+            (511, MSG_511, "94f4a1f3321da52736e0c86e415938547f57827b"),
+            (512, MSG_1003, "1e6d9317beeab849ad71bcedb1b475de294871f8"),
+            (513, MSG_1003, "7b119116770940750d53a4886202ab1bf00e8d5d"),
+            (1003, MSG_1003, "2938f160a1b69eee47794d1a52522108b4a97f35")
+        ];
+
+        for &(bit_len, msg, md) in vectors.iter() {
+            assert_eq!(hex::encode(sha1_bits(&hex::decode(msg).unwrap(), bit_len)), md, "Len = {}", bit_len);
+        }
+    }
+
+    // (Len, Msg, MD) from a SHAVS response file
+    fn parse_rsp(contents: &str) -> Vec<(u64, Vec<u8>, String)> {
+        let mut res = vec![];
+        let mut len = None;
+        let mut msg = None;
+
+        for line in contents.lines() {
+            let mut parts = line.splitn(2, " = ");
+            match (parts.next().map(str::trim), parts.next().map(str::trim)) {
+                (Some("Len"), Some(v)) => len = Some(v.parse().unwrap()),
+                (Some("Msg"), Some(v)) => msg = Some(hex::decode(v).unwrap()),
+                (Some("MD"), Some(v)) => res.push((len.take().unwrap(), msg.take().unwrap(), v.to_lowercase())),
+                _ => ()
+            }
+        }
+        res
+    }
+
+    // Needs the bit-oriented SHA1ShortMsg.rsp and SHA1LongMsg.rsp from
+    // NIST's SHAVS test vectors (shabittestvectors.zip) in src/hashing/shavs,
+    // run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn test_sha1_shavs_files() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src/hashing/shavs");
+
+        for name in ["SHA1ShortMsg.rsp", "SHA1LongMsg.rsp"].iter() {
+            let contents = std::fs::read_to_string(dir.join(name)).expect("Missing SHAVS response file");
+            let vectors = parse_rsp(&contents);
+            assert!(!vectors.is_empty(), "No vectors in {}", name);
+
+            for (bit_len, msg, md) in vectors {
+                assert_eq!(hex::encode(sha1_bits(&msg, bit_len)), md, "{} Len = {}", name, bit_len);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_rsp() {
+        let contents = "#  CAVS 11.1\n\n[L = 20]\n\nLen = 0\nMsg = 00\nMD = da39a3ee5e6b4b0d3255bfef95601890afd80709\n\n\
+            Len = 1\nMsg = 00\nMD = BB6B3E18F0115B57925241676F5B1AE88747B08A\n";
+        let vectors = parse_rsp(contents);
+
+        assert_eq!(vectors.len(), 2);
+        assert_eq!(vectors[1], (1, vec![0u8], "bb6b3e18f0115b57925241676f5b1ae88747b08a".to_string()));
+    }
+
+    #[test]
+    fn test_sha1_bits_whole_bytes() {
+        for i in 0..100 {
+            let data = crate::rng::vec::rand_len(i);
+            assert_eq!(sha1_bits(&data, i as u64 * 8), sha1(&data));
+        }
+
+        // Bits past the end of the message don't count
+        assert_eq!(sha1_bits(b"abc\xff", 24), sha1(b"abc"));
+        assert_eq!(sha1_bits(&[0x9f], 5), sha1_bits(&[0x98], 5));
+    }
+
     #[test]
     fn test_sha1() {
         assert_eq!(
@@ -0,0 +1,235 @@
+// An implementation of SHA-256 and SHA-224 from FIPS 180-4
+// (https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.180-4.pdf)
+// For practicality, only bit lengths that are multiples of 8 are allowed
+use std::convert::TryInto;
+use std::num::Wrapping;
+use crate::hashing::hash_padding::*;
+use crate::hashing::hasher::*;
+
+pub const SHA256_LEN_BYTES: usize = 32;
+pub const SHA224_LEN_BYTES: usize = 28;
+
+pub const SHA256_INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a,
+    0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19
+];
+
+pub const SHA224_INITIAL_STATE: [u32; 8] = [
+    0xc1059ed8, 0x367cd507, 0x3070dd17, 0xf70e5939,
+    0xffc00b31, 0x68581511, 0x64f98fa7, 0xbefa4fa4
+];
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2
+];
+
+pub fn sha256_process_block(h: &mut [u32; 8], msg_block: &[u8]) {
+    if msg_block.len() != HASH_BLOCK_LEN_BYTES {
+        panic!(
+            "Message length should have been 64 bytes. Was {}",
+            msg_block.len()
+        );
+    }
+
+    let mut w = [0u32; 64];
+
+    for i in 0..16 {
+        w[i] = u32::from_be_bytes(msg_block[i * 4..(i + 1) * 4].try_into().unwrap());
+    }
+
+    for t in 16..64 {
+        w[t] = (Wrapping(small_sigma_1(w[t - 2]))
+            + Wrapping(w[t - 7])
+            + Wrapping(small_sigma_0(w[t - 15]))
+            + Wrapping(w[t - 16])).0;
+    }
+
+    let mut a = *h;
+
+    for t in 0..64 {
+        let t1 = Wrapping(a[7])
+            + Wrapping(big_sigma_1(a[4]))
+            + Wrapping(ch(a[4], a[5], a[6]))
+            + Wrapping(K[t])
+            + Wrapping(w[t]);
+        let t2 = Wrapping(big_sigma_0(a[0])) + Wrapping(maj(a[0], a[1], a[2]));
+        a[7] = a[6];
+        a[6] = a[5];
+        a[5] = a[4];
+        a[4] = (Wrapping(a[3]) + t1).0;
+        a[3] = a[2];
+        a[2] = a[1];
+        a[1] = a[0];
+        a[0] = (t1 + t2).0;
+    }
+
+    for i in 0..8 {
+        h[i] = (Wrapping(h[i]) + Wrapping(a[i])).0;
+    }
+}
+
+// Turns a SHA-256 digest back into the state it was produced from, which is
+// all we need to keep hashing from where it left off. SHA-224 throws away
+// the last word of the state, so this doesn't work for it.
+pub fn sha256_state_from_digest(digest: &[u8]) -> [u32; 8] {
+    assert_eq!(digest.len(), SHA256_LEN_BYTES, "Not a SHA-256 digest");
+
+    let mut h = [0u32; 8];
+    for i in 0..8 {
+        h[i] = u32::from_be_bytes(digest[i * 4..(i + 1) * 4].try_into().unwrap());
+    }
+    h
+}
+
+pub fn sha256_digest_from_state(h: &[u32; 8]) -> Vec<u8> {
+    h.iter()
+        .flat_map(|x| x.to_be_bytes().to_vec())
+        .collect()
+}
+
+pub struct Sha256Core;
+
+impl MdCore for Sha256Core {
+    type State = [u32; 8];
+
+    const BLOCK_SIZE: usize = HASH_BLOCK_LEN_BYTES;
+    const OUTPUT_SIZE: usize = SHA256_LEN_BYTES;
+    const LEN_FIELD_BYTES: usize = HASH_LEN_FIELD_BYTES;
+    const BIG_ENDIAN: bool = true;
+
+    fn initial_state() -> [u32; 8] {
+        SHA256_INITIAL_STATE
+    }
+
+    fn process_block(state: &mut [u32; 8], block: &[u8]) {
+        sha256_process_block(state, block)
+    }
+
+    fn state_to_bytes(state: &[u32; 8]) -> Vec<u8> {
+        sha256_digest_from_state(state)
+    }
+
+    fn state_from_bytes(bytes: &[u8]) -> [u32; 8] {
+        sha256_state_from_digest(bytes)
+    }
+}
+
+pub type Sha256 = MdHasher<Sha256Core>;
+
+pub struct Sha224Core;
+
+impl MdCore for Sha224Core {
+    type State = [u32; 8];
+
+    const BLOCK_SIZE: usize = HASH_BLOCK_LEN_BYTES;
+    const OUTPUT_SIZE: usize = SHA224_LEN_BYTES;
+    const LEN_FIELD_BYTES: usize = HASH_LEN_FIELD_BYTES;
+    const BIG_ENDIAN: bool = true;
+
+    fn initial_state() -> [u32; 8] {
+        SHA224_INITIAL_STATE
+    }
+
+    fn process_block(state: &mut [u32; 8], block: &[u8]) {
+        sha256_process_block(state, block)
+    }
+
+    fn state_to_bytes(state: &[u32; 8]) -> Vec<u8> {
+        sha256_digest_from_state(state)
+    }
+
+    fn state_from_bytes(bytes: &[u8]) -> [u32; 8] {
+        sha256_state_from_digest(bytes)
+    }
+}
+
+pub type Sha224 = MdHasher<Sha224Core>;
+
+pub fn sha256<T: AsRef<[u8]> + ?Sized>(content: &T) -> Vec<u8> {
+    Sha256::digest(content.as_ref())
+}
+
+// Same as SHA-256 with a different IV, truncated to 28 bytes
+pub fn sha224<T: AsRef<[u8]> + ?Sized>(content: &T) -> Vec<u8> {
+    Sha224::digest(content.as_ref())
+}
+
+fn ch(x: u32, y: u32, z: u32) -> u32 {
+    (x & y) ^ ((!x) & z)
+}
+
+fn maj(x: u32, y: u32, z: u32) -> u32 {
+    (x & y) ^ (x & z) ^ (y & z)
+}
+
+fn big_sigma_0(x: u32) -> u32 {
+    x.rotate_right(2) ^ x.rotate_right(13) ^ x.rotate_right(22)
+}
+
+fn big_sigma_1(x: u32) -> u32 {
+    x.rotate_right(6) ^ x.rotate_right(11) ^ x.rotate_right(25)
+}
+
+fn small_sigma_0(x: u32) -> u32 {
+    x.rotate_right(7) ^ x.rotate_right(18) ^ (x >> 3)
+}
+
+fn small_sigma_1(x: u32) -> u32 {
+    x.rotate_right(17) ^ x.rotate_right(19) ^ (x >> 10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            hex::encode(sha256(&vec![0u8; 0])),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex::encode(sha256(&b"abc".to_vec())),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hex::encode(sha256(&b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq".to_vec())),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    #[test]
+    fn test_sha224() {
+        assert_eq!(
+            hex::encode(sha224(&vec![0u8; 0])),
+            "d14a028c2a3a2bc9476102bb288234c415a2b01f828ea62ac5b3e42f"
+        );
+        assert_eq!(
+            hex::encode(sha224(&b"abc".to_vec())),
+            "23097d223405d8228642a477bda255b32aadbce4bda0b3f7e36c9da7"
+        );
+    }
+
+    #[test]
+    fn test_sha256_random() {
+        for _ in 0..500 {
+            let data = crate::rng::vec::rand_len_range(0, 512);
+
+            assert_eq!(&openssl::sha::sha256(&data), &sha256(&data)[..]);
+            assert_eq!(&openssl::sha::sha224(&data), &sha224(&data)[..]);
+        }
+    }
+
+    #[test]
+    fn test_sha256_state_round_trip() {
+        let digest = sha256(&b"YELLOW SUBMARINE".to_vec());
+        assert_eq!(sha256_digest_from_state(&sha256_state_from_digest(&digest)), digest);
+    }
+}
@@ -0,0 +1,343 @@
+// An implementation of SHA-512, SHA-384, SHA-512/224 and SHA-512/256 from
+// FIPS 180-4 (https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.180-4.pdf)
+// Same structure as SHA-256 with 64 bit words, 80 rounds, 128 byte blocks
+// and a 128 bit length field. The variants only differ in their IV and how
+// much of the output they keep.
+use std::convert::TryInto;
+use std::num::Wrapping;
+use crate::hashing::hasher::*;
+
+pub const SHA512_BLOCK_LEN_BYTES: usize = 128;
+pub const SHA512_LEN_FIELD_BYTES: usize = 16;
+
+pub const SHA512_LEN_BYTES: usize = 64;
+pub const SHA384_LEN_BYTES: usize = 48;
+pub const SHA512_224_LEN_BYTES: usize = 28;
+pub const SHA512_256_LEN_BYTES: usize = 32;
+
+pub const SHA512_INITIAL_STATE: [u64; 8] = [
+    0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+    0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179
+];
+
+pub const SHA384_INITIAL_STATE: [u64; 8] = [
+    0xcbbb9d5dc1059ed8, 0x629a292a367cd507, 0x9159015a3070dd17, 0x152fecd8f70e5939,
+    0x67332667ffc00b31, 0x8eb44a8768581511, 0xdb0c2e0d64f98fa7, 0x47b5481dbefa4fa4
+];
+
+// Generated by the SHA-512/t IV generation function in section 5.3.6
+pub const SHA512_224_INITIAL_STATE: [u64; 8] = [
+    0x8c3d37c819544da2, 0x73e1996689dcd4d6, 0x1dfab7ae32ff9c82, 0x679dd514582f9fcf,
+    0x0f6d2b697bd44da8, 0x77e36f7304c48942, 0x3f9d85a86a1d36c8, 0x1112e6ad91d692a1
+];
+
+pub const SHA512_256_INITIAL_STATE: [u64; 8] = [
+    0x22312194fc2bf72c, 0x9f555fa3c84c64c2, 0x2393b86b6f53b151, 0x963877195940eabd,
+    0x96283ee2a88effe3, 0xbe5e1e2553863992, 0x2b0199fc2c85b8aa, 0x0eb72ddc81c52ca2
+];
+
+const K: [u64; 80] = [
+    0x428a2f98d728ae22, 0x7137449123ef65cd, 0xb5c0fbcfec4d3b2f, 0xe9b5dba58189dbbc,
+    0x3956c25bf348b538, 0x59f111f1b605d019, 0x923f82a4af194f9b, 0xab1c5ed5da6d8118,
+    0xd807aa98a3030242, 0x12835b0145706fbe, 0x243185be4ee4b28c, 0x550c7dc3d5ffb4e2,
+    0x72be5d74f27b896f, 0x80deb1fe3b1696b1, 0x9bdc06a725c71235, 0xc19bf174cf692694,
+    0xe49b69c19ef14ad2, 0xefbe4786384f25e3, 0x0fc19dc68b8cd5b5, 0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275, 0x4a7484aa6ea6e483, 0x5cb0a9dcbd41fbd4, 0x76f988da831153b5,
+    0x983e5152ee66dfab, 0xa831c66d2db43210, 0xb00327c898fb213f, 0xbf597fc7beef0ee4,
+    0xc6e00bf33da88fc2, 0xd5a79147930aa725, 0x06ca6351e003826f, 0x142929670a0e6e70,
+    0x27b70a8546d22ffc, 0x2e1b21385c26c926, 0x4d2c6dfc5ac42aed, 0x53380d139d95b3df,
+    0x650a73548baf63de, 0x766a0abb3c77b2a8, 0x81c2c92e47edaee6, 0x92722c851482353b,
+    0xa2bfe8a14cf10364, 0xa81a664bbc423001, 0xc24b8b70d0f89791, 0xc76c51a30654be30,
+    0xd192e819d6ef5218, 0xd69906245565a910, 0xf40e35855771202a, 0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8, 0x1e376c085141ab53, 0x2748774cdf8eeb99, 0x34b0bcb5e19b48a8,
+    0x391c0cb3c5c95a63, 0x4ed8aa4ae3418acb, 0x5b9cca4f7763e373, 0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc, 0x78a5636f43172f60, 0x84c87814a1f0ab72, 0x8cc702081a6439ec,
+    0x90befffa23631e28, 0xa4506cebde82bde9, 0xbef9a3f7b2c67915, 0xc67178f2e372532b,
+    0xca273eceea26619c, 0xd186b8c721c0c207, 0xeada7dd6cde0eb1e, 0xf57d4f7fee6ed178,
+    0x06f067aa72176fba, 0x0a637dc5a2c898a6, 0x113f9804bef90dae, 0x1b710b35131c471b,
+    0x28db77f523047d84, 0x32caab7b40c72493, 0x3c9ebe0a15c9bebc, 0x431d67c49c100d4c,
+    0x4cc5d4becb3e42b6, 0x597f299cfc657e2a, 0x5fcb6fab3ad6faec, 0x6c44198c4a475817
+];
+
+pub fn sha512_process_block(h: &mut [u64; 8], msg_block: &[u8]) {
+    if msg_block.len() != SHA512_BLOCK_LEN_BYTES {
+        panic!(
+            "Message length should have been 128 bytes. Was {}",
+            msg_block.len()
+        );
+    }
+
+    let mut w = [0u64; 80];
+
+    for i in 0..16 {
+        w[i] = u64::from_be_bytes(msg_block[i * 8..(i + 1) * 8].try_into().unwrap());
+    }
+
+    for t in 16..80 {
+        w[t] = (Wrapping(small_sigma_1(w[t - 2]))
+            + Wrapping(w[t - 7])
+            + Wrapping(small_sigma_0(w[t - 15]))
+            + Wrapping(w[t - 16])).0;
+    }
+
+    let mut a = *h;
+
+    for t in 0..80 {
+        let t1 = Wrapping(a[7])
+            + Wrapping(big_sigma_1(a[4]))
+            + Wrapping(ch(a[4], a[5], a[6]))
+            + Wrapping(K[t])
+            + Wrapping(w[t]);
+        let t2 = Wrapping(big_sigma_0(a[0])) + Wrapping(maj(a[0], a[1], a[2]));
+        a[7] = a[6];
+        a[6] = a[5];
+        a[5] = a[4];
+        a[4] = (Wrapping(a[3]) + t1).0;
+        a[3] = a[2];
+        a[2] = a[1];
+        a[1] = a[0];
+        a[0] = (t1 + t2).0;
+    }
+
+    for i in 0..8 {
+        h[i] = (Wrapping(h[i]) + Wrapping(a[i])).0;
+    }
+}
+
+// Only full SHA-512 digests can be turned back into the state
+pub fn sha512_state_from_digest(digest: &[u8]) -> [u64; 8] {
+    assert_eq!(digest.len(), SHA512_LEN_BYTES, "Not a SHA-512 digest");
+
+    let mut h = [0u64; 8];
+    for i in 0..8 {
+        h[i] = u64::from_be_bytes(digest[i * 8..(i + 1) * 8].try_into().unwrap());
+    }
+    h
+}
+
+pub fn sha512_digest_from_state(h: &[u64; 8]) -> Vec<u8> {
+    h.iter()
+        .flat_map(|x| x.to_be_bytes().to_vec())
+        .collect()
+}
+
+pub struct Sha512Core;
+
+impl MdCore for Sha512Core {
+    type State = [u64; 8];
+
+    const BLOCK_SIZE: usize = SHA512_BLOCK_LEN_BYTES;
+    const OUTPUT_SIZE: usize = SHA512_LEN_BYTES;
+    const LEN_FIELD_BYTES: usize = SHA512_LEN_FIELD_BYTES;
+    const BIG_ENDIAN: bool = true;
+
+    fn initial_state() -> [u64; 8] {
+        SHA512_INITIAL_STATE
+    }
+
+    fn process_block(state: &mut [u64; 8], block: &[u8]) {
+        sha512_process_block(state, block)
+    }
+
+    fn state_to_bytes(state: &[u64; 8]) -> Vec<u8> {
+        sha512_digest_from_state(state)
+    }
+
+    fn state_from_bytes(bytes: &[u8]) -> [u64; 8] {
+        sha512_state_from_digest(bytes)
+    }
+}
+
+pub type Sha512 = MdHasher<Sha512Core>;
+
+pub struct Sha384Core;
+
+impl MdCore for Sha384Core {
+    type State = [u64; 8];
+
+    const BLOCK_SIZE: usize = SHA512_BLOCK_LEN_BYTES;
+    const OUTPUT_SIZE: usize = SHA384_LEN_BYTES;
+    const LEN_FIELD_BYTES: usize = SHA512_LEN_FIELD_BYTES;
+    const BIG_ENDIAN: bool = true;
+
+    fn initial_state() -> [u64; 8] {
+        SHA384_INITIAL_STATE
+    }
+
+    fn process_block(state: &mut [u64; 8], block: &[u8]) {
+        sha512_process_block(state, block)
+    }
+
+    fn state_to_bytes(state: &[u64; 8]) -> Vec<u8> {
+        sha512_digest_from_state(state)
+    }
+
+    fn state_from_bytes(bytes: &[u8]) -> [u64; 8] {
+        sha512_state_from_digest(bytes)
+    }
+}
+
+pub type Sha384 = MdHasher<Sha384Core>;
+
+pub struct Sha512Trunc224Core;
+
+impl MdCore for Sha512Trunc224Core {
+    type State = [u64; 8];
+
+    const BLOCK_SIZE: usize = SHA512_BLOCK_LEN_BYTES;
+    const OUTPUT_SIZE: usize = SHA512_224_LEN_BYTES;
+    const LEN_FIELD_BYTES: usize = SHA512_LEN_FIELD_BYTES;
+    const BIG_ENDIAN: bool = true;
+
+    fn initial_state() -> [u64; 8] {
+        SHA512_224_INITIAL_STATE
+    }
+
+    fn process_block(state: &mut [u64; 8], block: &[u8]) {
+        sha512_process_block(state, block)
+    }
+
+    fn state_to_bytes(state: &[u64; 8]) -> Vec<u8> {
+        sha512_digest_from_state(state)
+    }
+
+    fn state_from_bytes(bytes: &[u8]) -> [u64; 8] {
+        sha512_state_from_digest(bytes)
+    }
+}
+
+pub type Sha512Trunc224 = MdHasher<Sha512Trunc224Core>;
+
+pub struct Sha512Trunc256Core;
+
+impl MdCore for Sha512Trunc256Core {
+    type State = [u64; 8];
+
+    const BLOCK_SIZE: usize = SHA512_BLOCK_LEN_BYTES;
+    const OUTPUT_SIZE: usize = SHA512_256_LEN_BYTES;
+    const LEN_FIELD_BYTES: usize = SHA512_LEN_FIELD_BYTES;
+    const BIG_ENDIAN: bool = true;
+
+    fn initial_state() -> [u64; 8] {
+        SHA512_256_INITIAL_STATE
+    }
+
+    fn process_block(state: &mut [u64; 8], block: &[u8]) {
+        sha512_process_block(state, block)
+    }
+
+    fn state_to_bytes(state: &[u64; 8]) -> Vec<u8> {
+        sha512_digest_from_state(state)
+    }
+
+    fn state_from_bytes(bytes: &[u8]) -> [u64; 8] {
+        sha512_state_from_digest(bytes)
+    }
+}
+
+pub type Sha512Trunc256 = MdHasher<Sha512Trunc256Core>;
+
+pub fn sha512<T: AsRef<[u8]> + ?Sized>(content: &T) -> Vec<u8> {
+    Sha512::digest(content.as_ref())
+}
+
+pub fn sha384<T: AsRef<[u8]> + ?Sized>(content: &T) -> Vec<u8> {
+    Sha384::digest(content.as_ref())
+}
+
+pub fn sha512_224<T: AsRef<[u8]> + ?Sized>(content: &T) -> Vec<u8> {
+    Sha512Trunc224::digest(content.as_ref())
+}
+
+pub fn sha512_256<T: AsRef<[u8]> + ?Sized>(content: &T) -> Vec<u8> {
+    Sha512Trunc256::digest(content.as_ref())
+}
+
+fn ch(x: u64, y: u64, z: u64) -> u64 {
+    (x & y) ^ ((!x) & z)
+}
+
+fn maj(x: u64, y: u64, z: u64) -> u64 {
+    (x & y) ^ (x & z) ^ (y & z)
+}
+
+fn big_sigma_0(x: u64) -> u64 {
+    x.rotate_right(28) ^ x.rotate_right(34) ^ x.rotate_right(39)
+}
+
+fn big_sigma_1(x: u64) -> u64 {
+    x.rotate_right(14) ^ x.rotate_right(18) ^ x.rotate_right(41)
+}
+
+fn small_sigma_0(x: u64) -> u64 {
+    x.rotate_right(1) ^ x.rotate_right(8) ^ (x >> 7)
+}
+
+fn small_sigma_1(x: u64) -> u64 {
+    x.rotate_right(19) ^ x.rotate_right(61) ^ (x >> 6)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashing::hash_padding::HashPaddable;
+    use openssl::hash::{hash, MessageDigest};
+
+    #[test]
+    fn test_sha512_padding() {
+        let input: Vec<u8> = vec![0u8; 111];
+        let mut expected = vec![0u8; SHA512_BLOCK_LEN_BYTES];
+        expected[111] = 0x80;
+        expected[112..].copy_from_slice(&(111u128 * 8).to_be_bytes());
+        assert_eq!(input.hashpad_with(true, SHA512_BLOCK_LEN_BYTES, SHA512_LEN_FIELD_BYTES), expected);
+
+        // One more byte doesn't fit next to the length field anymore
+        let input: Vec<u8> = vec![0u8; 112];
+        assert_eq!(
+            input.hashpad_with(true, SHA512_BLOCK_LEN_BYTES, SHA512_LEN_FIELD_BYTES).len(),
+            2 * SHA512_BLOCK_LEN_BYTES
+        );
+    }
+
+    #[test]
+    fn test_sha512_family() {
+        let empty: Vec<u8> = Vec::new();
+        let abc = b"abc".to_vec();
+
+        assert_eq!(
+            hex::encode(sha512(&empty)),
+            "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e"
+        );
+        assert_eq!(
+            hex::encode(sha512(&abc)),
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+        );
+        assert_eq!(
+            hex::encode(sha384(&abc)),
+            "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7"
+        );
+        assert_eq!(
+            hex::encode(sha512_224(&abc)),
+            "4634270f707b6a54daae7530460842e20e37ed265ceee9a43e8924aa"
+        );
+        assert_eq!(
+            hex::encode(sha512_256(&abc)),
+            "53048e2681941ef99b2e29b76b4c7dabe4c2d0c634fc6d46e0e2f13107e7af23"
+        );
+    }
+
+    #[test]
+    fn test_sha512_random() {
+        let sha512_224_md = MessageDigest::from_name("sha512-224").unwrap();
+        let sha512_256_md = MessageDigest::from_name("sha512-256").unwrap();
+
+        for _ in 0..300 {
+            let data = crate::rng::vec::rand_len_range(0, 512);
+
+            assert_eq!(&openssl::sha::sha512(&data), &sha512(&data)[..]);
+            assert_eq!(&openssl::sha::sha384(&data), &sha384(&data)[..]);
+            assert_eq!(&hash(sha512_224_md, &data).unwrap()[..], &sha512_224(&data)[..]);
+            assert_eq!(&hash(sha512_256_md, &data).unwrap()[..], &sha512_256(&data)[..]);
+        }
+    }
+}
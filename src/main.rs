@@ -1,11 +1,13 @@
 mod asymmetric;
 mod hashing;
+mod oracles;
 mod rng;
 mod s1;
 mod s2;
 mod s3;
 mod s4;
 mod s5;
+mod s7;
 mod symmetric;
 mod utils;
 
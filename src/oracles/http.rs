@@ -0,0 +1,339 @@
+// Puts an oracle on the other side of an HTTP connection. `OracleServer`
+// serves an in-process oracle on an ephemeral localhost port and
+// `HttpOracle` implements the oracle traits by talking to it, so the same
+// attack code runs against either:
+//
+// let server = OracleServer::serve_padding(target);
+// let oracle = HttpOracle::new(&server.url());
+//
+// All byte strings go over the wire hex encoded in the query string. Boolean
+// answers are the status code: 200 for yes, 403 for no. Malformed requests
+// get a 400, and the client panics on anything that isn't a 200 or a 403.
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Request};
+use serde::Deserialize;
+use tokio::runtime::{Builder, Runtime};
+use tokio::sync::oneshot;
+use warp::http::StatusCode;
+use warp::Filter;
+
+use super::*;
+
+#[derive(Deserialize)]
+struct EncryptParams {
+    data: String
+}
+
+#[derive(Deserialize)]
+struct PaddingParams {
+    ciphertext: String
+}
+
+#[derive(Deserialize)]
+struct VerifyParams {
+    message: String,
+    signature: String
+}
+
+type Reply = warp::reply::WithStatus<String>;
+
+fn reply(body: String, status: StatusCode) -> Reply {
+    warp::reply::with_status(body, status)
+}
+
+fn reply_bool(valid: bool) -> Reply {
+    if valid {
+        reply(String::new(), StatusCode::OK)
+    } else {
+        reply(String::new(), StatusCode::FORBIDDEN)
+    }
+}
+
+fn bad_request() -> Reply {
+    reply(String::new(), StatusCode::BAD_REQUEST)
+}
+
+async fn handle_rejection(_: warp::Rejection) -> Result<Reply, Infallible> {
+    Ok(bad_request())
+}
+
+pub(crate) fn new_runtime() -> Runtime {
+    Builder::new()
+        .basic_scheduler()
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+// Shuts the server down when dropped
+pub struct OracleServer {
+    addr: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>
+}
+
+impl OracleServer {
+    // GET /encrypt?data=<hex>, responds with the hex encoded ciphertext
+    pub fn serve_encryption<O>(oracle: O) -> OracleServer
+    where O: EncryptionOracle + Send + Sync + 'static {
+        let oracle = Arc::new(oracle);
+        let route = warp::path!("encrypt")
+            .and(warp::query::<EncryptParams>())
+            .map(move |params: EncryptParams| {
+                match hex::decode(params.data) {
+                    Ok(pt) => reply(hex::encode(oracle.encrypt(&pt)), StatusCode::OK),
+                    Err(_) => bad_request()
+                }
+            });
+
+        OracleServer::start(route)
+    }
+
+    // GET /check_padding?ciphertext=<hex>
+    pub fn serve_padding<O>(oracle: O) -> OracleServer
+    where O: PaddingOracle + Send + Sync + 'static {
+        let oracle = Arc::new(oracle);
+        let route = warp::path!("check_padding")
+            .and(warp::query::<PaddingParams>())
+            .map(move |params: PaddingParams| {
+                match hex::decode(params.ciphertext) {
+                    Ok(ct) => reply_bool(oracle.check_padding(&ct)),
+                    Err(_) => bad_request()
+                }
+            });
+
+        OracleServer::start(route)
+    }
+
+    // GET /verify?message=<hex>&signature=<hex>
+    pub fn serve_mac_verify<O>(oracle: O) -> OracleServer
+    where O: MacVerifyOracle + Send + Sync + 'static {
+        let oracle = Arc::new(oracle);
+        let route = warp::path!("verify")
+            .and(warp::query::<VerifyParams>())
+            .map(move |params: VerifyParams| {
+                match (hex::decode(params.message), hex::decode(params.signature)) {
+                    (Ok(message), Ok(tag)) => reply_bool(oracle.verify(&message, &tag)),
+                    _ => bad_request()
+                }
+            });
+
+        OracleServer::start(route)
+    }
+
+    // The server gets a thread and a runtime of its own, so it works the
+    // same whether or not the caller is running inside tokio
+    fn start<F>(route: F) -> OracleServer
+    where F: Filter<Extract = (Reply,), Error = warp::Rejection> + Clone + Send + Sync + 'static {
+        let routes = warp::get().and(route).recover(handle_rejection);
+
+        let (addr_tx, addr_rx) = mpsc::channel();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+
+        let thread = thread::spawn(move || {
+            new_runtime().block_on(async move {
+                let (addr, server) = warp::serve(routes)
+                    .bind_with_graceful_shutdown(
+                        ([127, 0, 0, 1], 0),
+                        async {
+                            shutdown_rx.await.ok();
+                        }
+                    );
+
+                addr_tx.send(addr).unwrap();
+                server.await;
+            });
+        });
+
+        OracleServer {
+            addr: addr_rx.recv().unwrap(),
+            shutdown: Some(shutdown_tx),
+            thread: Some(thread)
+        }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+}
+
+impl Drop for OracleServer {
+    fn drop(&mut self) {
+        if let Some(tx) = self.shutdown.take() {
+            let _ = tx.send(());
+        }
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// Blocks on every query, so it can't be used from inside a tokio runtime
+pub struct HttpOracle {
+    base_url: String,
+    client: Client<HttpConnector>,
+    runtime: Mutex<Runtime>
+}
+
+impl HttpOracle {
+    pub fn new(base_url: &str) -> HttpOracle {
+        HttpOracle {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: Client::new(),
+            runtime: Mutex::new(new_runtime())
+        }
+    }
+
+    fn get(&self, path_and_query: &str) -> (StatusCode, Vec<u8>) {
+        let request = Request::get(format!("{}{}", self.base_url, path_and_query))
+            .body(Body::empty())
+            .unwrap();
+
+        self.runtime.lock().unwrap().block_on(async {
+            let response = self.client.request(request).await.unwrap();
+            let status = response.status();
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            (status, body.to_vec())
+        })
+    }
+}
+
+// 200 and 403 are the only answers a yes/no oracle gives. Anything else
+// means we aren't talking to the oracle we think we are, and treating it as
+// a no would send the attack off in the wrong direction
+fn expect_bool(status: StatusCode, body: &[u8]) -> bool {
+    match status {
+        StatusCode::OK => true,
+        StatusCode::FORBIDDEN => false,
+        _ => panic!("Unexpected answer from oracle: {} {}", status, String::from_utf8_lossy(body))
+    }
+}
+
+impl EncryptionOracle for HttpOracle {
+    fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        let (status, body) = self.get(&format!("/encrypt?data={}", hex::encode(plaintext)));
+        assert_eq!(status, StatusCode::OK, "Encryption request failed");
+        hex::decode(body).unwrap()
+    }
+}
+
+impl PaddingOracle for HttpOracle {
+    fn check_padding(&self, ciphertext: &[u8]) -> bool {
+        let (status, body) = self.get(&format!("/check_padding?ciphertext={}", hex::encode(ciphertext)));
+        expect_bool(status, &body)
+    }
+}
+
+impl MacVerifyOracle for HttpOracle {
+    fn verify(&self, message: &[u8], tag: &[u8]) -> bool {
+        let (status, body) = self.get(&format!(
+            "/verify?message={}&signature={}",
+            hex::encode(message),
+            hex::encode(tag)
+        ));
+        expect_bool(status, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oracles::instrumented::Instrumented;
+
+    struct XorOracle;
+
+    impl EncryptionOracle for XorOracle {
+        fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+            plaintext.iter().map(|x| x ^ 0x42).collect()
+        }
+    }
+
+    impl PaddingOracle for XorOracle {
+        fn check_padding(&self, ciphertext: &[u8]) -> bool {
+            ciphertext.last() == Some(&0x42)
+        }
+    }
+
+    impl MacVerifyOracle for XorOracle {
+        fn verify(&self, message: &[u8], tag: &[u8]) -> bool {
+            self.encrypt(message) == tag
+        }
+    }
+
+    #[test]
+    fn test_encryption_over_http() {
+        let server = OracleServer::serve_encryption(XorOracle);
+        let oracle = HttpOracle::new(&server.url());
+
+        assert_eq!(oracle.encrypt(b"hello"), XorOracle.encrypt(b"hello"));
+        assert_eq!(oracle.encrypt(b""), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_padding_over_http() {
+        let server = OracleServer::serve_padding(XorOracle);
+        let oracle = HttpOracle::new(&server.url());
+
+        assert!(oracle.check_padding(b"\x00\x42"));
+        assert!(!oracle.check_padding(b"\x42\x00"));
+    }
+
+    #[test]
+    fn test_mac_verify_over_http() {
+        let server = OracleServer::serve_mac_verify(XorOracle);
+        let oracle = HttpOracle::new(&server.url());
+
+        assert!(oracle.verify(b"\x00", b"\x42"));
+        assert!(!oracle.verify(b"\x00", b"\x43"));
+    }
+
+    #[test]
+    fn test_servers_dont_collide() {
+        let server_1 = OracleServer::serve_encryption(XorOracle);
+        let server_2 = OracleServer::serve_encryption(XorOracle);
+        assert_ne!(server_1.addr(), server_2.addr());
+    }
+
+    #[test]
+    #[should_panic(expected = "Unexpected answer from oracle")]
+    fn test_wrong_endpoint() {
+        // An encryption server doesn't know how to check padding
+        let server = OracleServer::serve_encryption(XorOracle);
+        let oracle = HttpOracle::new(&server.url());
+        oracle.check_padding(b"\x42");
+    }
+
+    #[test]
+    #[should_panic(expected = "Unexpected answer from oracle")]
+    fn test_wrong_endpoint_mac() {
+        let server = OracleServer::serve_padding(XorOracle);
+        let oracle = HttpOracle::new(&server.url());
+        oracle.verify(b"\x00", b"\x42");
+    }
+
+    #[test]
+    fn test_malformed_request() {
+        let server = OracleServer::serve_encryption(XorOracle);
+        let oracle = HttpOracle::new(&server.url());
+        assert_eq!(oracle.get("/encrypt?data=zz").0, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_client_can_be_instrumented() {
+        let server = OracleServer::serve_encryption(XorOracle);
+        let oracle = Instrumented::new(HttpOracle::new(&server.url()));
+        oracle.encrypt(b"a");
+        oracle.encrypt(b"b");
+        assert_eq!(oracle.queries(), 2);
+    }
+}
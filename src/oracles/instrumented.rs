@@ -0,0 +1,248 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{thread, time::Duration};
+
+use super::*;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TranscriptEntry {
+    Encrypt { plaintext: Vec<u8>, ciphertext: Vec<u8> },
+    Decrypt { ciphertext: Vec<u8>, plaintext: Option<Vec<u8>> },
+    CheckPadding { ciphertext: Vec<u8>, valid: bool },
+    Verify { message: Vec<u8>, tag: Vec<u8>, valid: bool },
+    GetCiphertext { ind: usize, ciphertext: Option<Vec<u8>> }
+}
+
+// Wraps any oracle and keeps track of how it gets used. Queries are always
+// counted, everything else is opt-in:
+//
+// let oracle = Instrumented::new(&server).with_budget(1000).with_transcript();
+pub struct Instrumented<O> {
+    oracle: O,
+    queries: AtomicUsize,
+    budget: Option<usize>,
+    latency: Option<Duration>,
+    transcript: Option<Mutex<Vec<TranscriptEntry>>>
+}
+
+impl<O> Instrumented<O> {
+    pub fn new(oracle: O) -> Instrumented<O> {
+        Instrumented {
+            oracle,
+            queries: AtomicUsize::new(0),
+            budget: None,
+            latency: None,
+            transcript: None
+        }
+    }
+
+    // Panics once the attack makes more than `budget` queries
+    pub fn with_budget(mut self, budget: usize) -> Instrumented<O> {
+        self.budget = Some(budget);
+        self
+    }
+
+    // Sleeps before answering every query, like a server on the other side of the world
+    pub fn with_latency(mut self, latency: Duration) -> Instrumented<O> {
+        self.latency = Some(latency);
+        self
+    }
+
+    pub fn with_transcript(mut self) -> Instrumented<O> {
+        self.transcript = Some(Mutex::new(Vec::new()));
+        self
+    }
+
+    pub fn queries(&self) -> usize {
+        self.queries.load(Ordering::SeqCst)
+    }
+
+    // Empty unless `with_transcript` was used
+    pub fn transcript(&self) -> Vec<TranscriptEntry> {
+        match &self.transcript {
+            Some(t) => t.lock().unwrap().clone(),
+            None => Vec::new()
+        }
+    }
+
+    pub fn into_inner(self) -> O {
+        self.oracle
+    }
+
+    fn begin_query(&self) {
+        let queries = self.queries.fetch_add(1, Ordering::SeqCst) + 1;
+
+        if let Some(budget) = self.budget {
+            if queries > budget {
+                panic!("Oracle query budget of {} exhausted", budget);
+            }
+        }
+
+        if let Some(latency) = self.latency {
+            thread::sleep(latency);
+        }
+    }
+
+    fn log(&self, entry: TranscriptEntry) {
+        if let Some(t) = &self.transcript {
+            t.lock().unwrap().push(entry);
+        }
+    }
+}
+
+impl<O: EncryptionOracle> EncryptionOracle for Instrumented<O> {
+    fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        self.begin_query();
+        let ciphertext = self.oracle.encrypt(plaintext);
+        self.log(TranscriptEntry::Encrypt {
+            plaintext: plaintext.to_vec(),
+            ciphertext: ciphertext.clone()
+        });
+        ciphertext
+    }
+}
+
+impl<O: DecryptionOracle> DecryptionOracle for Instrumented<O> {
+    fn decrypt(&self, ciphertext: &[u8]) -> Option<Vec<u8>> {
+        self.begin_query();
+        let plaintext = self.oracle.decrypt(ciphertext);
+        self.log(TranscriptEntry::Decrypt {
+            ciphertext: ciphertext.to_vec(),
+            plaintext: plaintext.clone()
+        });
+        plaintext
+    }
+}
+
+impl<O: PaddingOracle> PaddingOracle for Instrumented<O> {
+    fn check_padding(&self, ciphertext: &[u8]) -> bool {
+        self.begin_query();
+        let valid = self.oracle.check_padding(ciphertext);
+        self.log(TranscriptEntry::CheckPadding {
+            ciphertext: ciphertext.to_vec(),
+            valid
+        });
+        valid
+    }
+}
+
+impl<O: MacVerifyOracle> MacVerifyOracle for Instrumented<O> {
+    fn verify(&self, message: &[u8], tag: &[u8]) -> bool {
+        self.begin_query();
+        let valid = self.oracle.verify(message, tag);
+        self.log(TranscriptEntry::Verify {
+            message: message.to_vec(),
+            tag: tag.to_vec(),
+            valid
+        });
+        valid
+    }
+}
+
+impl<O: TimingOracle> TimingOracle for Instrumented<O> {
+    fn timed_verify(&self, message: &[u8], tag: &[u8]) -> (bool, Duration) {
+        self.begin_query();
+        let (valid, elapsed) = self.oracle.timed_verify(message, tag);
+        self.log(TranscriptEntry::Verify {
+            message: message.to_vec(),
+            tag: tag.to_vec(),
+            valid
+        });
+        (valid, elapsed)
+    }
+}
+
+impl<O: CiphertextProvider> CiphertextProvider for Instrumented<O> {
+    fn get_ciphertext(&self, ind: usize) -> Option<Vec<u8>> {
+        self.begin_query();
+        let ciphertext = self.oracle.get_ciphertext(ind);
+        self.log(TranscriptEntry::GetCiphertext {
+            ind,
+            ciphertext: ciphertext.clone()
+        });
+        ciphertext
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    struct XorOracle;
+
+    impl EncryptionOracle for XorOracle {
+        fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+            plaintext.iter().map(|x| x ^ 0x42).collect()
+        }
+    }
+
+    impl MacVerifyOracle for XorOracle {
+        fn verify(&self, message: &[u8], tag: &[u8]) -> bool {
+            self.encrypt(message) == tag
+        }
+    }
+
+    #[test]
+    fn test_counts_queries() {
+        let oracle = Instrumented::new(XorOracle);
+        for i in 0..10 {
+            oracle.encrypt(&vec![0u8; i]);
+        }
+        oracle.verify(b"a", b"b");
+        assert_eq!(oracle.queries(), 11);
+
+        // Nothing gets logged unless we ask for it
+        assert!(oracle.transcript().is_empty());
+    }
+
+    #[test]
+    fn test_transcript() {
+        let oracle = Instrumented::new(XorOracle).with_transcript();
+        oracle.encrypt(b"\x42");
+        oracle.verify(b"\x42", b"\x00");
+
+        assert_eq!(
+            oracle.transcript(),
+            vec![
+                TranscriptEntry::Encrypt { plaintext: vec![0x42], ciphertext: vec![0] },
+                TranscriptEntry::Verify { message: vec![0x42], tag: vec![0], valid: true }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_budget_allows_exact_budget() {
+        let oracle = Instrumented::new(XorOracle).with_budget(3);
+        for _ in 0..3 {
+            oracle.encrypt(b"A");
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "budget of 3 exhausted")]
+    fn test_budget_exhausted() {
+        let oracle = Instrumented::new(XorOracle).with_budget(3);
+        for _ in 0..4 {
+            oracle.encrypt(b"A");
+        }
+    }
+
+    #[test]
+    fn test_latency() {
+        let oracle = Instrumented::new(XorOracle).with_latency(Duration::from_millis(5));
+        let start = Instant::now();
+        oracle.encrypt(b"A");
+        oracle.encrypt(b"A");
+        assert!(start.elapsed() >= Duration::from_millis(10));
+    }
+
+    #[test]
+    fn test_wraps_borrowed_oracle() {
+        let inner = XorOracle;
+        let outer = Instrumented::new(Instrumented::new(&inner));
+        outer.encrypt(b"A");
+        assert_eq!(outer.queries(), 1);
+        assert_eq!(outer.into_inner().queries(), 1);
+    }
+}
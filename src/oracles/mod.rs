@@ -0,0 +1,97 @@
+pub mod http;
+pub mod instrumented;
+pub mod timing;
+
+use std::time::Duration;
+
+// Every attack in the repo talks to its target through one of these traits.
+// An oracle only exposes what a real attacker would be able to observe.
+
+pub trait EncryptionOracle {
+    fn encrypt(&self, plaintext: &[u8]) -> Vec<u8>;
+}
+
+// Returns whatever plaintext the target leaks back to us (e.g. in an error
+// message). None if the target doesn't leak anything for this ciphertext
+pub trait DecryptionOracle {
+    fn decrypt(&self, ciphertext: &[u8]) -> Option<Vec<u8>>;
+}
+
+pub trait PaddingOracle {
+    fn check_padding(&self, ciphertext: &[u8]) -> bool;
+}
+
+pub trait MacVerifyOracle {
+    fn verify(&self, message: &[u8], tag: &[u8]) -> bool;
+}
+
+// Verifies a MAC and tells us how long the target took to answer
+pub trait TimingOracle {
+    fn timed_verify(&self, message: &[u8], tag: &[u8]) -> (bool, Duration);
+}
+
+// Sends a request carrying our data and tells us how long the (compressed,
+// then encrypted) result was. That's all we get to see
+pub trait CompressionOracle {
+    fn encrypted_len(&self, data: &[u8]) -> usize;
+}
+
+// Hands out captured ciphertexts, returns None once we run out
+pub trait CiphertextProvider {
+    fn get_ciphertext(&self, ind: usize) -> Option<Vec<u8>>;
+}
+
+// Writes `newtext` over the plaintext of a ciphertext starting at `offset`
+// and hands back the re-encrypted result (the seek/edit API of challenge 25)
+pub trait EditOracle {
+    fn edit(&self, ciphertext: &[u8], offset: usize, newtext: &[u8]) -> Vec<u8>;
+}
+
+// Lets attacks borrow an oracle (or a wrapper around one) without taking ownership
+impl<T: EncryptionOracle + ?Sized> EncryptionOracle for &T {
+    fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        (**self).encrypt(plaintext)
+    }
+}
+
+impl<T: DecryptionOracle + ?Sized> DecryptionOracle for &T {
+    fn decrypt(&self, ciphertext: &[u8]) -> Option<Vec<u8>> {
+        (**self).decrypt(ciphertext)
+    }
+}
+
+impl<T: PaddingOracle + ?Sized> PaddingOracle for &T {
+    fn check_padding(&self, ciphertext: &[u8]) -> bool {
+        (**self).check_padding(ciphertext)
+    }
+}
+
+impl<T: MacVerifyOracle + ?Sized> MacVerifyOracle for &T {
+    fn verify(&self, message: &[u8], tag: &[u8]) -> bool {
+        (**self).verify(message, tag)
+    }
+}
+
+impl<T: TimingOracle + ?Sized> TimingOracle for &T {
+    fn timed_verify(&self, message: &[u8], tag: &[u8]) -> (bool, Duration) {
+        (**self).timed_verify(message, tag)
+    }
+}
+
+impl<T: CompressionOracle + ?Sized> CompressionOracle for &T {
+    fn encrypted_len(&self, data: &[u8]) -> usize {
+        (**self).encrypted_len(data)
+    }
+}
+
+impl<T: CiphertextProvider + ?Sized> CiphertextProvider for &T {
+    fn get_ciphertext(&self, ind: usize) -> Option<Vec<u8>> {
+        (**self).get_ciphertext(ind)
+    }
+}
+
+impl<T: EditOracle + ?Sized> EditOracle for &T {
+    fn edit(&self, ciphertext: &[u8], offset: usize, newtext: &[u8]) -> Vec<u8> {
+        (**self).edit(ciphertext, offset, newtext)
+    }
+}
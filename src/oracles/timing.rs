@@ -0,0 +1,212 @@
+use std::cell::RefCell;
+use std::time::Instant;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::*;
+
+// Times queries to any MAC verifier with the local clock. With an HttpOracle
+// inside this measures the full round trip, like a real remote attacker would.
+pub struct WallClock<O> {
+    oracle: O
+}
+
+impl<O: MacVerifyOracle> WallClock<O> {
+    pub fn new(oracle: O) -> WallClock<O> {
+        WallClock { oracle }
+    }
+
+    pub fn into_inner(self) -> O {
+        self.oracle
+    }
+}
+
+impl<O: MacVerifyOracle> TimingOracle for WallClock<O> {
+    fn timed_verify(&self, message: &[u8], tag: &[u8]) -> (bool, Duration) {
+        let start = Instant::now();
+        let valid = self.oracle.verify(message, tag);
+        (valid, start.elapsed())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Noise {
+    None,
+    Gaussian { std_dev: Duration },
+    // Pareto distributed delays with the given shape. Mostly small, with the
+    // occasional huge spike like a context switch or a GC pause
+    HeavyTailed { scale: Duration, shape: f64 }
+}
+
+// Verifies tags with an early exit comparison but never actually sleeps. The
+// reported time is made up from the number of matching bytes:
+//
+//   base + matching * leak + noise + uniform(0, jitter)
+//
+// With a seed the timings are fully reproducible.
+pub struct SimulatedTiming<F> {
+    mac: F,
+    base: Duration,
+    leak: Duration,
+    noise: Noise,
+    jitter: Duration,
+    rng: RefCell<StdRng>
+}
+
+impl<F: Fn(&[u8]) -> Vec<u8>> SimulatedTiming<F> {
+    // `mac` computes the correct tag for a message
+    pub fn new(mac: F) -> SimulatedTiming<F> {
+        SimulatedTiming {
+            mac,
+            base: Duration::from_micros(100),
+            leak: Duration::from_millis(5),
+            noise: Noise::None,
+            jitter: Duration::from_secs(0),
+            rng: RefCell::new(StdRng::from_entropy())
+        }
+    }
+
+    // Extra time taken for every matching byte
+    pub fn with_leak(mut self, leak: Duration) -> SimulatedTiming<F> {
+        self.leak = leak;
+        self
+    }
+
+    pub fn with_noise(mut self, noise: Noise) -> SimulatedTiming<F> {
+        self.noise = noise;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: Duration) -> SimulatedTiming<F> {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn with_seed(self, seed: u64) -> SimulatedTiming<F> {
+        self.rng.replace(StdRng::seed_from_u64(seed));
+        self
+    }
+
+    // In nanoseconds, can be negative
+    fn sample_noise(&self) -> f64 {
+        let mut rng = self.rng.borrow_mut();
+
+        let noise = match self.noise {
+            Noise::None => 0.0,
+            Noise::Gaussian { std_dev } => {
+                // Box-Muller
+                let u1: f64 = 1.0 - rng.gen::<f64>();
+                let u2: f64 = rng.gen();
+                let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+                z * std_dev.as_nanos() as f64
+            },
+            Noise::HeavyTailed { scale, shape } => {
+                let u: f64 = 1.0 - rng.gen::<f64>();
+                scale.as_nanos() as f64 * (u.powf(-1.0 / shape) - 1.0)
+            }
+        };
+
+        let jitter = if self.jitter.as_nanos() > 0 {
+            rng.gen_range(0.0, self.jitter.as_nanos() as f64)
+        } else {
+            0.0
+        };
+
+        noise + jitter
+    }
+}
+
+impl<F: Fn(&[u8]) -> Vec<u8>> TimingOracle for SimulatedTiming<F> {
+    fn timed_verify(&self, message: &[u8], tag: &[u8]) -> (bool, Duration) {
+        let expected = (self.mac)(message);
+        let matching = expected.iter()
+            .zip(tag.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let valid = expected.len() == tag.len() && matching == expected.len();
+
+        let nanos = self.base.as_nanos() as f64
+            + (matching as u128 * self.leak.as_nanos()) as f64
+            + self.sample_noise();
+
+        (valid, Duration::from_nanos(nanos.max(0.0) as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    struct SlowOracle;
+
+    impl MacVerifyOracle for SlowOracle {
+        fn verify(&self, _message: &[u8], tag: &[u8]) -> bool {
+            thread::sleep(Duration::from_millis(tag.len() as u64));
+            tag.is_empty()
+        }
+    }
+
+    #[test]
+    fn test_wall_clock() {
+        let oracle = WallClock::new(SlowOracle);
+
+        let (valid, elapsed) = oracle.timed_verify(b"", b"abcde");
+        assert!(!valid);
+        assert!(elapsed >= Duration::from_millis(5));
+
+        assert!(oracle.timed_verify(b"", b"").0);
+    }
+
+    fn simulated(seed: u64) -> SimulatedTiming<impl Fn(&[u8]) -> Vec<u8>> {
+        SimulatedTiming::new(|_: &[u8]| b"abc".to_vec())
+            .with_leak(Duration::from_micros(10))
+            .with_noise(Noise::Gaussian { std_dev: Duration::from_micros(5) })
+            .with_jitter(Duration::from_micros(1))
+            .with_seed(seed)
+    }
+
+    #[test]
+    fn test_simulated_timing_leaks() {
+        let oracle = SimulatedTiming::new(|_: &[u8]| b"abc".to_vec())
+            .with_leak(Duration::from_millis(1));
+
+        let (valid, none) = oracle.timed_verify(b"", b"xxx");
+        assert!(!valid);
+        let (valid, two) = oracle.timed_verify(b"", b"abx");
+        assert!(!valid);
+        assert_eq!(two - none, Duration::from_millis(2));
+
+        assert!(oracle.timed_verify(b"", b"abc").0);
+        assert!(!oracle.timed_verify(b"", b"ab").0);
+        assert!(!oracle.timed_verify(b"", b"abcd").0);
+    }
+
+    #[test]
+    fn test_simulated_timing_is_deterministic() {
+        let (o1, o2) = (simulated(1337), simulated(1337));
+        for tag in [&b"xxx"[..], b"axx", b"abx"].iter() {
+            assert_eq!(o1.timed_verify(b"", tag), o2.timed_verify(b"", tag));
+        }
+
+        let o3 = simulated(1338);
+        assert_ne!(simulated(1337).timed_verify(b"", b"xxx"), o3.timed_verify(b"", b"xxx"));
+    }
+
+    #[test]
+    fn test_heavy_tailed_noise() {
+        let oracle = SimulatedTiming::new(|_: &[u8]| b"abc".to_vec())
+            .with_leak(Duration::from_secs(0))
+            .with_noise(Noise::HeavyTailed { scale: Duration::from_micros(10), shape: 1.5 })
+            .with_seed(0);
+
+        let mut times: Vec<Duration> = (0..1000).map(|_| oracle.timed_verify(b"", b"x").1).collect();
+        times.sort();
+
+        // Never faster than the base time, and the slowest samples are way
+        // off from the typical one
+        assert!(times[0] >= Duration::from_micros(100));
+        assert!(times[999] - times[0] > 20 * (times[500] - times[0]));
+    }
+}
@@ -0,0 +1,163 @@
+use crate::oracles::EncryptionOracle;
+use crate::symmetric::aes::AES_BLOCK_SIZE;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BitflipMode {
+    Cbc,
+    Ctr
+}
+
+// The planner only ever sends these bytes to the oracle. They need to make it
+// through whatever escaping the server does untouched.
+const FILLER: u8 = b'A';
+const PROBE_1: u8 = b'B';
+const PROBE_2: u8 = b'C';
+
+fn first_difference(ct1: &[u8], ct2: &[u8]) -> usize {
+    ct1.iter().zip(ct2.iter()).position(|(a, b)| a != b).unwrap_or(ct1.len())
+}
+
+// Returns the index of the first byte of user data in the plaintext.
+// Assumes that the oracle is deterministic (fixed IV or nonce), which is
+// the case for the servers in c16 and c26.
+pub fn find_user_data_offset<T: EncryptionOracle>(oracle: &T, mode: BitflipMode) -> usize {
+    match mode {
+        BitflipMode::Ctr => {
+            // Every byte is encrypted on its own, so the first byte that
+            // changes is the first byte we control
+            first_difference(&oracle.encrypt(&[PROBE_1]), &oracle.encrypt(&[PROBE_2]))
+        },
+        BitflipMode::Cbc => {
+            // Only tells us the block, we still need the offset into the block
+            let first_block = first_difference(
+                &oracle.encrypt(&[PROBE_1]),
+                &oracle.encrypt(&[PROBE_2])
+            ) / AES_BLOCK_SIZE;
+
+            // Push the probe forward until it falls into the next block
+            for fill_len in 1..=AES_BLOCK_SIZE {
+                let mut pt_1 = vec![FILLER; fill_len];
+                let mut pt_2 = pt_1.clone();
+                pt_1.push(PROBE_1);
+                pt_2.push(PROBE_2);
+
+                let block = first_difference(
+                    &oracle.encrypt(&pt_1),
+                    &oracle.encrypt(&pt_2)
+                ) / AES_BLOCK_SIZE;
+
+                if block != first_block {
+                    return first_block * AES_BLOCK_SIZE + (AES_BLOCK_SIZE - fill_len);
+                }
+            }
+
+            panic!("Probe never moved to the next block, is the oracle using CBC?");
+        }
+    }
+}
+
+// Returns a ciphertext that decrypts to something containing `target`.
+// For CBC, `target` has to fit in a single block since the block before it
+// gets garbled. We send a whole block of our own data to be garbled instead
+// of mangling the data that comes before us.
+pub fn plan_bitflip<T: EncryptionOracle>(oracle: &T, target: &[u8], mode: BitflipMode) -> Result<Vec<u8>, &'static str> {
+    let offset = find_user_data_offset(oracle, mode);
+
+    let (user_data_len, flip_start) = match mode {
+        BitflipMode::Ctr => (target.len(), offset),
+        BitflipMode::Cbc => {
+            if target.len() > AES_BLOCK_SIZE {
+                return Err("CBC targets have to fit in a single block");
+            }
+
+            // Fill up the current block, then add the block that gets garbled
+            let align = (AES_BLOCK_SIZE - offset % AES_BLOCK_SIZE) % AES_BLOCK_SIZE;
+            (align + AES_BLOCK_SIZE + target.len(), offset + align)
+        }
+    };
+
+    let mut ct = oracle.encrypt(&vec![FILLER; user_data_len]);
+
+    // In CTR mode flipping a ciphertext bit flips the same plaintext bit.
+    // In CBC mode it flips the bit at the same position in the next block.
+    for (i, &b) in target.iter().enumerate() {
+        ct[flip_start + i] ^= FILLER ^ b;
+    }
+
+    Ok(ct)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::s2::c16::ServerOracle;
+    use crate::symmetric::aes::*;
+    use rand::Rng;
+
+    struct OddPrefixOracle<'a> {
+        crypter: &'a (dyn CryptoWrapper + 'a)
+    }
+
+    impl<'a> EncryptionOracle for OddPrefixOracle<'a> {
+        fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+            let mut pt = b"uid=1".to_vec();
+            pt.extend_from_slice(plaintext);
+            self.crypter.encrypt(&pt)
+        }
+    }
+
+    #[test]
+    fn test_find_user_data_offset() {
+        let key = gen_random_16_bytes();
+        let cbc = AesCbcWrapper::new(&key, None, true);
+        let ctr = AesCtrWrapper::new(&key, rand::thread_rng().gen());
+
+        let prefix_len = "comment1=cooking%20MCs;userdata=".len();
+        assert_eq!(find_user_data_offset(&ServerOracle::new(&cbc), BitflipMode::Cbc), prefix_len);
+        assert_eq!(find_user_data_offset(&ServerOracle::new(&ctr), BitflipMode::Ctr), prefix_len);
+
+        assert_eq!(find_user_data_offset(&OddPrefixOracle { crypter: &cbc }, BitflipMode::Cbc), 5);
+        assert_eq!(find_user_data_offset(&OddPrefixOracle { crypter: &ctr }, BitflipMode::Ctr), 5);
+    }
+
+    #[test]
+    fn test_plan_bitflip_cbc() {
+        let key = gen_random_16_bytes();
+        let crypter = AesCbcWrapper::new(&key, None, true);
+        let so = ServerOracle::new(&crypter);
+
+        let ct = plan_bitflip(&so, b";admin=true;", BitflipMode::Cbc).unwrap();
+        assert!(so.is_client_admin(ct));
+    }
+
+    #[test]
+    fn test_plan_bitflip_ctr() {
+        let key = gen_random_16_bytes();
+        let crypter = AesCtrWrapper::new(&key, rand::thread_rng().gen());
+        let so = ServerOracle::new(&crypter);
+
+        let ct = plan_bitflip(&so, b";role=root;admin=true;", BitflipMode::Ctr).unwrap();
+        assert!(so.is_client_admin(ct));
+    }
+
+    #[test]
+    fn test_plan_bitflip_odd_prefix() {
+        let key = gen_random_16_bytes();
+        let crypter = AesCbcWrapper::new(&key, None, true);
+        let oracle = OddPrefixOracle { crypter: &crypter };
+
+        let ct = plan_bitflip(&oracle, b";admin=true;", BitflipMode::Cbc).unwrap();
+        let pt = crypter.decrypt(&ct).unwrap();
+        assert!(String::from_utf8_lossy(&pt).contains(";admin=true;"));
+        assert_eq!(&pt[0..5], b"uid=1");
+    }
+
+    #[test]
+    fn test_plan_bitflip_cbc_target_too_long() {
+        let key = gen_random_16_bytes();
+        let crypter = AesCbcWrapper::new(&key, None, true);
+        let so = ServerOracle::new(&crypter);
+
+        assert!(plan_bitflip(&so, b";role=root;admin=true;", BitflipMode::Cbc).is_err());
+    }
+}
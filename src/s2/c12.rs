@@ -9,10 +9,7 @@ use crate::symmetric::aes::*;
 
 pub mod oracle {
     use super::*;
-
-    pub trait IsOracle {
-        fn encrypt(&self, plaintext: &[u8]) -> Vec<u8>;
-    }
+    use crate::oracles::EncryptionOracle;
 
     pub struct AesOracleCore<'a> {
         pub secret: &'a [u8],
@@ -49,7 +46,7 @@ pub mod oracle {
     pub struct AesOracle<'a> {
         oracle_core: &'a AesOracleCore<'a>
     }
-    impl<'a> IsOracle for AesOracle<'a> {
+    impl<'a> EncryptionOracle for AesOracle<'a> {
         fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
             let mut plaintext_with_secret: Vec<u8> = plaintext.to_vec();
             plaintext_with_secret.extend_from_slice(self.oracle_core.secret);
@@ -67,7 +64,7 @@ pub mod oracle {
     pub struct AesPrefixOracle<'a> {
         oracle_core: &'a AesOracleCore<'a>
     }
-    impl<'a> IsOracle for AesPrefixOracle<'a> {
+    impl<'a> EncryptionOracle for AesPrefixOracle<'a> {
         fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
             let mut plaintext_with_secret: Vec<u8> = self.oracle_core.prefix.clone();
             plaintext_with_secret.extend_from_slice(plaintext);
@@ -85,7 +82,7 @@ pub mod oracle {
 }
 
 pub mod attacker {
-    use super::oracle::*;
+    use crate::oracles::EncryptionOracle;
 
     fn are_blocks_equal(block_size: usize, block_num: usize, b1: &[u8], b2: &[u8]) -> bool {
         let target_block_start = block_num * block_size;
@@ -96,7 +93,7 @@ pub mod attacker {
         s1 == s2
     }
 
-    pub fn get_oracle_block_size<T: IsOracle>(oracle: &T) -> usize {
+    pub fn get_oracle_block_size<T: EncryptionOracle>(oracle: &T) -> usize {
 
         let mut size_last = oracle.encrypt(&['A' as u8]).len();
         let mut size_changed: bool = false;
@@ -146,7 +143,7 @@ pub mod attacker {
     }
 
     // Returns (blocks_length, start_index, user_length, total_len)
-    fn get_user_data_start_block_<T: IsOracle>(oracle: &T, block_size: usize, padding: u8) -> (usize, usize, usize, usize) {
+    fn get_user_data_start_block_<T: EncryptionOracle>(oracle: &T, block_size: usize, padding: u8) -> (usize, usize, usize, usize) {
         let mut test_vec = vec![padding; 0];
 
         let initial: Vec<(usize, usize)> = get_consecutive_equal_ecb_blocks(&oracle.encrypt(&test_vec), block_size);
@@ -167,7 +164,7 @@ pub mod attacker {
     }
 
     // Returns (blocks_length, start_index, user_length, total_len)
-    pub fn get_user_data_start_block<T: IsOracle>(oracle: &T, block_size: usize) -> (usize, usize, usize, usize) {
+    pub fn get_user_data_start_block<T: EncryptionOracle>(oracle: &T, block_size: usize) -> (usize, usize, usize, usize) {
         let res1 = get_user_data_start_block_(oracle, block_size, 2);
         let res2 = get_user_data_start_block_(oracle, block_size, 1);
 
@@ -177,7 +174,7 @@ pub mod attacker {
         res2
     }
 
-    pub fn attack_aes_oracle<T: IsOracle>(oracle: &T) -> Vec<u8> {
+    pub fn attack_aes_oracle<T: EncryptionOracle>(oracle: &T) -> Vec<u8> {
         let block_size = get_oracle_block_size(oracle);
 
         let (_blocks_length, start_index, user_length, total_len) = get_user_data_start_block(oracle, block_size);
@@ -229,6 +226,7 @@ pub mod attacker {
 mod tests {
     use super::*;
     use super::oracle::*;
+    use crate::oracles::EncryptionOracle;
     use crate::s2::c11::detect_ecb_from_stream;
 
     fn run_base64_test(base64_secret: &str) {
@@ -1,5 +1,9 @@
 extern crate rand;
 
+use std::cell::Cell;
+
+use crate::oracles::EncryptionOracle;
+use crate::s2::c9::pad_pkcs7;
 use crate::symmetric::aes::*;
 
 struct UserAccount {
@@ -10,26 +14,27 @@ struct UserAccount {
 
 struct EmailOracle<'a> {
     key: &'a [u8],
-    uid: u32
+    // Bumped on every profile we hand out
+    uid: Cell<u32>
 }
 
 impl<'a> EmailOracle <'a> {
     fn new(key: &'a [u8]) -> EmailOracle {
         EmailOracle {
             key: key,
-            uid: 1
+            uid: Cell::new(1)
         }
     }
 
-    fn profile_for(&mut self, email: &str) -> Vec<u8> {
+    fn profile_for(&self, email: &str) -> Vec<u8> {
         let mut email = str::replace(&email, "&", "");
         email = str::replace(&email, "=", "");
         let plaintext = format!(
             "email={}&uid={}&role=user",
             email,
-            self.uid,
+            self.uid.get(),
         );
-        self.uid += 1;
+        self.uid.set(self.uid.get() + 1);
 
         aes_ecb_encrypt(self.key, plaintext.as_bytes())
     }
@@ -57,39 +62,45 @@ impl<'a> EmailOracle <'a> {
     }
 }
 
+// The attacker only gets to pick the email address
+impl<'a> EncryptionOracle for EmailOracle<'a> {
+    fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        self.profile_for(&String::from_utf8_lossy(plaintext))
+    }
+}
+
+// Expects "email=" + email + "&uid=" + uid + "&role=" to end on a block
+// boundary, so the role is alone in the last block
+fn forge_admin_cookie<T: EncryptionOracle>(oracle: &T, email: &str) -> Vec<u8> {
+    // "email=" + 10 bytes fills the first block, "admin" + padding the second
+    let admin_block_plaintext = pad_pkcs7(b"admin", AES_BLOCK_SIZE).unwrap();
+    let mut attack_email = b"a23456789@".to_vec();
+    attack_email.extend_from_slice(&admin_block_plaintext);
+    attack_email.extend_from_slice(b".com");
+
+    let enc1 = oracle.encrypt(&attack_email);
+    let admin_block_ciphertext = &enc1[AES_BLOCK_SIZE..2 * AES_BLOCK_SIZE];
+
+    let mut enc2 = oracle.encrypt(email.as_bytes());
+    let enc2_len = enc2.len();
+    enc2[enc2_len - AES_BLOCK_SIZE..].copy_from_slice(admin_block_ciphertext);
+    enc2
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use rand::Rng;
-    use crate::s2::c9::pad_pkcs7;
 
     #[test]
     fn test_aes_ecb_copy_paste() {
         let key: [u8; AES_BLOCK_SIZE] = rand::thread_rng().gen();
-        let mut oracle = EmailOracle::new(&key);
-
-        let admin_block_plaintext = pad_pkcs7(b"admin", AES_BLOCK_SIZE).unwrap();
-        let admin_block_plaintext_str = String::from_utf8(admin_block_plaintext).unwrap();
-
-        let mut attack_string: String = String::from("a23456789@");
-        attack_string.push_str(&admin_block_plaintext_str);
-        attack_string.push_str(".com");
-
-        let enc1 = oracle.profile_for(
-            &attack_string
-        );
-
-        // TODO: Maybe create a method to return a block by index
-        let admin_block_ciphertext = &enc1[AES_BLOCK_SIZE..2 * AES_BLOCK_SIZE];
+        let oracle = EmailOracle::new(&key);
 
         let hacker_email = "aesecb@lwn.net";
-        let mut enc2 = oracle.profile_for(hacker_email);
-
-        let enc2_len = enc2.len();
-
-        enc2[enc2_len - AES_BLOCK_SIZE..].copy_from_slice(&admin_block_ciphertext);
+        let cookie = forge_admin_cookie(&oracle, hacker_email);
 
-        let res = oracle.cookie_to_object(&enc2);
+        let res = oracle.cookie_to_object(&cookie);
         assert_eq!(hacker_email, res.email);
         assert_eq!(2, res.uid);
         assert_eq!("admin", res.role);
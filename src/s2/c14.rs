@@ -3,6 +3,7 @@ mod tests {
     use crate::symmetric::aes::*;
 
     use crate::s2::c11::detect_ecb_from_stream;
+    use crate::oracles::EncryptionOracle;
     use crate::s2::c12::oracle::*;
     use crate::s2::c12::attacker;
 
@@ -1,8 +1,6 @@
 use crate::symmetric::aes::*;
-
-pub trait IsOracle {
-    fn encrypt(&self, plaintext: &[u8]) -> Vec<u8>;
-}
+use crate::oracles::EncryptionOracle;
+use crate::s2::bitflip::{plan_bitflip, BitflipMode};
 
 pub struct ServerOracle<'a> {
     crypter: &'a (dyn CryptoWrapper + 'a)
@@ -23,7 +21,7 @@ impl<'a> ServerOracle<'a> {
     }
 }
 
-impl<'a> IsOracle for ServerOracle<'a> {
+impl<'a> EncryptionOracle for ServerOracle<'a> {
     fn encrypt(&self, client_data: &[u8]) -> Vec<u8> {
         let mut vec_contents = "comment1=cooking%20MCs;userdata=".as_bytes().to_vec();
 
@@ -42,17 +40,8 @@ impl<'a> IsOracle for ServerOracle<'a> {
     }
 }
 
-pub fn attack_server<T: IsOracle>(oracle: &T) -> Vec<u8> {
-    // "<" is 0x3C. "=" is 0x3D
-    // ":" is 0x3A. ";" is 0x3B
-    let mut ciphertext = oracle.encrypt(b":admin<true");
-
-    // Need to set the last bit in the 39th byte
-    ciphertext[38 - AES_BLOCK_SIZE] = ciphertext[38 - AES_BLOCK_SIZE] ^ 1;
-
-    // Need to set the last bit in the 33rd byte
-    ciphertext[32 - AES_BLOCK_SIZE] = ciphertext[32 - AES_BLOCK_SIZE] ^ 1;
-    ciphertext
+pub fn attack_server<T: EncryptionOracle>(oracle: &T) -> Vec<u8> {
+    plan_bitflip(oracle, b";admin=true;", BitflipMode::Cbc).unwrap()
 }
 
 #[cfg(test)]
@@ -6,3 +6,4 @@ mod c13;
 mod c14;
 pub mod c15;
 pub mod c16;
+pub mod bitflip;
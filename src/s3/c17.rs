@@ -1,21 +1,18 @@
 use crate::symmetric::aes::*;
 
+use crate::oracles::{CiphertextProvider, PaddingOracle};
+use crate::s1::c6::xor_vecs;
 use crate::s2::c15::padding_validation;
 use crate::utils::decode::base64_from_str;
 
-pub trait IsServerOracle {
-    fn get_ciphertext(&self) -> &[u8];
-    fn check_padding(&self, ciphertext: &[u8]) -> bool;
-}
-
-pub struct ServerOracle<'a> {
-    key: &'a [u8],
+pub struct ServerOracle {
+    key: Vec<u8>,
     ciphertext: Vec<u8>,
     plaintext: Vec<u8>
 }
 
-impl<'a> ServerOracle<'a> {
-    fn new(key: &'a [u8], ind: usize) -> ServerOracle<'a> {
+impl ServerOracle {
+    fn new(key: &[u8], ind: usize) -> ServerOracle {
 
         let plaintexts = [
             "MDAwMDAwTm93IHRoYXQgdGhlIHBhcnR5IGlzIGp1bXBpbmc=",
@@ -32,18 +29,23 @@ impl<'a> ServerOracle<'a> {
 
         let ind_bounded = usize::min(ind, plaintexts.len() - 1);
         ServerOracle {
-            key: key,
+            key: key.to_vec(),
             plaintext: base64_from_str(plaintexts[ind_bounded]),
             ciphertext: aes_cbc_encrypt(key, &base64_from_str(plaintexts[ind_bounded]), None)
         }
     }
 }
 
-impl<'a> IsServerOracle for ServerOracle<'a> {
-    fn get_ciphertext(&self) -> &[u8] {
-        &self.ciphertext
+impl CiphertextProvider for ServerOracle {
+    fn get_ciphertext(&self, ind: usize) -> Option<Vec<u8>> {
+        match ind {
+            0 => Some(self.ciphertext.clone()),
+            _ => None
+        }
     }
+}
 
+impl PaddingOracle for ServerOracle {
     fn check_padding(&self, ciphertext: &[u8]) -> bool {
         let iv = [0u8; AES_BLOCK_SIZE]; // We just so happen to know what the IV is
         let pt = aes_cbc_decrypt_nopad(&self.key, ciphertext, &iv);
@@ -54,17 +56,16 @@ impl<'a> IsServerOracle for ServerOracle<'a> {
     }
 }
 
-// Expects ct to be of length 32
-// Returns the plaintext of the second block
-// We could inline this for better performance
-fn attacker_decrypt_block<T: IsServerOracle>(oracle: &T, ct: &[u8]) -> Vec<u8> {
-    assert_eq!(ct.len(), 32);
+// Expects block to be of length 16
+// Returns the intermediate state of the block, i.e. the output of the
+// block cipher before it gets XORed with the previous ciphertext block
+fn attacker_intermediate_block<T: PaddingOracle>(oracle: &T, block: &[u8]) -> Vec<u8> {
+    assert_eq!(block.len(), AES_BLOCK_SIZE);
     let mut new_ciphertext = gen_random_16_bytes().to_vec();
-    new_ciphertext.extend_from_slice(&ct[AES_BLOCK_SIZE..2 * AES_BLOCK_SIZE]);
+    new_ciphertext.extend_from_slice(block);
 
     let mut original_ct = new_ciphertext.to_vec();
 
-    let mut result = vec![0u8; AES_BLOCK_SIZE];
     let mut intermediate = vec![0u8; AES_BLOCK_SIZE];
 
     let mut ambiguity: usize = 0;
@@ -77,7 +78,7 @@ fn attacker_decrypt_block<T: IsServerOracle>(oracle: &T, ct: &[u8]) -> Vec<u8> {
             // We got some bad random numbers which require too much backtracking
             // Just restart
             new_ciphertext = gen_random_16_bytes().to_vec();
-            new_ciphertext.extend_from_slice(&ct[AES_BLOCK_SIZE..2 * AES_BLOCK_SIZE]);
+            new_ciphertext.extend_from_slice(block);
             original_ct = new_ciphertext.to_vec();
             i = AES_BLOCK_SIZE - 1;
             ambiguity = 0;
@@ -108,7 +109,6 @@ fn attacker_decrypt_block<T: IsServerOracle>(oracle: &T, ct: &[u8]) -> Vec<u8> {
                     }
                     backtracking = false;
                     intermediate[i] = padding_val as u8 ^ new_ciphertext[i];
-                    result[i] = ct[i] ^ intermediate[i];
                     valid = true;
                     println!("Found byte {} {}, A: {}", i, v, ambiguity);
                     break;
@@ -135,13 +135,21 @@ fn attacker_decrypt_block<T: IsServerOracle>(oracle: &T, ct: &[u8]) -> Vec<u8> {
         i -= 1;
     }
 
-    result
+    intermediate
+}
+
+// Expects ct to be of length 32
+// Returns the plaintext of the second block
+fn attacker_decrypt_block<T: PaddingOracle>(oracle: &T, ct: &[u8]) -> Vec<u8> {
+    assert_eq!(ct.len(), 32);
+    let intermediate = attacker_intermediate_block(oracle, &ct[AES_BLOCK_SIZE..2 * AES_BLOCK_SIZE]);
+    xor_vecs(&ct[0..AES_BLOCK_SIZE], &intermediate).unwrap()
 }
 
 // Good article on this attack:
 // https://robertheaton.com/2013/07/29/padding-oracle-attack/
-fn attacker<T: IsServerOracle>(oracle: &T) -> Vec<u8> {
-    let ct = oracle.get_ciphertext();
+fn attacker<T: PaddingOracle + CiphertextProvider>(oracle: &T) -> Vec<u8> {
+    let ct = oracle.get_ciphertext(0).unwrap();
     let mut res: Vec<u8> = Vec::new();
 
     for i in 0..ct.len() / 16 {
@@ -164,9 +172,42 @@ fn attacker<T: IsServerOracle>(oracle: &T) -> Vec<u8> {
     res
 }
 
+// CBC-R: the padding oracle lets us decrypt any block we want, so we can
+// work backwards from a random final block and pick each previous block
+// such that it XORs the intermediate state into our chosen plaintext.
+// Returns the IV followed by the ciphertext blocks.
+pub fn attacker_encrypt<T: PaddingOracle>(oracle: &T, plaintext: &[u8]) -> Vec<u8> {
+    // Unlike `pad_pkcs7` we always need to add padding, since the server
+    // will strip the last byte no matter what
+    let padding_len = AES_BLOCK_SIZE - plaintext.len() % AES_BLOCK_SIZE;
+    let mut padded = plaintext.to_vec();
+    padded.resize(plaintext.len() + padding_len, padding_len as u8);
+
+    let num_blocks = padded.len() / AES_BLOCK_SIZE;
+    let mut res = vec![0u8; (num_blocks + 1) * AES_BLOCK_SIZE];
+    res[num_blocks * AES_BLOCK_SIZE..].copy_from_slice(&gen_random_16_bytes());
+
+    for i in (0..num_blocks).rev() {
+        println!("Forging block # {}", i);
+        let intermediate = attacker_intermediate_block(
+            oracle,
+            &res[(i + 1) * AES_BLOCK_SIZE..(i + 2) * AES_BLOCK_SIZE]
+        );
+        let prev_block = xor_vecs(
+            &intermediate,
+            &padded[i * AES_BLOCK_SIZE..(i + 1) * AES_BLOCK_SIZE]
+        ).unwrap();
+        res[i * AES_BLOCK_SIZE..(i + 1) * AES_BLOCK_SIZE].copy_from_slice(&prev_block);
+    }
+
+    res
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::oracles::http::{HttpOracle, OracleServer};
+    use std::convert::TryInto;
 
     fn run_oracle_test(ind: usize) {
         let key = gen_random_16_bytes();
@@ -183,4 +224,46 @@ mod tests {
             println!("Test # {}", i);
         }
     }
+
+    fn run_forge_test(pt: &[u8]) {
+        let key = gen_random_16_bytes();
+        let so = ServerOracle::new(&key, 0);
+
+        let forged = attacker_encrypt(&so, pt);
+        assert_eq!(forged.len() % AES_BLOCK_SIZE, 0);
+        assert!(so.check_padding(&forged));
+
+        let iv: [u8; AES_BLOCK_SIZE] = forged[0..AES_BLOCK_SIZE].try_into().unwrap();
+        assert_eq!(
+            aes_cbc_decrypt(&key, &forged[AES_BLOCK_SIZE..], Some(iv)).unwrap(),
+            pt
+        );
+    }
+
+    #[test]
+    fn test_cbc_padding_oracle_encrypt() {
+        run_forge_test(b"user=admin;expires=never;sig=none");
+    }
+
+    #[test]
+    fn test_cbc_padding_oracle_encrypt_block_aligned() {
+        run_forge_test(b"YELLOW SUBMARINE");
+        run_forge_test(b"");
+    }
+
+    #[test]
+    fn test_cbc_padding_oracle_encrypt_over_http() {
+        let key = gen_random_16_bytes();
+        let server = OracleServer::serve_padding(ServerOracle::new(&key, 0));
+        let oracle = HttpOracle::new(&server.url());
+
+        let pt = b"user=admin;sig=none";
+        let forged = attacker_encrypt(&oracle, pt);
+
+        let iv: [u8; AES_BLOCK_SIZE] = forged[0..AES_BLOCK_SIZE].try_into().unwrap();
+        assert_eq!(
+            aes_cbc_decrypt(&key, &forged[AES_BLOCK_SIZE..], Some(iv)).unwrap(),
+            pt
+        );
+    }
 }
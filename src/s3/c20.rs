@@ -1,11 +1,8 @@
 use crate::symmetric::aes::*;
 
+use crate::oracles::CiphertextProvider;
 use crate::s1::c4::xor_break;
 
-trait CtProvider {
-    fn get_ct(&self, ind: usize) -> Option<&[u8]>;
-}
-
 struct CtManager {
     key: Vec<u8>,
     ciphertexts: Vec<Vec<u8>>
@@ -31,19 +28,19 @@ impl CtManager {
     }
 }
 
-impl CtProvider for CtManager {
-    fn get_ct(&self, ind: usize) -> Option<&[u8]> {
-        self.get_ct(ind)
+impl CiphertextProvider for CtManager {
+    fn get_ciphertext(&self, ind: usize) -> Option<Vec<u8>> {
+        self.get_ct(ind).map(|ct| ct.to_vec())
     }
 }
 
 // Returns the keystream
-fn attacker<T: CtProvider>(ct_provider: &T) -> Vec<u8> {
+fn attacker<T: CiphertextProvider>(ct_provider: &T) -> Vec<u8> {
     let mut ind: usize = 0;
     let mut cols: Vec<Vec<u8>> = Vec::new();
 
     loop {
-        let ct = match ct_provider.get_ct(ind) {
+        let ct = match ct_provider.get_ciphertext(ind) {
             Some(c) => c,
             None => break
         };
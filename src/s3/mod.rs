@@ -4,3 +4,4 @@ mod c20;
 mod c22;
 mod c23;
 mod c24;
+mod poodle;
@@ -0,0 +1,155 @@
+use std::cell::RefCell;
+
+use crate::oracles::PaddingOracle;
+use crate::symmetric::aes::AES_BLOCK_SIZE;
+use crate::symmetric::sslv3::Sslv3Session;
+
+// `check_padding` forwards a (possibly modified) record to the server and
+// tells us if the server accepted it
+pub trait PoodleOracle: PaddingOracle {
+    // Makes the victim send `POST /{path}` with the given body over a fresh
+    // connection. Returns the encrypted record that was sent
+    fn send_request(&self, path: &[u8], body: &[u8]) -> Vec<u8>;
+}
+
+pub struct PoodleTarget {
+    cookie: Vec<u8>,
+    server: RefCell<Sslv3Session>
+}
+
+impl PoodleTarget {
+    pub fn new(cookie: &[u8]) -> PoodleTarget {
+        PoodleTarget {
+            cookie: cookie.to_vec(),
+            server: RefCell::new(Sslv3Session::new())
+        }
+    }
+}
+
+pub fn format_request(path: &[u8], cookie: &[u8], body: &[u8]) -> Vec<u8> {
+    let mut res = b"POST /".to_vec();
+    res.extend_from_slice(path);
+    res.extend_from_slice(b" HTTP/1.1\r\nHost: example.com\r\nCookie: session=");
+    res.extend_from_slice(cookie);
+    res.extend_from_slice(b"\r\n\r\n");
+    res.extend_from_slice(body);
+    res
+}
+
+impl PoodleOracle for PoodleTarget {
+    fn send_request(&self, path: &[u8], body: &[u8]) -> Vec<u8> {
+        // The attacker kills the connection every time the server rejects a
+        // record, so every request ends up negotiating new keys
+        let mut client = Sslv3Session::new();
+        self.server.replace(client.clone());
+        client.seal(&format_request(path, &self.cookie, body))
+    }
+}
+
+impl PaddingOracle for PoodleTarget {
+    fn check_padding(&self, record: &[u8]) -> bool {
+        self.server.borrow_mut().open(record).is_ok()
+    }
+}
+
+pub struct PoodleResult {
+    pub plaintext: Vec<u8>,
+    pub queries: usize
+}
+
+fn get_block(record: &[u8], ind: usize) -> &[u8] {
+    &record[ind * AES_BLOCK_SIZE..(ind + 1) * AES_BLOCK_SIZE]
+}
+
+// Recovers `secret_len` bytes starting at `secret_offset` in the request
+// plaintext (as sent with an empty path and body). The secret has to come
+// after the path since we push it around by making the path longer.
+//
+// Writeup of the attack: https://www.openssl.org/~bodo/ssl-poodle.pdf
+pub fn poodle_attack<T: PoodleOracle>(oracle: &T, secret_offset: usize, secret_len: usize) -> PoodleResult {
+    let mut queries = 0;
+
+    // Grow the body until the record gets an extra block. At that point the
+    // padding is a full block: 15 bytes we don't care about and a length of 15
+    let base_len = oracle.send_request(b"", b"").len();
+    queries += 1;
+
+    let mut full_padding_body_len = 0;
+    for i in 1..=AES_BLOCK_SIZE {
+        queries += 1;
+        if oracle.send_request(b"", &vec![b'A'; i]).len() != base_len {
+            full_padding_body_len = i;
+            break;
+        }
+    }
+    assert_ne!(full_padding_body_len, 0, "Record length never changed");
+
+    let mut plaintext: Vec<u8> = Vec::new();
+    for i in secret_offset..secret_offset + secret_len {
+        // Push byte i to the end of a block, and take the same number of
+        // bytes out of the body so the padding stays a full block
+        let shift = (AES_BLOCK_SIZE - 1 - i % AES_BLOCK_SIZE) % AES_BLOCK_SIZE;
+        let path = vec![b'A'; shift];
+        let body = vec![b'A'; full_padding_body_len + AES_BLOCK_SIZE - shift];
+
+        // +1 because the IV is the first block of the record
+        let target_block = (i + shift) / AES_BLOCK_SIZE + 1;
+
+        loop {
+            let mut record = oracle.send_request(&path, &body);
+            let last_block = record.len() / AES_BLOCK_SIZE - 1;
+
+            let replacement = get_block(&record, target_block).to_vec();
+            record[last_block * AES_BLOCK_SIZE..].copy_from_slice(&replacement);
+            queries += 2;
+
+            // The server accepts the record if the last byte decrypts to 15,
+            // which happens with probability 1/256
+            if oracle.check_padding(&record) {
+                let byte = (AES_BLOCK_SIZE as u8 - 1)
+                    ^ get_block(&record, last_block - 1)[AES_BLOCK_SIZE - 1]
+                    ^ get_block(&record, target_block - 1)[AES_BLOCK_SIZE - 1];
+                println!("Found byte {} {:#04x} after {} queries", i, byte, queries);
+                plaintext.push(byte);
+                break;
+            }
+        }
+    }
+
+    PoodleResult {
+        plaintext,
+        queries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+    use rand::distributions::Alphanumeric;
+
+    #[test]
+    fn test_poodle_attack() {
+        let cookie: Vec<u8> = rand::thread_rng()
+            .sample_iter(Alphanumeric)
+            .take(24)
+            .map(|c| c as u8)
+            .collect();
+        let target = PoodleTarget::new(&cookie);
+
+        // The attacker knows what the browser requests look like, just not the cookie
+        let secret_offset = format_request(b"", b"", b"").len() - b"\r\n\r\n".len();
+
+        let res = poodle_attack(&target, secret_offset, cookie.len());
+        println!("Recovered cookie in {} queries", res.queries);
+        assert_eq!(res.plaintext, cookie);
+        assert!(res.queries > cookie.len());
+    }
+
+    #[test]
+    fn test_poodle_target_accepts_requests() {
+        let target = PoodleTarget::new(b"hunter2");
+        let record = target.send_request(b"index.html", b"a=b");
+        assert!(target.check_padding(&record));
+    }
+}
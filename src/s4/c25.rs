@@ -1,3 +1,5 @@
+use crate::oracles::{CiphertextProvider, EditOracle};
+use crate::s1::c6::xor_vecs;
 use crate::symmetric::aes::*;
 use rand;
 use rand::Rng;
@@ -18,48 +20,60 @@ impl CtrContainer {
             nonce
         }
     }
+}
 
-    fn get_ct(&self) -> Vec<u8> {
-        return self.ct.to_vec();
+impl CiphertextProvider for CtrContainer {
+    fn get_ciphertext(&self, ind: usize) -> Option<Vec<u8>> {
+        match ind {
+            0 => Some(self.ct.to_vec()),
+            _ => None
+        }
     }
+}
 
-    fn edit(&mut self, offset: usize, newtext: &[u8]) {
-        // Special case where the new content would increase the size of the array
-        if offset + newtext.len() >= self.ct.len() {
-            self.ct.truncate(offset);
-
-            let mut pt = aes_ctr_crypt(&self.key, &self.ct, self.nonce);
-            pt.extend_from_slice(newtext);
+impl EditOracle for CtrContainer {
+    fn edit(&self, ciphertext: &[u8], offset: usize, newtext: &[u8]) -> Vec<u8> {
+        let mut pt = aes_ctr_crypt(&self.key, ciphertext, self.nonce);
 
-            self.ct = aes_ctr_crypt(&self.key, &pt, self.nonce);
-            return;
+        // The new content may run past the end (or start past it, in which
+        // case the gap is zero filled)
+        if offset + newtext.len() > pt.len() {
+            pt.resize(offset + newtext.len(), 0);
         }
-
-        let mut pt = aes_ctr_crypt(&self.key, &self.ct, self.nonce);
-        pt[offset..newtext.len()].copy_from_slice(newtext);
-        self.ct = aes_ctr_crypt(&self.key, &pt, self.nonce);
+        pt[offset..offset + newtext.len()].copy_from_slice(newtext);
+        aes_ctr_crypt(&self.key, &pt, self.nonce)
     }
 }
 
+// Writing zeros over everything gives us the keystream
+fn recover_plaintext<T: CiphertextProvider + EditOracle>(oracle: &T) -> Vec<u8> {
+    let orig_ct = oracle.get_ciphertext(0).unwrap();
+    let keystream = oracle.edit(&orig_ct, 0, &vec![0u8; orig_ct.len()]);
+    xor_vecs(&keystream, &orig_ct).unwrap()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::s1::c6::xor_vecs;
 
     #[test]
     fn test_ctr_edit_decrypt() {
         let file_contents: &'static str = include_str!("7ans.hex");
         let raw_bytes: Vec<u8> = hex::decode(file_contents).unwrap();
 
-        let mut cont = CtrContainer::new(gen_random_16_bytes(), &raw_bytes);
+        let cont = CtrContainer::new(gen_random_16_bytes(), &raw_bytes);
+        let pt = recover_plaintext(&cont);
+        assert_eq!(pt, raw_bytes);
+    }
 
-        // Hacking in progress
-        let orig_ct = cont.get_ct();
-        cont.edit(0, &vec![0u8; orig_ct.len()]);
-        let new_ct = cont.get_ct();
-        let pt = xor_vecs(&new_ct, &orig_ct).unwrap();
+    #[test]
+    fn test_edit() {
+        let cont = CtrContainer::new(gen_random_16_bytes(), b"0123456789");
+        let ct = cont.get_ciphertext(0).unwrap();
+        let decrypt = |ct: &[u8]| aes_ctr_crypt(&cont.key, ct, cont.nonce);
 
-        // Hacking done
-        assert_eq!(pt, raw_bytes);
+        assert_eq!(decrypt(&cont.edit(&ct, 2, b"ab")), b"01ab456789".to_vec());
+        assert_eq!(decrypt(&cont.edit(&ct, 8, b"abcd")), b"01234567abcd".to_vec());
+        assert_eq!(decrypt(&cont.edit(&ct, 12, b"ab")), b"0123456789\0\0ab".to_vec());
     }
 }
@@ -1,22 +1,14 @@
-use crate::symmetric::aes::*;
-use crate::s2::c16::IsOracle;
+use crate::oracles::EncryptionOracle;
+use crate::s2::bitflip::{plan_bitflip, BitflipMode};
 
-pub fn attack_server<T: IsOracle>(oracle: &T) -> Vec<u8> {
-    // "<" is 0x3C. "=" is 0x3D
-    // ":" is 0x3A. ";" is 0x3B
-    let mut ciphertext = oracle.encrypt(b":admin<true");
-
-    // Need to set the last bit in the 39th byte
-    ciphertext[38] = ciphertext[38] ^ 1;
-
-    // Need to set the last bit in the 33rd byte
-    ciphertext[32] = ciphertext[32] ^ 1;
-    ciphertext
+pub fn attack_server<T: EncryptionOracle>(oracle: &T) -> Vec<u8> {
+    plan_bitflip(oracle, b";admin=true;", BitflipMode::Ctr).unwrap()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::symmetric::aes::*;
     use crate::s2::c16::ServerOracle;
     use rand::Rng;
 
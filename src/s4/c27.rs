@@ -1,4 +1,5 @@
 use crate::symmetric::aes::*;
+use crate::oracles::{DecryptionOracle, EncryptionOracle};
 use crate::s1::c6::xor_vecs;
 
 struct Server<'a> {
@@ -11,31 +12,35 @@ impl<'a> Server<'a> {
             crypter: crypter
         }
     }
+}
 
-    pub fn encrypt(&self, text: &[u8]) -> Vec<u8> {
+impl<'a> EncryptionOracle for Server<'a> {
+    fn encrypt(&self, text: &[u8]) -> Vec<u8> {
         self.crypter.encrypt(text)
     }
+}
 
-    // Can't implement CryptoWrapper since we have a different return type :(
-    pub fn decrypt(&self, ct: &[u8]) -> Result<usize, Vec<u8>> { // Ok(status code), Err(plaintext)
+impl<'a> DecryptionOracle for Server<'a> {
+    // The server complains about high ASCII and helpfully includes the plaintext
+    fn decrypt(&self, ct: &[u8]) -> Option<Vec<u8>> {
         let pt = self.crypter.decrypt(ct).unwrap();
         for i in &pt {
             if *i >= 128 {
-                return Err(pt);
+                return Some(pt);
             }
         }
-        return Ok(0);
+        None
     }
 }
 
-fn attack_server(ct: &[u8], s: Server) -> Vec<u8> {
+fn attack_server<T: DecryptionOracle>(ct: &[u8], s: &T) -> Vec<u8> {
     let mut new_ct = ct[0..AES_BLOCK_SIZE].to_vec();
     new_ct.extend_from_slice(&[0u8; AES_BLOCK_SIZE]);
     new_ct.extend_from_slice(&ct[0..AES_BLOCK_SIZE]);
     let pt = s.decrypt(&new_ct);
     let vec_pt = match pt {
-        Err(e) => e,
-        Ok(_) => {
+        Some(e) => e,
+        None => {
             panic!("Failed because decryption succeeded");
         }
     };
@@ -57,7 +62,7 @@ mod tests {
         Lit up the world as I fell asleep";
         let ct = s.encrypt(pt.as_bytes());
 
-        let found_key = attack_server(&ct, s);
+        let found_key = attack_server(&ct, &s);
         assert_eq!(key.to_vec(), found_key);
     }
 }
@@ -1,4 +1,6 @@
+use crate::hashing::hmac::constant_time_eq;
 use crate::hashing::sha1::*;
+use crate::oracles::MacVerifyOracle;
 
 pub fn sha1_keyed_mac(content: &[u8], key: &[u8]) -> Vec<u8> {
     let mut to_hash = key.to_vec();
@@ -7,7 +9,25 @@ pub fn sha1_keyed_mac(content: &[u8], key: &[u8]) -> Vec<u8> {
 }
 
 pub fn sha1_verify_mac(content: &[u8], key: &[u8], hash: &[u8]) -> bool {
-    &sha1_keyed_mac(content, key)[..] == hash
+    constant_time_eq(&sha1_keyed_mac(content, key), hash)
+}
+
+pub struct Sha1MacOracle {
+    key: Vec<u8>
+}
+
+impl Sha1MacOracle {
+    pub fn new(key: &[u8]) -> Sha1MacOracle {
+        Sha1MacOracle {
+            key: key.to_vec()
+        }
+    }
+}
+
+impl MacVerifyOracle for Sha1MacOracle {
+    fn verify(&self, message: &[u8], tag: &[u8]) -> bool {
+        sha1_verify_mac(message, &self.key, tag)
+    }
 }
 
 #[cfg(test)]
@@ -1,30 +1,23 @@
 #[cfg(test)]
 mod tests {
-    use tokio::sync::oneshot;
-    use serde::Deserialize;
-    use warp::Filter;
     use std::{thread, time};
-    use rand;
-    use rand::Rng;
-    use rand::distributions::Standard;
-    use hyper::{Body, client::Client, Request};
-    use std::convert::Infallible;
-    use crate::hashing::{hmac::hmac, sha1::{sha1, SHA1_LEN_BYTES}, hash_padding::HASH_BLOCK_LEN_BYTES};
+    use crate::oracles::{MacVerifyOracle, TimingOracle};
+    use crate::oracles::http::{HttpOracle, OracleServer};
+    use crate::oracles::timing::{Noise, SimulatedTiming, WallClock};
+    use crate::s4::timing_attack::{Progress, TimingAttack};
+    use crate::hashing::{hmac::hmac, sha1::{Sha1, SHA1_LEN_BYTES}, hash_padding::HASH_BLOCK_LEN_BYTES};
     // For flushing
     use std::io;
     use std::io::prelude::*;
 
-    // If the test starts failing, bump this up
+    // Only used by the network test. If it starts failing, bump this up
     const SERVER_CMP_SLEEP: u64 = 1;
-    // Or increase this one
-    const SAMPLE_SIZE: usize = 5;
 
-    #[derive(Deserialize)]
-    struct Params {
-        file: String,
-        signature: String,
+    struct HmacServer {
+        key: Vec<u8>
     }
 
+    // Deliberately not Hmac::verify, this early exit is the whole challenge
     fn cmd_bytes_slow(vec1: &[u8], vec2: &[u8]) -> bool {
         if vec1.len() != vec2.len() {
             println!("Your lengths are different...");
@@ -41,133 +34,74 @@ mod tests {
         true
     }
 
-    fn validate(payload: &Params, key: &[u8]) -> bool {
-        let expected = hmac(key, payload.file.as_bytes(), sha1, HASH_BLOCK_LEN_BYTES);
-
-        let decoded = hex::decode(payload.signature.as_bytes()).unwrap();
-        cmd_bytes_slow(&expected, &decoded)
+    impl MacVerifyOracle for HmacServer {
+        fn verify(&self, message: &[u8], tag: &[u8]) -> bool {
+            let expected = hmac::<Sha1>(&self.key, message);
+            cmd_bytes_slow(&expected, tag)
+        }
     }
 
-    // Returns true if the attack succeeded... (?)
-    // `file` might as well be ascii
-    async fn attack_server(file: &[u8]) -> Result<Vec<u8>, ()> {
-        let mut hash = vec![0u8; SHA1_LEN_BYTES];
-
-        let new_file = String::from_utf8_lossy(file);
-
-        let client = Client::new();
-        for index in 0..hash.len() {
-            let mut guess_results = vec![time::Duration::new(0, 0); 256];
-
-            for _ in 0..SAMPLE_SIZE {
-                for guess in 0u8..=255 {
-                    hash[index] = guess;
-
-                    let uri = format!(
-                        "http://localhost:1337/test?file={}&signature={}",
-                        new_file,
-                        &hex::encode(&hash)
-                    );
-
-                    let request = Request::get(
-                        uri
-                    ).body(Body::from("")).unwrap();
-
-                    let start = time::Instant::now();
-                    let response = client.request(request).await.unwrap();
-                    guess_results[guess as usize] = (start.elapsed() + guess_results[guess as usize]) / 2;
-
-                    let status = response.status();
-                    if status == hyper::StatusCode::OK {
-                        println!("\nFound hash on index {}", index);
-                        return Ok(hash)
-                    }
+    // Seeding both the attack and the simulated oracle makes a run
+    // reproducible, so a failure here is a real regression and not bad luck
+    fn attack<T: TimingOracle>(oracle: T, message: &[u8], samples: (usize, usize), seed: u64) -> Vec<u8> {
+        TimingAttack::new(oracle)
+            .with_samples(samples.0, samples.1)
+            .with_seed(seed)
+            .with_progress(|p| {
+                // Used to indicate progress for the impatient types
+                if let Progress::ByteFound { byte, .. } = p {
+                    print!("{:02x}", byte);
+                    io::stdout().flush().unwrap();
                 }
-            }
+            })
+            .recover_tag(message, SHA1_LEN_BYTES)
+            .unwrap()
+            .tag
+    }
 
-            let res = guess_results.iter()
-                .enumerate()
-                .max_by(|(_, a), (_, b)| a.cmp(b))
-                .map(|(index, _)| index).unwrap() as u8;
+    const KEY: &[u8; HASH_BLOCK_LEN_BYTES] = b"Fixed HMAC key so every run of these tests recovers the same tag";
 
-            // Used to indicate progress for the impatient types
-            print!("{:02x}", res);
-            io::stdout().flush().unwrap();
+    const MESSAGE: &[u8] = b"The_spy_has_already_breached_our_defenses";
 
-            hash[index] = res;
-        }
-        println!();
+    #[test]
+    fn break_hmac_non_constant_time() {
+        let expected = hmac::<Sha1>(KEY, MESSAGE);
 
-        let request = Request::get(
-            format!(
-                "http://localhost:1337/test?file={}&signature={}",
-                new_file,
-                &hex::encode(&hash)
-            )
-        ).body(Body::from("")).unwrap();
-        let response = client.request(request);
-
-        if response.await.unwrap().status() == hyper::StatusCode::OK {
-            Ok(hash)
-        } else {
-            println!("Failed to get hash! Result: {}", &hex::encode(&hash));
-            Err(())
-        }
-    }
+        let oracle = SimulatedTiming::new(|m: &[u8]| hmac::<Sha1>(KEY, m))
+            .with_leak(time::Duration::from_millis(5))
+            .with_noise(Noise::Gaussian { std_dev: time::Duration::from_millis(1) })
+            .with_seed(31);
 
-    async fn handle_rejection(err: warp::Rejection) -> Result<impl warp::Reply, Infallible> {
-        println!("{:?}", err);
-        Ok(warp::reply::with_status("", warp::http::StatusCode::BAD_REQUEST))
+        assert_eq!(attack(oracle, MESSAGE, (5, 50), 31), expected);
     }
 
-    #[tokio::test]
-    async fn break_hmac_non_constant_time() {
-        let key: Vec<u8> = rand::thread_rng().sample_iter(Standard).take(
-            HASH_BLOCK_LEN_BYTES
-        ).collect();
-        let key_1 = key.to_vec();
-
-        let broken_endpoint = warp::path!("test")
-            .and(warp::query::<Params>())
-            .map(
-                move |name: Params| {
-                    if validate(&name, &key) {
-                        warp::reply::with_status(":)", warp::http::StatusCode::OK)
-                    } else {
-                        warp::reply::with_status(":(", warp::http::StatusCode::FORBIDDEN)
-                    }
-                }
-            );
+    // Challenge 32, the leak is now much smaller than the noise
+    #[test]
+    fn break_hmac_small_leak() {
+        let expected = hmac::<Sha1>(KEY, MESSAGE);
 
-        let routes = warp::get().and(broken_endpoint).recover(handle_rejection);
+        let oracle = SimulatedTiming::new(|_: &[u8]| expected.clone())
+            .with_leak(time::Duration::from_micros(50))
+            .with_noise(Noise::HeavyTailed { scale: time::Duration::from_micros(100), shape: 1.5 })
+            .with_jitter(time::Duration::from_micros(50))
+            .with_seed(32);
 
-        let (tx, rx) = oneshot::channel::<()>();
+        assert_eq!(attack(oracle, MESSAGE, (5, 500), 32), expected);
+    }
 
-        let (_, server) = warp::serve(routes)
-            .bind_with_graceful_shutdown((
-                [127, 0, 0, 1], 1337),
-                async {
-                    rx.await.ok();
-                }
-            );
+    // Takes around 10 minutes, run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn break_hmac_over_network() {
+        let expected = hmac::<Sha1>(KEY, MESSAGE);
+        println!("Starting attack run. Would like: {}", &hex::encode(&expected));
 
-        tokio::task::spawn(server);
+        let server = OracleServer::serve_mac_verify(HmacServer { key: KEY.to_vec() });
+        let oracle = HttpOracle::new(&server.url());
 
-        let message = "The_spy_has_already_breached_our_defenses".as_bytes();
-        let expected = hmac(&key_1, message, sha1, HASH_BLOCK_LEN_BYTES);
-        println!("Starting attack run. Would like: {}", &hex::encode(&expected));
-        let res = attack_server(&message);
-
-        match res.await {
-            Ok(val) => {
-                assert_eq!(expected, val);
-            },
-            Err(_) => {
-                println!("Failed to get hash! Wanted: {}", &hex::encode(&expected));
-                assert!(false);
-            }
-        }
+        let res = attack(WallClock::new(&oracle), MESSAGE, (5, 50), 33);
+        println!();
 
-        let _ = tx.send(());
+        assert_eq!(expected, res);
     }
 }
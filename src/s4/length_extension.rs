@@ -0,0 +1,121 @@
+// Forges secret prefix MACs, tag = H(key || message), for any Merkle-Damgard
+// hash (challenges 29 and 30).
+//
+// The tag is the whole internal state of H after hashing key, message and
+// padding. We can pick the hash up from there and feed it more data without
+// ever seeing the key. The only thing we need to know is how long the key
+// is, since it decides the padding in the middle of the forged message, so
+// we try every length in a range until the target accepts one.
+use std::ops::Range;
+
+use crate::hashing::hasher::Hasher;
+use crate::oracles::MacVerifyOracle;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Forgery {
+    // message || glue padding || appended data
+    pub message: Vec<u8>,
+    pub tag: Vec<u8>,
+    pub key_len: usize
+}
+
+// Extends `message` assuming the key is exactly `key_len` bytes long
+pub fn extend<H: Hasher>(message: &[u8], tag: &[u8], to_append: &[u8], key_len: usize) -> Forgery {
+    let glue = H::padding((key_len + message.len()) as u64);
+
+    let mut forged = message.to_vec();
+    forged.extend(glue);
+
+    let mut hasher = H::from_state(tag, (key_len + forged.len()) as u64);
+    hasher.update(to_append);
+    forged.extend_from_slice(to_append);
+
+    Forgery {
+        message: forged,
+        tag: hasher.finalize(),
+        key_len
+    }
+}
+
+// Tries every key length in `key_lens` until the oracle accepts a forgery
+pub fn forge<H: Hasher, O: MacVerifyOracle>(
+    oracle: O,
+    message: &[u8],
+    tag: &[u8],
+    to_append: &[u8],
+    key_lens: Range<usize>
+) -> Result<Forgery, &'static str> {
+    key_lens
+        .map(|key_len| extend::<H>(message, tag, to_append, key_len))
+        .find(|forgery| oracle.verify(&forgery.message, &forgery.tag))
+        .ok_or("No key length in the range worked")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::marker::PhantomData;
+    use crate::hashing::md4::Md4;
+    use crate::hashing::md5::Md5;
+    use crate::hashing::sha1::Sha1;
+    use crate::hashing::sha256::Sha256;
+    use crate::hashing::sha512::Sha512;
+
+    const MESSAGE: &[u8] = b"comment1=cooking%20MCs;userdata=foo;comment2=%20like%20a%20pound%20of%20bacon";
+    // Long enough to need more than one block of its own
+    const TO_APPEND: &[u8] = b";admin=true;comment3=%20and%20a%20side%20of%20toast;role=superuser";
+
+    struct SecretPrefixMac<H> {
+        key: Vec<u8>,
+        hash: PhantomData<H>
+    }
+
+    impl<H: Hasher> SecretPrefixMac<H> {
+        fn new(key: Vec<u8>) -> SecretPrefixMac<H> {
+            SecretPrefixMac { key, hash: PhantomData }
+        }
+
+        fn mac(&self, message: &[u8]) -> Vec<u8> {
+            let mut hasher = H::new();
+            hasher.update(&self.key);
+            hasher.update(message);
+            hasher.finalize()
+        }
+    }
+
+    impl<H: Hasher> MacVerifyOracle for SecretPrefixMac<H> {
+        fn verify(&self, message: &[u8], tag: &[u8]) -> bool {
+            self.mac(message) == tag
+        }
+    }
+
+    fn check_forge<H: Hasher>() {
+        let key = crate::rng::vec::rand_len_range(1, 40);
+        let oracle = SecretPrefixMac::<H>::new(key.clone());
+        let tag = oracle.mac(MESSAGE);
+
+        let forgery = forge::<H, _>(&oracle, MESSAGE, &tag, TO_APPEND, 0..64).unwrap();
+
+        assert_eq!(forgery.key_len, key.len());
+        assert!(forgery.message.starts_with(MESSAGE));
+        assert!(forgery.message.ends_with(TO_APPEND));
+        assert_eq!(forgery.tag, oracle.mac(&forgery.message));
+    }
+
+    #[test]
+    fn test_forge_all_hashes() {
+        check_forge::<Sha1>();
+        check_forge::<Md4>();
+        check_forge::<Md5>();
+        check_forge::<Sha256>();
+        check_forge::<Sha512>();
+    }
+
+    #[test]
+    fn test_key_len_out_of_range() {
+        let oracle = SecretPrefixMac::<Sha1>::new(vec![0x42; 20]);
+        let tag = oracle.mac(MESSAGE);
+
+        assert!(forge::<Sha1, _>(&oracle, MESSAGE, &tag, TO_APPEND, 0..20).is_err());
+    }
+}
@@ -5,3 +5,5 @@ mod c28;
 mod c29;
 mod c30;
 mod c31;
+pub mod length_extension;
+pub mod timing_attack;
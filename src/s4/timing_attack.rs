@@ -0,0 +1,434 @@
+// Recovers a MAC from a verifier that compares tags one byte at a time and
+// bails out on the first mismatch (challenges 31 and 32).
+//
+// Every round queries each candidate byte once in a random order, so drift in
+// the target (load, other traffic) hits all candidates the same. We keep
+// sampling until the slowest candidate stands out from the rest by
+// `confidence` standard deviations. If we can't find any signal for a byte, the
+// byte before it was probably wrong and we go back and try again.
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::oracles::TimingOracle;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Estimator {
+    // Ranks the candidates by time in every round and averages the ranks.
+    // Doesn't care what the noise looks like, so it's the default
+    MeanRank,
+    Median,
+    // Drops this fraction of the samples from each end before averaging
+    TrimmedMean(f64)
+}
+
+impl Estimator {
+    // `samples` has to be sorted
+    fn estimate(&self, samples: &[f64]) -> f64 {
+        match *self {
+            Estimator::MeanRank => samples.iter().sum::<f64>() / samples.len() as f64,
+            Estimator::Median => median(samples),
+            Estimator::TrimmedMean(fraction) => {
+                let trim = (samples.len() as f64 * fraction) as usize;
+                let kept = &samples[trim..samples.len() - trim];
+                kept.iter().sum::<f64>() / kept.len() as f64
+            }
+        }
+    }
+
+}
+
+fn median(sorted: &[f64]) -> f64 {
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+fn sort(samples: &mut [f64]) {
+    samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+}
+
+// Replaces every sample with the rank of the candidate in that round
+// (0 for the fastest). Every candidate is sampled once per round
+fn rank_by_round(candidates: &[u8], samples: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let rounds = samples[candidates[0] as usize].len();
+    let mut ranks = vec![Vec::with_capacity(rounds); candidates.len()];
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+
+    let mut columns: Vec<_> = candidates.iter().map(|&c| samples[c as usize].iter()).collect();
+
+    for _ in 0..rounds {
+        let times: Vec<f64> = columns.iter_mut().map(|col| *col.next().unwrap()).collect();
+        order.sort_by(|&a, &b| times[a].partial_cmp(&times[b]).unwrap());
+        for (rank, &i) in order.iter().enumerate() {
+            ranks[i].push(rank as f64);
+        }
+    }
+
+    ranks
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Progress {
+    ByteFound { index: usize, byte: u8, samples: usize, confidence: f64 },
+    Backtrack { index: usize }
+}
+
+pub struct TimingAttackResult {
+    pub tag: Vec<u8>,
+    pub queries: usize,
+    pub backtracks: usize
+}
+
+enum Guess {
+    Valid,
+    Byte { byte: u8, samples: usize, confidence: f64 },
+    NoSignal
+}
+
+type ProgressFn<'a> = Box<dyn FnMut(&Progress) + 'a>;
+
+// let res = TimingAttack::new(&oracle)
+//     .with_estimator(Estimator::TrimmedMean(0.2))
+//     .with_progress(|p| println!("{:?}", p))
+//     .recover_tag(b"file", SHA1_LEN_BYTES);
+pub struct TimingAttack<'a, T> {
+    oracle: T,
+    estimator: Estimator,
+    min_samples: usize,
+    max_samples: usize,
+    confidence: f64,
+    max_backtracks: usize,
+    seed: Option<u64>,
+    progress: Option<ProgressFn<'a>>
+}
+
+impl<'a, T: TimingOracle> TimingAttack<'a, T> {
+    pub fn new(oracle: T) -> TimingAttack<'a, T> {
+        TimingAttack {
+            oracle,
+            estimator: Estimator::MeanRank,
+            min_samples: 5,
+            max_samples: 50,
+            confidence: 5.0,
+            max_backtracks: 10,
+            seed: None,
+            progress: None
+        }
+    }
+
+    pub fn with_estimator(mut self, estimator: Estimator) -> TimingAttack<'a, T> {
+        self.estimator = estimator;
+        self
+    }
+
+    // Samples per candidate byte. We won't decide before `min` and give up
+    // on the byte after `max`
+    pub fn with_samples(mut self, min: usize, max: usize) -> TimingAttack<'a, T> {
+        assert!(min >= 1 && min <= max, "Need 1 <= min <= max samples");
+        self.min_samples = min;
+        self.max_samples = max;
+        self
+    }
+
+    // In standard deviations of the other candidates. With 255 wrong
+    // guesses one of them is often 3 away just by chance, so don't go much
+    // lower than 4
+    pub fn with_confidence(mut self, confidence: f64) -> TimingAttack<'a, T> {
+        self.confidence = confidence;
+        self
+    }
+
+    pub fn with_max_backtracks(mut self, max_backtracks: usize) -> TimingAttack<'a, T> {
+        self.max_backtracks = max_backtracks;
+        self
+    }
+
+    // Fixes the order candidates get queried in. Together with a seeded
+    // SimulatedTiming oracle this makes the whole attack reproducible
+    pub fn with_seed(mut self, seed: u64) -> TimingAttack<'a, T> {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn with_progress<F: FnMut(&Progress) + 'a>(mut self, progress: F) -> TimingAttack<'a, T> {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    fn report(&mut self, progress: Progress) {
+        if let Some(f) = &mut self.progress {
+            f(&progress);
+        }
+    }
+
+    pub fn recover_tag(&mut self, message: &[u8], tag_len: usize) -> Result<TimingAttackResult, &'static str> {
+        let mut tag = vec![0u8; tag_len];
+        // Bytes we already gave up on at every index
+        let mut rejected: Vec<Vec<u8>> = vec![Vec::new(); tag_len];
+        let mut queries = 0;
+        let mut backtracks = 0;
+        let mut index = 0;
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy()
+        };
+
+        while index < tag_len {
+            match self.guess_byte(message, &mut tag, index, &rejected[index], &mut queries, &mut rng) {
+                Guess::Valid => {
+                    return Ok(TimingAttackResult {
+                        tag,
+                        queries,
+                        backtracks
+                    });
+                },
+                Guess::Byte { byte, samples, confidence } => {
+                    tag[index] = byte;
+                    self.report(Progress::ByteFound { index, byte, samples, confidence });
+                    index += 1;
+                },
+                Guess::NoSignal => {
+                    if index == 0 || backtracks == self.max_backtracks {
+                        return Err("No timing signal left to follow");
+                    }
+
+                    backtracks += 1;
+                    rejected[index].clear();
+                    index -= 1;
+                    rejected[index].push(tag[index]);
+                    self.report(Progress::Backtrack { index });
+                }
+            }
+        }
+
+        Err("Recovered every byte but the oracle rejected the tag")
+    }
+
+    fn guess_byte(
+        &self,
+        message: &[u8],
+        tag: &mut [u8],
+        index: usize,
+        rejected: &[u8],
+        queries: &mut usize,
+        rng: &mut StdRng
+    ) -> Guess {
+        let mut order: Vec<u8> = (0..=255u8).filter(|b| !rejected.contains(b)).collect();
+        if order.is_empty() {
+            return Guess::NoSignal;
+        }
+
+        let mut samples = vec![Vec::new(); 256];
+        // Looking at the numbers is expensive with lots of samples, so do it
+        // less and less often
+        let mut next_check = self.min_samples;
+
+        for round in 1..=self.max_samples {
+            order.shuffle(rng);
+            for &guess in order.iter() {
+                tag[index] = guess;
+                let (valid, elapsed) = self.oracle.timed_verify(message, tag);
+                *queries += 1;
+
+                if valid {
+                    return Guess::Valid;
+                }
+                samples[guess as usize].push(elapsed.as_nanos() as f64);
+            }
+
+            // The last byte doesn't leak anything, one of them just works
+            if index == tag.len() - 1 {
+                return Guess::NoSignal;
+            }
+
+            if round == next_check || round == self.max_samples {
+                let (byte, confidence) = self.pick_slowest(&order, &samples);
+                if confidence >= self.confidence {
+                    return Guess::Byte { byte, samples: round, confidence };
+                }
+                next_check = usize::max(round + 1, round * 5 / 4);
+            }
+        }
+
+        Guess::NoSignal
+    }
+
+    // Returns the slowest candidate and how far it stands out from the
+    // estimates of all the other candidates
+    fn pick_slowest(&self, candidates: &[u8], samples: &[Vec<f64>]) -> (u8, f64) {
+        let per_candidate = match self.estimator {
+            Estimator::MeanRank => rank_by_round(candidates, samples),
+            _ => candidates.iter().map(|&c| samples[c as usize].clone()).collect()
+        };
+
+        let mut estimates: Vec<(f64, u8)> = per_candidate.into_iter()
+            .zip(candidates.iter())
+            .map(|(mut sorted, &c)| {
+                sort(&mut sorted);
+                (self.estimator.estimate(&sorted), c)
+            })
+            .collect();
+
+        estimates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+        let (best, byte) = estimates[0];
+        if estimates.len() < 3 {
+            return (byte, f64::INFINITY);
+        }
+
+        // The wrong guesses all take the same time on average, so their
+        // estimates tell us how noisy an estimate is. Median and MAD (scaled
+        // by 1.4826 to a standard deviation) don't care about the occasional
+        // outlier.
+        let mut others: Vec<f64> = estimates[1..].iter().map(|e| e.0).collect();
+        sort(&mut others);
+        let center = median(&others);
+
+        let mut deviations: Vec<f64> = others.iter().map(|x| (x - center).abs()).collect();
+        sort(&mut deviations);
+        let sigma = 1.4826 * median(&deviations);
+
+        let confidence = if sigma > 0.0 {
+            (best - center) / sigma
+        } else if best > center {
+            f64::INFINITY
+        } else {
+            0.0
+        };
+
+        (byte, confidence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oracles::timing::{Noise, SimulatedTiming};
+    use rand::Rng;
+    use std::time::Duration;
+
+    // Takes 1ms per matching byte plus some uniform noise. Optionally makes
+    // one wrong first byte look even better than the right one
+    struct LeakyOracle {
+        tag: Vec<u8>,
+        decoy: Option<u8>
+    }
+
+    impl TimingOracle for LeakyOracle {
+        fn timed_verify(&self, _message: &[u8], tag: &[u8]) -> (bool, Duration) {
+            let matching = tag.iter().zip(self.tag.iter()).take_while(|(a, b)| a == b).count();
+            let mut micros = 1000 * matching as u64 + rand::thread_rng().gen_range(0, 300);
+            if self.decoy == Some(tag[0]) {
+                micros += 1500;
+            }
+            (matching == self.tag.len(), Duration::from_micros(micros))
+        }
+    }
+
+    #[test]
+    fn test_estimators() {
+        assert_eq!(Estimator::MeanRank.estimate(&[1.0, 2.0, 6.0]), 3.0);
+        assert_eq!(Estimator::Median.estimate(&[1.0, 2.0, 100.0]), 2.0);
+        assert_eq!(Estimator::Median.estimate(&[1.0, 2.0, 4.0, 100.0]), 3.0);
+        assert_eq!(Estimator::TrimmedMean(0.25).estimate(&[1.0, 2.0, 4.0, 100.0]), 3.0);
+        assert_eq!(Estimator::TrimmedMean(0.0).estimate(&[1.0, 2.0, 6.0]), 3.0);
+    }
+
+    #[test]
+    fn test_rank_by_round() {
+        let samples = vec![vec![5.0, 1.0], vec![3.0, 2.0], vec![4.0, 0.0]];
+        assert_eq!(
+            rank_by_round(&[0, 1, 2], &samples),
+            vec![vec![2.0, 1.0], vec![0.0, 2.0], vec![1.0, 0.0]]
+        );
+    }
+
+    #[test]
+    fn test_recover_tag_median() {
+        let oracle = LeakyOracle { tag: b"abc".to_vec(), decoy: None };
+        let res = TimingAttack::new(&oracle)
+            .with_estimator(Estimator::Median)
+            .recover_tag(b"", 3)
+            .unwrap();
+        assert_eq!(res.tag, oracle.tag);
+    }
+
+    #[test]
+    fn test_recover_tag() {
+        let oracle = LeakyOracle { tag: b"\x00\xffhi".to_vec(), decoy: None };
+
+        let mut found = Vec::new();
+        let res = TimingAttack::new(&oracle)
+            .with_progress(|p| found.push(p.clone()))
+            .recover_tag(b"", 4)
+            .unwrap();
+
+        assert_eq!(res.tag, oracle.tag);
+        assert_eq!(res.backtracks, 0);
+        assert_eq!(found.len(), 3);
+        assert!(res.queries >= 3 * 5 * 256);
+    }
+
+    #[test]
+    fn test_recover_tag_trimmed_mean() {
+        let oracle = LeakyOracle { tag: b"abc".to_vec(), decoy: None };
+        let res = TimingAttack::new(&oracle)
+            .with_estimator(Estimator::TrimmedMean(0.2))
+            .recover_tag(b"", 3)
+            .unwrap();
+        assert_eq!(res.tag, oracle.tag);
+    }
+
+    #[test]
+    fn test_recover_tag_backtracks() {
+        let oracle = LeakyOracle { tag: b"abc".to_vec(), decoy: Some(b'z') };
+
+        let mut backtracked = false;
+        let res = TimingAttack::new(&oracle)
+            .with_samples(5, 20)
+            .with_progress(|p| {
+                if let Progress::Backtrack { index: 0 } = p {
+                    backtracked = true;
+                }
+            })
+            .recover_tag(b"", 3)
+            .unwrap();
+
+        assert_eq!(res.tag, oracle.tag);
+        assert!(res.backtracks >= 1);
+        assert!(backtracked);
+    }
+
+    #[test]
+    fn test_no_signal() {
+        let oracle = LeakyOracle { tag: b"abc".to_vec(), decoy: Some(b'z') };
+        let res = TimingAttack::new(&oracle)
+            .with_samples(5, 10)
+            .with_max_backtracks(0)
+            .recover_tag(b"", 3);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_seeded_attack_is_reproducible() {
+        let run = || {
+            let oracle = SimulatedTiming::new(|_: &[u8]| b"abcd".to_vec())
+                .with_leak(Duration::from_micros(20))
+                .with_noise(Noise::Gaussian { std_dev: Duration::from_micros(50) })
+                .with_seed(1);
+            let res = TimingAttack::new(&oracle)
+                .with_samples(5, 500)
+                .with_seed(2)
+                .recover_tag(b"", 4)
+                .unwrap();
+            (res.tag, res.queries)
+        };
+
+        let (tag, queries) = run();
+        assert_eq!(tag, b"abcd");
+        assert_eq!(run(), (tag, queries));
+    }
+}
@@ -1,7 +1,9 @@
 #[cfg(test)]
 mod tests {
     use super::super::peer::Peer;
+    use crate::hashing::kdf::{Hkdf, Kdf};
     use crate::hashing::sha1::sha1;
+    use crate::hashing::sha256::Sha256;
     use crate::symmetric::aes::*;
     use std::convert::TryInto;
 
@@ -53,4 +55,28 @@ mod tests {
             assert_eq!(attacker_pt.unwrap(), data);
         }
     }
+
+    #[test]
+    fn test_dh_mitm_with_hkdf() {
+        // A proper KDF doesn't help, the attacker still knows the secret
+        let kdf = || Hkdf::<Sha256>::new(b"c34 session key");
+        let mut alice = Peer::nist().with_kdf(kdf());
+        let mut bob = Peer::new(&alice.dh.p, &alice.dh.g).with_kdf(kdf());
+
+        bob.make_session_key(&alice.dh.p);
+        alice.make_session_key(&bob.dh.p);
+
+        let data = b"Intruder alert! Red Spy is in the base!";
+        let ct = alice.aes_encrypt(data);
+        assert_eq!(bob.aes_decrypt(&ct).unwrap(), data);
+
+        let shared_key = kdf().derive(&[0u8], &[], AES_BLOCK_SIZE);
+        let ct_len = ct.len();
+        let attacker_pt = aes_cbc_decrypt(
+            &shared_key,
+            &ct[..ct_len - AES_BLOCK_SIZE],
+            Some(ct[ct_len - AES_BLOCK_SIZE..].try_into().unwrap())
+        );
+        assert_eq!(attacker_pt.unwrap(), data);
+    }
 }
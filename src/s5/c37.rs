@@ -7,8 +7,9 @@ mod tests {
     use num_bigint::Sign::Plus;
     use tokio::sync::oneshot;
     use hyper::{client::Client, Request, Body};
+    use hyper::http::StatusCode;
     use serde::{Serialize, Deserialize};
-    use openssl::sha::sha256;
+    use crate::hashing::sha256::sha256;
 
     pub type Db = Arc<RwLock<HashMap<String, SrpServer>>>;
 
@@ -22,21 +23,28 @@ mod tests {
         warp::any().map(move || db.clone())
     }
 
-    fn send_public_key(user_db: Db) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    fn send_public_key(user_db: Db, insecure: bool) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
         warp::path!("send_public_key")
             .and(warp::body::form())
             .and(with_db(user_db))
             .and(warp::post()).and_then(
-                |params: HashMap<String, String>, db: Db| async move {
+                move |params: HashMap<String, String>, db: Db| async move {
                     match params.get(&String::from("username")) {
                         Some(username) => {
                             println!("Found username {}", username);
                             let mut server = SrpServer::new("hunter2".as_bytes());
+                            if insecure {
+                                server = server.insecure_skip_public_key_check();
+                            }
                             let pkey = hex::decode(params.get("pkey").unwrap()).unwrap();
 
-                            let (salt, server_pkey) = server.initial_req(
-                                &BigInt::from_bytes_be(Plus, &pkey)
-                            ).unwrap();
+                            let (salt, server_pkey) = match server.initial_req(&BigInt::from_bytes_be(Plus, &pkey)) {
+                                Ok(res) => res,
+                                Err(e) => {
+                                    let body = warp::reply::json(&format!("{:?}", e));
+                                    return Ok(warp::reply::with_status(body, StatusCode::BAD_REQUEST));
+                                }
+                            };
 
                             let mut db = db.write().unwrap();
                             db.insert(From::from(username), server);
@@ -44,7 +52,7 @@ mod tests {
                             let mut res: HashMap<String, String> = HashMap::new();
                             res.insert(String::from("salt"), hex::encode(&salt.to_bytes_be().1));
                             res.insert(String::from("pkey"), hex::encode(&server_pkey.to_bytes_be().1));
-                            return Ok(warp::reply::json(&res));
+                            return Ok(warp::reply::with_status(warp::reply::json(&res), StatusCode::OK));
                         },
                         None => {
                             // Need a username to connect
@@ -63,7 +71,11 @@ mod tests {
                 |params: HashMap<String, String>, db: Db| async move {
                     let hmac = hex::decode(params.get("hmac").unwrap()).unwrap();
                     let db = db.write().unwrap();
-                    let server = db.get(From::from(params.get("username").unwrap())).unwrap();
+                    let server = match db.get(params.get("username").unwrap()) {
+                        Some(server) => server,
+                        // Never got as far as agreeing on a key
+                        None => return Err(warp::reject::not_found())
+                    };
 
                     match server.is_ok(&hmac) {
                         true => return Ok(warp::reply::reply()),
@@ -76,21 +88,31 @@ mod tests {
             )
     }
 
-    fn srp_endpoint(user_db: Db) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    fn srp_endpoint(user_db: Db, insecure: bool) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
         warp::path("login")
             .and(
-                send_public_key(user_db.clone()).or(verify(user_db.clone()))
+                send_public_key(user_db.clone(), insecure).or(verify(user_db.clone()))
             )
     }
 
-    fn start_server(port: u16, user_db: Db) -> tokio::sync::oneshot::Sender<()> {
+    // Binds to an ephemeral port, returns the base url of the server
+    fn start_server(user_db: Db) -> (String, tokio::sync::oneshot::Sender<()>) {
+        start(user_db, false)
+    }
+
+    // Doesn't check the client's public key, so challenge 37 works
+    fn start_insecure_server(user_db: Db) -> (String, tokio::sync::oneshot::Sender<()>) {
+        start(user_db, true)
+    }
+
+    fn start(user_db: Db, insecure: bool) -> (String, tokio::sync::oneshot::Sender<()>) {
         let (tx, rx) = oneshot::channel::<()>();
 
-        let route = srp_endpoint(user_db);
+        let route = srp_endpoint(user_db, insecure);
 
-        let (_, server) = warp::serve(route)
+        let (addr, server) = warp::serve(route)
             .bind_with_graceful_shutdown((
-                [127, 0, 0, 1], port),
+                [127, 0, 0, 1], 0),
                 async {
                     rx.await.ok();
                 }
@@ -98,7 +120,7 @@ mod tests {
 
         tokio::task::spawn(server);
 
-        tx
+        (format!("http://{}", addr), tx)
     }
 
     #[tokio::test]
@@ -109,10 +131,10 @@ mod tests {
 
         let mut c = SrpClient::new();
 
-        let tx = start_server(1338, db);
+        let (url, tx) = start_server(db);
         let pkey = hex::encode(c.dh.public_key.to_bytes_be().1);
 
-        let req = Request::post("http://127.0.0.1:1338/login/send_public_key")
+        let req = Request::post(format!("{}/login/send_public_key", url))
             .header("content-type", "application/x-www-form-urlencoded")
             .body(Body::from(format!("username=lol&pkey={}", pkey))).unwrap();
 
@@ -130,9 +152,9 @@ mod tests {
             &BigInt::from_bytes_be(Plus, &hex::decode(salt_and_server_pkey.salt).unwrap()),
             &BigInt::from_bytes_be(Plus, &hex::decode(salt_and_server_pkey.pkey).unwrap()),
             "hunter2".as_bytes()
-        );
+        ).unwrap();
 
-        let req = Request::post("http://127.0.0.1:1338/login/verify")
+        let req = Request::post(format!("{}/login/verify", url))
             .header("content-type", "application/x-www-form-urlencoded")
             .body(Body::from(format!("username=lol&hmac={}", hex::encode(c.get_hmac())))).unwrap();
 
@@ -151,9 +173,9 @@ mod tests {
 
         let mut c = SrpClient::new();
 
-        let tx = start_server(1339, db);
+        let (url, tx) = start_insecure_server(db);
 
-        let req = Request::post("http://127.0.0.1:1339/login/send_public_key")
+        let req = Request::post(format!("{}/login/send_public_key", url))
             .header("content-type", "application/x-www-form-urlencoded")
             .body(Body::from("username=lol&pkey=00")).unwrap();
 
@@ -168,14 +190,14 @@ mod tests {
         ).unwrap();
 
         c.attacker_set_shared_key(
-            Some(sha256(&[0u8]).to_vec()),
+            Some(sha256(&vec![0u8])),
             &BigInt::from_bytes_be(
                 Plus,
                 &hex::decode(salt_and_server_pkey.salt).unwrap()
             )
         );
 
-        let req = Request::post("http://127.0.0.1:1339/login/verify")
+        let req = Request::post(format!("{}/login/verify", url))
             .header("content-type", "application/x-www-form-urlencoded")
             .body(Body::from(format!("username=lol&hmac={}", hex::encode(c.get_hmac())))).unwrap();
 
@@ -194,10 +216,10 @@ mod tests {
 
         let mut c = SrpClient::new();
 
-        let tx = start_server(1340, db);
+        let (url, tx) = start_insecure_server(db);
 
         for i in 0..5 {
-            let req = Request::post("http://127.0.0.1:1340/login/send_public_key")
+            let req = Request::post(format!("{}/login/send_public_key", url))
             .header("content-type", "application/x-www-form-urlencoded")
             .body(
                 Body::from(
@@ -222,14 +244,14 @@ mod tests {
             ).unwrap();
 
             c.attacker_set_shared_key(
-                Some(sha256(&[0u8]).to_vec()),
+                Some(sha256(&vec![0u8])),
                 &BigInt::from_bytes_be(
                     Plus,
                     &hex::decode(salt_and_server_pkey.salt).unwrap()
                 )
             );
 
-            let req = Request::post("http://127.0.0.1:1340/login/verify")
+            let req = Request::post(format!("{}/login/verify", url))
                 .header("content-type", "application/x-www-form-urlencoded")
                 .body(Body::from(format!("username=lol&hmac={}", hex::encode(c.get_hmac())))).unwrap();
 
@@ -240,4 +262,37 @@ mod tests {
         // Shut down the server
         tx.send(()).unwrap();
     }
+
+    #[tokio::test]
+    async fn test_hardened_server_rejects_bad_keys() {
+        let db: Db = Arc::new(
+            RwLock::new(HashMap::new())
+        );
+
+        let mut c = SrpClient::new();
+        let (url, tx) = start_server(db);
+        let client = Client::new();
+
+        for i in 0..5 {
+            let pkey = hex::encode(&(&c.dh.p * BigInt::from(i)).to_bytes_be().1);
+            let req = Request::post(format!("{}/login/send_public_key", url))
+                .header("content-type", "application/x-www-form-urlencoded")
+                .body(Body::from(format!("username=lol&pkey={}", pkey))).unwrap();
+
+            let response = client.request(req).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+            // Same forgery as above, but there's no session to log into
+            c.attacker_set_shared_key(Some(sha256(&vec![0u8])), &BigInt::from(0));
+
+            let req = Request::post(format!("{}/login/verify", url))
+                .header("content-type", "application/x-www-form-urlencoded")
+                .body(Body::from(format!("username=lol&hmac={}", hex::encode(c.get_hmac())))).unwrap();
+
+            let response = client.request(req).await.unwrap();
+            assert_ne!(response.status(), StatusCode::OK);
+        }
+
+        tx.send(()).unwrap();
+    }
 }
@@ -0,0 +1,426 @@
+// Offline dictionary attack on the simplified SRP variant (challenge 38).
+//
+// In the variant the server sends u itself and B doesn't depend on the
+// password, so a MITM playing the server never needs the verifier to get
+// the client to send its HMAC. The client computes
+//
+//   S = B^(a + u*x) = (A * g^(u*x))^b
+//
+// and the right hand side only needs A, b, u and the salt, so every password
+// guess can be checked against the HMAC without talking to anyone.
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use num_bigint::BigInt;
+use num_traits::One;
+
+use crate::asymmetric::diffie_hellman::get_nist;
+use crate::asymmetric::srp::password_to_x;
+use crate::hashing::hmac::hmac;
+use crate::hashing::kdf::{HashKdf, Kdf};
+use crate::hashing::sha256::{sha256, Sha256};
+
+// Words handed to a worker at a time
+const BATCH_SIZE: usize = 8;
+
+// Everything a MITM in the server's seat gets to see or choose
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transcript {
+    pub salt: BigInt,
+    pub a_pub: BigInt,
+    // The server's private key, not B. The attacker picked B = g^b
+    pub b: BigInt,
+    pub u: BigInt,
+    pub hmac: Vec<u8>
+}
+
+// Plays the server in the variant protocol without knowing any password.
+// By default b = 1, u = 1 and the salt is 0, which makes a guess cost a
+// single modpow
+pub struct MitmServer {
+    salt: BigInt,
+    b: BigInt,
+    u: BigInt,
+    a_pub: Option<BigInt>
+}
+
+impl MitmServer {
+    pub fn new() -> MitmServer {
+        MitmServer::with_parameters(BigInt::from(0), BigInt::one(), BigInt::one())
+    }
+
+    pub fn with_parameters(salt: BigInt, b: BigInt, u: BigInt) -> MitmServer {
+        MitmServer { salt, b, u, a_pub: None }
+    }
+
+    // Same shape as SrpServer::variant_initial_req: returns (salt, B, u)
+    pub fn variant_initial_req(&mut self, pubkey: &BigInt) -> (BigInt, BigInt, BigInt) {
+        let (p, g) = get_nist();
+        self.a_pub = Some(pubkey.clone());
+        (self.salt.clone(), BigInt::from(g).modpow(&self.b, &p), self.u.clone())
+    }
+
+    // None until the client has sent A
+    pub fn transcript(&self, hmac: &[u8]) -> Option<Transcript> {
+        Some(Transcript {
+            salt: self.salt.clone(),
+            a_pub: self.a_pub.clone()?,
+            b: self.b.clone(),
+            u: self.u.clone(),
+            hmac: hmac.to_vec()
+        })
+    }
+}
+
+fn leetspeak(word: &str) -> String {
+    word.chars()
+        .map(|c| match c.to_ascii_lowercase() {
+            'a' => '4',
+            'e' => '3',
+            'i' => '1',
+            'o' => '0',
+            's' => '5',
+            't' => '7',
+            _ => c
+        })
+        .collect()
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect(),
+        None => String::new()
+    }
+}
+
+// Which variations of each dictionary word to try. Rules stack: with case
+// and leetspeak both on, "Medic" also gets tried as "M3d1c" and "m3d1c"
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Mangling {
+    case: bool,
+    leetspeak: bool,
+    digits: usize
+}
+
+impl Mangling {
+    // Just the word as written
+    pub fn none() -> Mangling {
+        Mangling::default()
+    }
+
+    // lowercase, UPPERCASE and Capitalized
+    pub fn with_case(mut self) -> Mangling {
+        self.case = true;
+        self
+    }
+
+    pub fn with_leetspeak(mut self) -> Mangling {
+        self.leetspeak = true;
+        self
+    }
+
+    // Appends every number of up to `digits` digits, zero padded
+    pub fn with_digits(mut self, digits: usize) -> Mangling {
+        assert!(digits <= 6, "That's a lot of guesses per word");
+        self.digits = digits;
+        self
+    }
+
+    // The word itself always comes first
+    pub fn candidates(&self, word: &str) -> Vec<String> {
+        let mut words = vec![word.to_string()];
+
+        if self.case {
+            words.extend(vec![word.to_lowercase(), word.to_uppercase(), capitalize(word)]);
+        }
+
+        if self.leetspeak {
+            let leet: Vec<String> = words.iter().map(|w| leetspeak(w)).collect();
+            words.extend(leet);
+        }
+
+        let mut seen = HashSet::new();
+        words.retain(|w| seen.insert(w.clone()));
+
+        let mut suffixed = vec![];
+        for len in 1..=self.digits {
+            for n in 0..10usize.pow(len as u32) {
+                for w in words.iter() {
+                    suffixed.push(format!("{}{:0len$}", w, n, len = len));
+                }
+            }
+        }
+
+        words.extend(suffixed);
+        words
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Progress {
+    pub words_tried: usize,
+    pub words_total: usize,
+    // Including every mangled variant
+    pub guesses: usize
+}
+
+enum Message {
+    Batch { words: usize, guesses: usize },
+    Found(String)
+}
+
+type ProgressFn<'a> = Box<dyn FnMut(&Progress) + 'a>;
+
+// The part every worker thread shares
+struct Guesser {
+    transcript: Transcript,
+    kdf: Box<dyn Kdf + Send + Sync>,
+    p: BigInt,
+    g: BigInt
+}
+
+impl Guesser {
+    fn check(&self, password: &str) -> bool {
+        let t = &self.transcript;
+        let x = password_to_x(self.kdf.as_ref(), &t.salt, password.as_bytes());
+
+        let base = &t.a_pub * self.g.modpow(&(&t.u * x), &self.p) % &self.p;
+        let s = if t.b.is_one() { base } else { base.modpow(&t.b, &self.p) };
+
+        let key = sha256(&s.to_bytes_be().1);
+        hmac::<Sha256>(&key, &t.salt.to_bytes_be().1) == t.hmac
+    }
+}
+
+// let password = DictionaryAttack::new(transcript)
+//     .with_mangling(Mangling::none().with_case().with_digits(2))
+//     .with_progress(|p| println!("{}/{}", p.words_tried, p.words_total))
+//     .crack_file("words.txt")?;
+pub struct DictionaryAttack<'a> {
+    guesser: Guesser,
+    mangling: Mangling,
+    threads: usize,
+    progress: Option<ProgressFn<'a>>
+}
+
+impl<'a> DictionaryAttack<'a> {
+    pub fn new(transcript: Transcript) -> DictionaryAttack<'a> {
+        let (p, g) = get_nist();
+
+        DictionaryAttack {
+            guesser: Guesser {
+                transcript,
+                kdf: Box::new(HashKdf::<Sha256>::new()),
+                p,
+                g: BigInt::from(g)
+            },
+            mangling: Mangling::none(),
+            threads: thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            progress: None
+        }
+    }
+
+    pub fn with_mangling(mut self, mangling: Mangling) -> DictionaryAttack<'a> {
+        self.mangling = mangling;
+        self
+    }
+
+    // Defaults to one per core
+    pub fn with_threads(mut self, threads: usize) -> DictionaryAttack<'a> {
+        assert!(threads > 0, "Need at least one thread");
+        self.threads = threads;
+        self
+    }
+
+    // Has to match whatever the client turns passwords into x with
+    pub fn with_kdf<K: Kdf + Send + Sync + 'static>(mut self, kdf: K) -> DictionaryAttack<'a> {
+        self.guesser.kdf = Box::new(kdf);
+        self
+    }
+
+    // Called from the calling thread after every batch of words
+    pub fn with_progress<F: FnMut(&Progress) + 'a>(mut self, progress: F) -> DictionaryAttack<'a> {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    // One word per line, blank lines skipped
+    pub fn crack_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<Option<String>> {
+        let contents = fs::read(path)?;
+        let words: Vec<String> = String::from_utf8_lossy(&contents)
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect();
+
+        Ok(self.crack(&words))
+    }
+
+    pub fn crack<S: AsRef<str> + Sync>(&mut self, words: &[S]) -> Option<String> {
+        let next = AtomicUsize::new(0);
+        let found = AtomicBool::new(false);
+        let (tx, rx) = mpsc::channel();
+
+        let guesser = &self.guesser;
+        let mangling = &self.mangling;
+        let threads = self.threads;
+        let progress = &mut self.progress;
+
+        thread::scope(|scope| {
+            for _ in 0..threads {
+                let tx = tx.clone();
+                let (next, found) = (&next, &found);
+
+                scope.spawn(move || {
+                    while !found.load(Ordering::Relaxed) {
+                        let start = next.fetch_add(BATCH_SIZE, Ordering::Relaxed);
+                        if start >= words.len() {
+                            return;
+                        }
+
+                        let batch = &words[start..words.len().min(start + BATCH_SIZE)];
+                        let mut guesses = 0;
+                        for word in batch {
+                            for candidate in mangling.candidates(word.as_ref()) {
+                                guesses += 1;
+                                if guesser.check(&candidate) {
+                                    found.store(true, Ordering::Relaxed);
+                                    let _ = tx.send(Message::Found(candidate));
+                                    return;
+                                }
+                            }
+                        }
+
+                        let _ = tx.send(Message::Batch { words: batch.len(), guesses });
+                    }
+                });
+            }
+            // Otherwise rx never sees the channel close
+            drop(tx);
+
+            let mut state = Progress { words_tried: 0, words_total: words.len(), guesses: 0 };
+            let mut res = None;
+
+            for message in rx {
+                match message {
+                    Message::Batch { words, guesses } => {
+                        state.words_tried += words;
+                        state.guesses += guesses;
+                        if let Some(f) = progress {
+                            f(&state);
+                        }
+                    },
+                    Message::Found(password) => res = Some(password)
+                }
+            }
+
+            res
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use crate::asymmetric::srp::SrpClient;
+    use crate::hashing::kdf::Pbkdf2;
+
+    const WORDS: [&str; 9] = ["scout", "pyro", "heavy", "demo", "soldier", "medic", "spy", "sniper", "engie"];
+
+    // What the MITM ends up with after a client logs in with `password`
+    fn capture(mut mitm: MitmServer, client: SrpClient, password: &str) -> Transcript {
+        let mut client = client;
+        let (salt, b_pub, u) = mitm.variant_initial_req(&client.dh.public_key);
+        client.set_salt_and_pkey_variant(&salt, &b_pub, password.as_bytes(), &u).unwrap();
+        mitm.transcript(&client.get_hmac()).unwrap()
+    }
+
+    #[test]
+    fn test_mangling() {
+        assert_eq!(Mangling::none().candidates("Medic"), vec!["Medic"]);
+
+        let cased = Mangling::none().with_case().candidates("Medic");
+        assert_eq!(cased, vec!["Medic", "medic", "MEDIC"]);
+
+        let all = Mangling::none().with_case().with_leetspeak().with_digits(2).candidates("Medic");
+        assert!(all.contains(&"m3d1c".to_string()));
+        assert!(all.contains(&"MEDIC7".to_string()));
+        assert!(all.contains(&"M3D1C07".to_string()));
+        // 6 distinct words, each alone and with 10 + 100 suffixes
+        assert_eq!(all.len(), 6 * 111);
+    }
+
+    #[test]
+    fn test_crack_wordlist_file() {
+        let path = std::env::temp_dir().join(format!(
+            "srp-words-{}.txt",
+            hex::encode(crate::rng::vec::rand_len(8))
+        ));
+        fs::write(&path, WORDS.join("\n") + "\n\n").unwrap();
+
+        let transcript = capture(MitmServer::new(), SrpClient::new(), "sniper");
+        let res = DictionaryAttack::new(transcript).crack_file(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(res.unwrap(), Some("sniper".to_string()));
+    }
+
+    #[test]
+    fn test_crack_mangled() {
+        let transcript = capture(MitmServer::new(), SrpClient::new(), "M3d1c7");
+
+        let res = DictionaryAttack::new(transcript)
+            .with_mangling(Mangling::none().with_case().with_leetspeak().with_digits(1))
+            .crack(&WORDS);
+        assert_eq!(res, Some("M3d1c7".to_string()));
+    }
+
+    #[test]
+    fn test_not_in_wordlist() {
+        let transcript = capture(MitmServer::new(), SrpClient::new(), "hunter2");
+        let reports = RefCell::new(vec![]);
+
+        let res = DictionaryAttack::new(transcript)
+            .with_threads(3)
+            .with_mangling(Mangling::none().with_case())
+            .with_progress(|p| reports.borrow_mut().push(p.clone()))
+            .crack(&WORDS);
+        assert_eq!(res, None);
+
+        let reports = reports.into_inner();
+        let last = reports.iter().max_by_key(|p| p.words_tried).unwrap();
+        assert_eq!(last.words_tried, WORDS.len());
+        assert_eq!(last.words_total, WORDS.len());
+        assert!(last.guesses > WORDS.len());
+    }
+
+    #[test]
+    fn test_random_server_parameters() {
+        let mitm = MitmServer::with_parameters(
+            BigInt::from(0x5a17u32),
+            BigInt::from(0xdeadbeefu32),
+            BigInt::from(0x1234_5678_9abcu64)
+        );
+        let transcript = capture(mitm, SrpClient::new(), "demo");
+
+        assert_eq!(DictionaryAttack::new(transcript).crack(&WORDS), Some("demo".to_string()));
+    }
+
+    #[test]
+    fn test_crack_with_kdf() {
+        let client = SrpClient::new().with_kdf(Pbkdf2::<Sha256>::new(10));
+        let transcript = capture(MitmServer::new(), client, "heavy");
+
+        let res = DictionaryAttack::new(transcript)
+            .with_kdf(Pbkdf2::<Sha256>::new(10))
+            .crack(&WORDS);
+        assert_eq!(res, Some("heavy".to_string()));
+    }
+}
@@ -3,3 +3,4 @@ mod c35;
 mod c37;
 mod c38;
 mod peer;
+pub mod dictionary_attack;
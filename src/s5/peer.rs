@@ -2,13 +2,15 @@ use crate::asymmetric::diffie_hellman::DiffieHellmanContext;
 use num_bigint::BigInt;
 use num_bigint::ToBigInt;
 use num_bigint::Sign::Minus;
-use crate::hashing::sha1::sha1;
+use crate::hashing::kdf::{HashKdf, Kdf};
+use crate::hashing::sha1::Sha1;
 use crate::symmetric::aes::*;
 use std::convert::TryInto;
 
 pub struct Peer {
     pub dh: DiffieHellmanContext,
-    s_key: Vec<u8>
+    s_key: Vec<u8>,
+    kdf: Box<dyn Kdf>
 }
 
 impl Peer {
@@ -16,7 +18,8 @@ impl Peer {
         let dh = DiffieHellmanContext::new(p, g);
         Peer {
             dh: dh,
-            s_key: vec![]
+            s_key: vec![],
+            kdf: Box::new(HashKdf::<Sha1>::new())
         }
     }
 
@@ -24,17 +27,24 @@ impl Peer {
         let dh = DiffieHellmanContext::nist();
         Peer {
             dh: dh,
-            s_key: vec![]
+            s_key: vec![],
+            kdf: Box::new(HashKdf::<Sha1>::new())
         }
     }
 
+    // Defaults to a truncated SHA-1 of the shared secret, which the attacks
+    // in set 5 rely on. Both peers have to agree on this
+    pub fn with_kdf<K: Kdf + 'static>(mut self, kdf: K) -> Peer {
+        self.kdf = Box::new(kdf);
+        self
+    }
+
     pub fn make_session_key(&mut self, pub_key: &BigInt) {
         let s = self.dh.make_session_key(pub_key);
         let (sign, b) = s.to_bytes_be();
         assert_ne!(sign, Minus);
 
-        self.s_key = sha1(&b);
-        self.s_key.truncate(AES_BLOCK_SIZE);
+        self.s_key = self.kdf.derive(&b, &[], AES_BLOCK_SIZE);
     }
 
     pub fn aes_encrypt(&self, pt: &[u8]) -> Vec<u8> {
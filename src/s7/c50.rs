@@ -0,0 +1,26 @@
+#[cfg(test)]
+mod tests {
+    use crate::s7::cbc_mac_forgery::{forge_javascript, printable_ascii};
+    use crate::symmetric::cbc_mac::cbc_mac;
+
+    const KEY: &[u8] = b"YELLOW SUBMARINE";
+    const IV: [u8; 16] = [0u8; 16];
+
+    #[test]
+    fn test_forge_javascript() {
+        let original = b"alert('MZA who was that?');\n";
+        let target = cbc_mac(KEY, &IV, original);
+
+        let code = b"alert('Ayo, the Wu is back!');";
+        let forged = forge_javascript(KEY, &IV, &target, code).unwrap();
+
+        assert_eq!(cbc_mac(KEY, &IV, &forged.message), target);
+        assert!(forged.message.starts_with(code));
+
+        // One line of code, one comment line
+        let (last, body) = forged.message.split_last().unwrap();
+        assert_eq!(*last, b'\n');
+        assert!(body.iter().all(|b| printable_ascii().contains(b)));
+        assert_eq!(&body[code.len()..code.len() + 2], b"//");
+    }
+}
@@ -0,0 +1,28 @@
+#[cfg(test)]
+mod tests {
+    use crate::s7::compression_oracle::{CipherMode, CompressionTarget, CrimeAttack};
+
+    const SESSION_ID: &[u8] = b"TmV2ZXIgcmV2ZWFsIHRoZSBXdS1UYW5nIFNlY3JldCE=";
+
+    #[test]
+    fn test_crime_ctr() {
+        let target = CompressionTarget::new(SESSION_ID, CipherMode::Ctr);
+        let res = CrimeAttack::new(b"sessionid=").recover(&target).unwrap();
+        assert_eq!(res.secret, SESSION_ID.to_vec());
+    }
+
+    #[test]
+    fn test_crime_cbc() {
+        let target = CompressionTarget::new(SESSION_ID, CipherMode::Cbc);
+        let res = CrimeAttack::new(b"sessionid=").recover(&target).unwrap();
+        assert_eq!(res.secret, SESSION_ID.to_vec());
+    }
+
+    #[test]
+    fn test_crime_random_secret() {
+        let session_id = base64::encode(crate::rng::vec::rand_len(30));
+        let target = CompressionTarget::new(session_id.as_bytes(), CipherMode::Cbc);
+        let res = CrimeAttack::new(b"sessionid=").recover(&target).unwrap();
+        assert_eq!(res.secret, session_id.into_bytes());
+    }
+}
@@ -0,0 +1,21 @@
+#[cfg(test)]
+mod tests {
+    use crate::hashing::cheap_hash::CheapHash;
+    use crate::hashing::compression::Compression;
+    use crate::s7::expandable_message::second_preimage;
+
+    #[test]
+    fn test_second_preimage() {
+        let hash = CheapHash::new(24);
+        // 2^10 blocks and a bit
+        let target = crate::rng::vec::rand_len((1 << 10) * 16 + 5);
+
+        let forged = second_preimage(&hash, &target).unwrap();
+
+        assert_ne!(forged.message, target);
+        assert_eq!(forged.message.len(), target.len());
+        assert_eq!(hash.hash(&forged.message), hash.hash(&target));
+        // Brute force would be around 2^24
+        assert!(forged.calls < 1 << 20);
+    }
+}
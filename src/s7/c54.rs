@@ -0,0 +1,22 @@
+#[cfg(test)]
+mod tests {
+    use crate::hashing::cheap_hash::CheapHash;
+    use crate::hashing::compression::Compression;
+    use crate::s7::herding::Diamond;
+
+    #[test]
+    fn test_nostradamus() {
+        let hash = CheapHash::new(20);
+        let diamond = Diamond::build(&hash, 6);
+
+        // Published before the season starts, with room for the scores
+        // however they turn out
+        let prediction = diamond.predict(&hash, 48);
+
+        let results = b"Final scores: 8 - 3, 2 - 1, 0 - 0";
+        let herded = diamond.herd(&hash, &prediction, results).unwrap();
+
+        assert!(herded.message.starts_with(results));
+        assert_eq!(hash.hash(&herded.message), prediction.hash);
+    }
+}
@@ -0,0 +1,13 @@
+#[cfg(test)]
+mod tests {
+    use crate::hashing::md4::md4;
+    use crate::s7::md4_collision::find_collision;
+
+    #[test]
+    fn test_md4_collision() {
+        let collision = find_collision();
+
+        assert_ne!(collision.block_1, collision.block_2);
+        assert_eq!(md4(&collision.block_1), md4(&collision.block_2));
+    }
+}
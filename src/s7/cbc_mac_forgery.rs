@@ -0,0 +1,172 @@
+// CBC-MAC second preimages under a known key (challenge 50).
+//
+// With the key public, CBC-MAC runs backwards as easily as forwards: from
+// the target MAC we decrypt our way back through whatever fixed suffix we
+// want, and get the chaining value some block G has to produce. For any
+// state s in front of G that pins G down to s ^ D(required). G is only
+// useful if it looks like the rest of the message though (printable, no
+// line breaks in the middle of a comment), so we put a free block F in
+// front of it, walk F through blocks made of allowed bytes, and keep going
+// until G comes out allowed too. For printable ASCII that's about
+// (256 / 95)^16 ~ 2^23 tries, each one a single AES call.
+use openssl::symm::Cipher;
+use rand::Rng;
+
+use crate::symmetric::aes::{aes_decrypt_nopad, aes_encrypt_nopad, AES_BLOCK_SIZE};
+use crate::symmetric::cbc_mac::{cbc_mac_pad, cbc_mac_state, cbc_mac_state_before};
+
+// Free blocks we encrypt per call into OpenSSL
+const BATCH_BLOCKS: usize = 4096;
+
+pub struct CbcMacForgery {
+    pub message: Vec<u8>,
+    pub attempts: u64
+}
+
+pub fn printable_ascii() -> Vec<u8> {
+    (0x20..=0x7e).collect()
+}
+
+pub struct ConstrainedForger<'a> {
+    key: &'a [u8],
+    iv: [u8; AES_BLOCK_SIZE],
+    // Bytes the filler, F and G are made of
+    allowed: Vec<u8>,
+    max_attempts: u64
+}
+
+impl<'a> ConstrainedForger<'a> {
+    // Printable ASCII unless told otherwise
+    pub fn new(key: &'a [u8], iv: &[u8; AES_BLOCK_SIZE]) -> ConstrainedForger<'a> {
+        ConstrainedForger {
+            key,
+            iv: *iv,
+            allowed: printable_ascii(),
+            max_attempts: 1 << 30
+        }
+    }
+
+    pub fn with_allowed(mut self, allowed: &[u8]) -> ConstrainedForger<'a> {
+        let mut allowed = allowed.to_vec();
+        allowed.sort_unstable();
+        allowed.dedup();
+        self.allowed = allowed;
+        self
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: u64) -> ConstrainedForger<'a> {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    // Finds prefix || filler || F || G || suffix with the target MAC. The
+    // filler (made of allowed bytes) takes the prefix up to a block boundary
+    pub fn forge(&self, target_mac: &[u8; AES_BLOCK_SIZE], prefix: &[u8], suffix: &[u8]) -> Result<CbcMacForgery, &'static str> {
+        if self.allowed.is_empty() {
+            return Err("No allowed bytes");
+        }
+        let mut is_allowed = [false; 256];
+        for &b in &self.allowed {
+            is_allowed[b as usize] = true;
+        }
+
+        let mut message = prefix.to_vec();
+        let filler = if is_allowed[b' ' as usize] { b' ' } else { self.allowed[0] };
+        let filler_len = (AES_BLOCK_SIZE - prefix.len() % AES_BLOCK_SIZE) % AES_BLOCK_SIZE;
+        message.extend(vec![filler; filler_len]);
+        let state = cbc_mac_state(self.key, &self.iv, &message);
+
+        // G has to encrypt (after the xor with F's output) to this
+        let required = cbc_mac_state_before(self.key, &cbc_mac_pad(suffix), target_mac);
+        let decrypted = aes_decrypt_nopad(self.key, &required, &[], Cipher::aes_128_ecb());
+
+        // F counts through blocks of allowed bytes like an odometer,
+        // starting somewhere random
+        let mut rng = rand::thread_rng();
+        let mut digits: Vec<usize> = (0..AES_BLOCK_SIZE).map(|_| rng.gen_range(0, self.allowed.len())).collect();
+
+        let mut attempts = 0u64;
+        let mut batch = vec![0u8; BATCH_BLOCKS * AES_BLOCK_SIZE];
+        while attempts < self.max_attempts {
+            for block in batch.chunks_mut(AES_BLOCK_SIZE) {
+                for j in 0..AES_BLOCK_SIZE {
+                    block[j] = self.allowed[digits[j]] ^ state[j];
+                }
+                for digit in digits.iter_mut() {
+                    *digit += 1;
+                    if *digit < self.allowed.len() {
+                        break;
+                    }
+                    *digit = 0;
+                }
+            }
+            let outputs = aes_encrypt_nopad(self.key, &batch, &[], Cipher::aes_128_ecb());
+
+            for (i, output) in outputs.chunks(AES_BLOCK_SIZE).enumerate() {
+                let glue_ok = (0..AES_BLOCK_SIZE).all(|j| is_allowed[(output[j] ^ decrypted[j]) as usize]);
+                if glue_ok {
+                    let free_block: Vec<u8> = (0..AES_BLOCK_SIZE).map(|j| batch[i * AES_BLOCK_SIZE + j] ^ state[j]).collect();
+                    let glue_block: Vec<u8> = (0..AES_BLOCK_SIZE).map(|j| output[j] ^ decrypted[j]).collect();
+                    message.extend(free_block);
+                    message.extend(glue_block);
+                    message.extend_from_slice(suffix);
+                    return Ok(CbcMacForgery { message, attempts: attempts + i as u64 + 1 });
+                }
+            }
+            attempts += BATCH_BLOCKS as u64;
+        }
+        Err("Ran out of attempts")
+    }
+}
+
+// `code`, then a line comment hiding the junk blocks, closed by the final
+// newline. Everything but that newline is printable
+pub fn forge_javascript(
+    key: &[u8],
+    iv: &[u8; AES_BLOCK_SIZE],
+    target_mac: &[u8; AES_BLOCK_SIZE],
+    code: &[u8]
+) -> Result<CbcMacForgery, &'static str> {
+    let mut prefix = code.to_vec();
+    prefix.extend_from_slice(b"//");
+    ConstrainedForger::new(key, iv).forge(target_mac, &prefix, b"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::s7::cbc_mac_forgery::ConstrainedForger;
+    use crate::symmetric::aes::gen_random_16_bytes;
+    use crate::symmetric::cbc_mac::cbc_mac;
+
+    #[test]
+    fn test_custom_alphabet() {
+        let key = gen_random_16_bytes();
+        let iv = gen_random_16_bytes();
+        let target = cbc_mac(&key, &iv, b"some message nobody should be able to match");
+
+        // Anything but the bytes that would end a comment
+        let allowed: Vec<u8> = (0..=255u8).filter(|&b| b != b'*' && b != b'/').collect();
+        let suffix = b"*/ and some more text after the comment";
+        let forged = ConstrainedForger::new(&key, &iv)
+            .with_allowed(&allowed)
+            .forge(&target, b"/* prefix", suffix)
+            .unwrap();
+
+        assert_eq!(cbc_mac(&key, &iv, &forged.message), target);
+        assert!(forged.message.starts_with(b"/* prefix"));
+        assert!(forged.message.ends_with(suffix));
+        let middle = &forged.message[9..forged.message.len() - suffix.len()];
+        assert!(middle.iter().all(|b| allowed.contains(b)));
+    }
+
+    #[test]
+    fn test_out_of_attempts() {
+        let key = gen_random_16_bytes();
+        let target = cbc_mac(&key, &[0u8; 16], b"abc");
+        let res = ConstrainedForger::new(&key, &[0u8; 16])
+            .with_allowed(b"01")
+            .with_max_attempts(1 << 14)
+            .forge(&target, b"", b"");
+        assert!(res.is_err());
+    }
+}
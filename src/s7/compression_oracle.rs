@@ -0,0 +1,169 @@
+// Compression ratio side channel, CRIME style (challenge 51).
+//
+// The target compresses a request holding both a secret cookie and data we
+// control, then encrypts it. Encryption hides the content but not the
+// length, and DEFLATE makes the request shorter when our data repeats part
+// of the cookie. So we send `sessionid=` + what we know + a guess, and the
+// right guess extends the LZ77 match back into the cookie by one byte.
+//
+// That saves a literal, i.e. a handful of bits, which rounding to whole
+// bytes (or whole blocks for CBC) can easily hide. We get around it by
+// putting incompressible filler in front of the guess: every filler byte
+// is a 9 bit literal, so stepping through filler lengths moves the end of
+// the output across every possible bit offset. Whenever a guess lands on
+// the far side of a byte or block boundary while another one doesn't, it
+// compressed worse, and it's out.
+use rand::Rng;
+
+use crate::oracles::CompressionOracle;
+use crate::symmetric::aes::{aes_cbc_encrypt, aes_ctr_crypt, gen_random_16_bytes, AES_BLOCK_SIZE};
+use crate::utils::deflate::deflate;
+
+const BASE64_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/=";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CipherMode {
+    Ctr,
+    Cbc
+}
+
+pub fn format_request(session_id: &[u8], body: &[u8]) -> Vec<u8> {
+    let mut res = b"POST / HTTP/1.1\r\nHost: hapless.com\r\nCookie: sessionid=".to_vec();
+    res.extend_from_slice(session_id);
+    res.extend_from_slice(format!("\r\nContent-Length: {}\r\n\r\n", body.len()).as_bytes());
+    res.extend_from_slice(body);
+    res
+}
+
+pub struct CompressionTarget {
+    session_id: Vec<u8>,
+    mode: CipherMode
+}
+
+impl CompressionTarget {
+    pub fn new(session_id: &[u8], mode: CipherMode) -> CompressionTarget {
+        CompressionTarget {
+            session_id: session_id.to_vec(),
+            mode
+        }
+    }
+}
+
+impl CompressionOracle for CompressionTarget {
+    fn encrypted_len(&self, data: &[u8]) -> usize {
+        let compressed = deflate(&format_request(&self.session_id, data));
+        // Fresh key and nonce/IV every time, so only the length carries over
+        let key = gen_random_16_bytes();
+        let ciphertext = match self.mode {
+            CipherMode::Ctr => aes_ctr_crypt(&key, &compressed, rand::thread_rng().gen()),
+            CipherMode::Cbc => aes_cbc_encrypt(&key, &compressed, Some(gen_random_16_bytes()))
+        };
+        ciphertext.len()
+    }
+}
+
+// 9 bit literals that never show up in the request and never repeat a 3
+// byte run, so LZ77 can't do anything with them
+fn incompressible_filler(len: usize) -> Vec<u8> {
+    let mut rng = rand::thread_rng();
+    loop {
+        let filler: Vec<u8> = (0..len).map(|_| rng.gen_range(0x90, 0x100) as u8).collect();
+        let repeats = (0..len.saturating_sub(2))
+            .any(|i| (i + 1..len - 2).any(|j| filler[i..i + 3] == filler[j..j + 3]));
+        if !repeats {
+            return filler;
+        }
+    }
+}
+
+pub struct CrimeResult {
+    pub secret: Vec<u8>,
+    pub queries: usize
+}
+
+pub struct CrimeAttack {
+    // What comes right before the secret in the request
+    prefix: Vec<u8>,
+    alphabet: Vec<u8>,
+    // First byte after the secret
+    terminator: u8,
+    max_len: usize,
+    filler: Vec<u8>
+}
+
+impl CrimeAttack {
+    // Defaults to a base64 secret ending at the header's \r\n
+    pub fn new(prefix: &[u8]) -> CrimeAttack {
+        CrimeAttack {
+            prefix: prefix.to_vec(),
+            alphabet: BASE64_ALPHABET.to_vec(),
+            terminator: b'\r',
+            max_len: 256,
+            // 9 * n mod 128 hits every bit offset within a block for n < 128
+            filler: incompressible_filler(8 * AES_BLOCK_SIZE)
+        }
+    }
+
+    pub fn with_alphabet(mut self, alphabet: &[u8], terminator: u8) -> CrimeAttack {
+        self.alphabet = alphabet.to_vec();
+        self.terminator = terminator;
+        self
+    }
+
+    pub fn with_max_len(mut self, max_len: usize) -> CrimeAttack {
+        self.max_len = max_len;
+        self
+    }
+
+    fn body(&self, filler_len: usize, known: &[u8], guess: u8) -> Vec<u8> {
+        let mut res = self.filler[..filler_len].to_vec();
+        res.extend_from_slice(&self.prefix);
+        res.extend_from_slice(known);
+        res.push(guess);
+        res
+    }
+
+    // Narrows the candidates down to the one that compresses best
+    fn next_byte<O: CompressionOracle>(&self, oracle: &O, known: &[u8], queries: &mut usize) -> Result<u8, &'static str> {
+        let mut survivors = self.alphabet.clone();
+        survivors.push(self.terminator);
+
+        for filler_len in 0..=self.filler.len() {
+            if survivors.len() == 1 {
+                break;
+            }
+            let lens: Vec<usize> = survivors
+                .iter()
+                .map(|&guess| oracle.encrypted_len(&self.body(filler_len, known, guess)))
+                .collect();
+            *queries += lens.len();
+
+            let shortest = *lens.iter().min().unwrap();
+            survivors = survivors
+                .iter()
+                .zip(&lens)
+                .filter(|&(_, &len)| len == shortest)
+                .map(|(&guess, _)| guess)
+                .collect();
+        }
+
+        match survivors.as_slice() {
+            [byte] => Ok(*byte),
+            _ => Err("Couldn't tell the remaining candidates apart")
+        }
+    }
+
+    pub fn recover<O: CompressionOracle>(&self, oracle: &O) -> Result<CrimeResult, &'static str> {
+        let mut secret = Vec::new();
+        let mut queries = 0;
+
+        while secret.len() < self.max_len {
+            let byte = self.next_byte(oracle, &secret, &mut queries)?;
+            if byte == self.terminator {
+                return Ok(CrimeResult { secret, queries });
+            }
+            secret.push(byte);
+        }
+        Err("Secret is longer than max_len")
+    }
+}
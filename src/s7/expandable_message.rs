@@ -0,0 +1,175 @@
+// Kelsey and Schneier's second preimages for long messages (challenge 53).
+//
+// A long target message passes through lots of intermediate chaining
+// values, and hitting any one of them with a single block is 2^b / blocks
+// work instead of 2^b. The catch is the length in the padding: whatever we
+// splice in front of the rest of the target has to be exactly as long as
+// the part it replaces.
+//
+// An expandable message fixes that. Pair i is a single block and a
+// 2^(k - 1 - i) + 1 block message that collide, so picking one of each
+// pair gives every length from k to k + 2^k - 1 blocks, all ending in the
+// same state. We bridge from that state into the target and pad the prefix
+// to whatever length the target needs there.
+use std::collections::HashMap;
+
+use crate::hashing::compression::Compression;
+use crate::s7::multicollision::{find_collision_between, random_block};
+
+// A single block and `dummy_blocks` + 1 blocks that collide from `state`.
+// Returns them, the new state and how many compression calls it took
+fn find_uneven_collision<C: Compression>(
+    hash: &C,
+    state: C::State,
+    dummy_blocks: usize
+) -> (Vec<u8>, Vec<u8>, C::State, usize) {
+    let prefix = vec![0u8; dummy_blocks * hash.block_len()];
+    let long_start = hash.hash_blocks(state, &prefix);
+    let (short_block, long_block, end, calls) = find_collision_between(hash, state, long_start);
+
+    let mut long_message = prefix;
+    long_message.extend(long_block);
+    (short_block, long_message, end, dummy_blocks + calls)
+}
+
+#[derive(Clone, Debug)]
+pub struct ExpandableMessage<S> {
+    // (1 block, 2^(k - 1 - i) + 1 blocks) for pair i
+    pub pairs: Vec<(Vec<u8>, Vec<u8>)>,
+    pub start: S,
+    // Where every length ends up
+    pub state: S,
+    pub calls: usize
+}
+
+impl<S: Copy> ExpandableMessage<S> {
+    pub fn build<C: Compression<State = S>>(hash: &C, start: S, k: usize) -> ExpandableMessage<S> {
+        let mut res = ExpandableMessage { pairs: vec![], start, state: start, calls: 0 };
+
+        for i in 0..k {
+            let (short, long, state, calls) = find_uneven_collision(hash, res.state, 1 << (k - 1 - i));
+            res.pairs.push((short, long));
+            res.state = state;
+            res.calls += calls;
+        }
+
+        res
+    }
+
+    pub fn k(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn min_blocks(&self) -> usize {
+        self.k()
+    }
+
+    pub fn max_blocks(&self) -> usize {
+        self.k() + (1 << self.k()) - 1
+    }
+
+    // Exactly `blocks` blocks long and hashing from `start` to `state`
+    pub fn message(&self, blocks: usize) -> Vec<u8> {
+        assert!(
+            (self.min_blocks()..=self.max_blocks()).contains(&blocks),
+            "Can only make {} to {} blocks", self.min_blocks(), self.max_blocks()
+        );
+
+        // Taking the long side of pair i adds 2^(k - 1 - i) blocks
+        let extra = blocks - self.k();
+        let k = self.k();
+
+        self.pairs.iter()
+            .enumerate()
+            .flat_map(|(i, (short, long))| {
+                if extra >> (k - 1 - i) & 1 == 1 { long.clone() } else { short.clone() }
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SecondPreimage {
+    pub message: Vec<u8>,
+    // Compression calls, expandable message and bridge together
+    pub calls: usize
+}
+
+// A different message with the same hash as `target`. The longer the
+// target, the quicker this is
+pub fn second_preimage<C: Compression>(hash: &C, target: &[u8]) -> Result<SecondPreimage, &'static str> {
+    let block_len = hash.block_len();
+    let blocks = target.len() / block_len;
+    if blocks < 3 {
+        return Err("Target needs at least 3 whole blocks");
+    }
+
+    // The largest k with 2^k <= blocks, so an expandable message can reach
+    // most of the target
+    let k = (usize::BITS - 1 - blocks.leading_zeros()) as usize;
+    let reachable = (k + 1)..=(blocks.min(k + (1 << k)));
+
+    // State after i blocks -> i, only where i - 1 is a length we can make
+    let mut intermediate: HashMap<C::State, usize> = HashMap::new();
+    let mut state = hash.iv();
+    for (i, block) in target.chunks_exact(block_len).enumerate() {
+        state = hash.compress(state, block);
+        if reachable.contains(&(i + 1)) {
+            intermediate.insert(state, i + 1);
+        }
+    }
+
+    let expandable = ExpandableMessage::build(hash, hash.iv(), k);
+    let mut calls = blocks + expandable.calls;
+
+    loop {
+        let bridge = random_block(hash);
+        calls += 1;
+
+        if let Some(&i) = intermediate.get(&hash.compress(expandable.state, &bridge)) {
+            let mut message = expandable.message(i - 1);
+            message.extend(bridge);
+            message.extend_from_slice(&target[i * block_len..]);
+
+            if message != target {
+                return Ok(SecondPreimage { message, calls });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashing::cheap_hash::CheapHash;
+
+    #[test]
+    fn test_uneven_collision() {
+        let hash = CheapHash::new(16);
+        let (short, long, state, _) = find_uneven_collision(&hash, hash.iv(), 5);
+
+        assert_eq!(short.len(), 16);
+        assert_eq!(long.len(), 6 * 16);
+        assert_eq!(hash.hash_blocks(hash.iv(), &short), state);
+        assert_eq!(hash.hash_blocks(hash.iv(), &long), state);
+    }
+
+    #[test]
+    fn test_every_length() {
+        let hash = CheapHash::new(16);
+        let expandable = ExpandableMessage::build(&hash, hash.iv(), 4);
+
+        assert_eq!((expandable.min_blocks(), expandable.max_blocks()), (4, 19));
+        for blocks in 4..=19 {
+            let message = expandable.message(blocks);
+            assert_eq!(message.len(), blocks * 16);
+            assert_eq!(hash.hash_blocks(hash.iv(), &message), expandable.state);
+        }
+    }
+
+    #[test]
+    fn test_too_short() {
+        let hash = CheapHash::new(16);
+        assert!(second_preimage(&hash, &[0u8; 40]).is_err());
+    }
+}
@@ -0,0 +1,307 @@
+// Kelsey and Kohno's herding attack, "Nostradamus" (challenge 54).
+//
+// Build a diamond: start from 2^k arbitrary chaining values, pair them up
+// and find a block for each so both land in the same state, and repeat on
+// the 2^(k - 1) results until there's one root left. Publish the hash of
+// the root plus padding as a prediction. Once you know what you want to
+// have predicted, hash it, find one linking block into any of the 2^k
+// leaves (2^(b - k) work) and follow the tree up to the root.
+//
+// The prediction fixes the message length (it's in the padding), so it's
+// made for prefixes up to some length. Shorter ones get filled up with
+// spaces to the block boundary before the linking block.
+//
+// Only the leaves and one block per node are kept: node i at a level goes
+// to node i / 2 at the next, so the tree needs no pointers. Building it is
+// the expensive part, so it can be saved to disk and loaded again.
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::hashing::compression::Compression;
+use crate::s7::multicollision::{find_collision_between, random_block};
+
+const MAGIC: &[u8; 8] = b"DIAMOND1";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diamond<S> {
+    pub leaves: Vec<S>,
+    // levels[0] has one block per leaf, each level after that half as many.
+    // A level's blocks are stored back to back
+    levels: Vec<Vec<u8>>,
+    block_len: usize,
+    pub root: S,
+    // Compression calls spent building it
+    pub calls: usize
+}
+
+// What gets published ahead of time
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Prediction<S> {
+    pub hash: S,
+    // Longest prefix that can be herded into it, in bytes
+    pub prefix_len: usize
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Herded {
+    // prefix || filler || link || path to the root
+    pub message: Vec<u8>,
+    pub calls: usize
+}
+
+fn random_state<C: Compression>(hash: &C) -> C::State {
+    let len = hash.state_to_bytes(hash.iv()).len();
+    hash.state_from_bytes(&crate::rng::vec::rand_len(len))
+}
+
+impl<S: Copy + Eq + std::hash::Hash> Diamond<S> {
+    // 2^k leaves, about 2^k birthday searches
+    pub fn build<C: Compression<State = S>>(hash: &C, k: usize) -> Diamond<S> {
+        // Random leaves can repeat with a small state, and a repeated pair
+        // can't be collided
+        let mut leaves = vec![];
+        while leaves.len() < 1 << k {
+            let leaf = random_state(hash);
+            if !leaves.contains(&leaf) {
+                leaves.push(leaf);
+            }
+        }
+
+        let mut states = leaves.clone();
+        let mut levels = vec![];
+        let mut calls = 0;
+
+        while states.len() > 1 {
+            let mut blocks = Vec::with_capacity(states.len() * hash.block_len());
+            let mut next = Vec::with_capacity(states.len() / 2);
+
+            for pair in states.chunks(2) {
+                let (block_1, block_2, state, n) = find_collision_between(hash, pair[0], pair[1]);
+                blocks.extend(block_1);
+                blocks.extend(block_2);
+                next.push(state);
+                calls += n;
+            }
+
+            levels.push(blocks);
+            states = next;
+        }
+
+        Diamond {
+            leaves,
+            levels,
+            block_len: hash.block_len(),
+            root: states[0],
+            calls
+        }
+    }
+
+    pub fn k(&self) -> usize {
+        self.levels.len()
+    }
+
+    fn block(&self, level: usize, index: usize) -> &[u8] {
+        &self.levels[level][index * self.block_len..(index + 1) * self.block_len]
+    }
+
+    // The blocks from leaf `index` to the root
+    pub fn path(&self, mut index: usize) -> Vec<u8> {
+        let mut res = Vec::with_capacity(self.k() * self.block_len);
+        for level in 0..self.k() {
+            res.extend_from_slice(self.block(level, index));
+            index /= 2;
+        }
+        res
+    }
+
+    fn prefix_blocks(&self, prefix_len: usize) -> usize {
+        prefix_len.div_ceil(self.block_len)
+    }
+
+    // The final hash of any prefix of up to `prefix_len` bytes once it's
+    // been herded
+    pub fn predict<C: Compression<State = S>>(&self, hash: &C, prefix_len: usize) -> Prediction<S> {
+        let total_len = (self.prefix_blocks(prefix_len) + 1 + self.k()) * self.block_len;
+        Prediction {
+            hash: hash.hash_blocks(self.root, &hash.padding(total_len)),
+            prefix_len
+        }
+    }
+
+    pub fn herd<C: Compression<State = S>>(
+        &self,
+        hash: &C,
+        prediction: &Prediction<S>,
+        prefix: &[u8]
+    ) -> Result<Herded, &'static str> {
+        if prefix.len() > prediction.prefix_len {
+            return Err("Prefix is longer than predicted");
+        }
+
+        let prefix_blocks = self.prefix_blocks(prediction.prefix_len);
+        let mut padded = prefix.to_vec();
+        padded.resize(prefix_blocks * self.block_len, b' ');
+
+        let leaves: HashMap<S, usize> = self.leaves.iter().enumerate().map(|(i, &s)| (s, i)).collect();
+        let state = hash.hash_blocks(hash.iv(), &padded);
+        let mut calls = prefix_blocks;
+
+        loop {
+            let link = random_block(hash);
+            calls += 1;
+
+            if let Some(&leaf) = leaves.get(&hash.compress(state, &link)) {
+                let mut message = padded;
+                message.extend(link);
+                message.extend(self.path(leaf));
+                return Ok(Herded { message, calls });
+            }
+        }
+    }
+
+    // Recomputes the root from the leaves
+    pub fn verify<C: Compression<State = S>>(&self, hash: &C) -> bool {
+        self.leaves.iter()
+            .enumerate()
+            .all(|(i, &leaf)| hash.hash_blocks(leaf, &self.path(i)) == self.root)
+    }
+
+    // MAGIC, then k, block length and state length as u32 LE, the leaves,
+    // every level's blocks and the root
+    pub fn save<C: Compression<State = S>, P: AsRef<Path>>(&self, hash: &C, path: P) -> io::Result<()> {
+        let state_len = hash.state_to_bytes(self.root).len();
+
+        let mut out = MAGIC.to_vec();
+        for &n in [self.k(), self.block_len, state_len].iter() {
+            out.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+        for &leaf in self.leaves.iter() {
+            out.extend(hash.state_to_bytes(leaf));
+        }
+        for level in self.levels.iter() {
+            out.extend_from_slice(level);
+        }
+        out.extend(hash.state_to_bytes(self.root));
+
+        fs::write(path, out)
+    }
+
+    // Fails on anything that isn't a diamond for `hash`, including one
+    // whose blocks don't lead to its root
+    pub fn load<C: Compression<State = S>, P: AsRef<Path>>(hash: &C, path: P) -> io::Result<Diamond<S>> {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let data = fs::read(path)?;
+
+        if data.len() < 20 || &data[..8] != MAGIC {
+            return Err(invalid("Not a diamond file"));
+        }
+        let field = |i: usize| u32::from_le_bytes(data[8 + 4 * i..12 + 4 * i].try_into().unwrap()) as usize;
+        let (k, block_len, state_len) = (field(0), field(1), field(2));
+
+        if block_len != hash.block_len() || state_len != hash.state_to_bytes(hash.iv()).len() || k >= 32 {
+            return Err(invalid("Diamond was built for a different hash"));
+        }
+        // Leaves, then 2^k + 2^(k - 1) + ... + 2 blocks, then the root
+        let expected = 20 + (1 << k) * state_len + ((1 << (k + 1)) - 2) * block_len + state_len;
+        if data.len() != expected {
+            return Err(invalid("Diamond file is the wrong size"));
+        }
+
+        let mut rest = &data[20..];
+        let mut take = |n: usize| {
+            let (head, tail) = rest.split_at(n);
+            rest = tail;
+            head
+        };
+
+        let leaves = (0..1 << k).map(|_| hash.state_from_bytes(take(state_len))).collect();
+        let levels = (0..k).map(|level| take((1 << (k - level)) * block_len).to_vec()).collect();
+        let root = hash.state_from_bytes(take(state_len));
+
+        let diamond = Diamond { leaves, levels, block_len, root, calls: 0 };
+        if !diamond.verify(hash) {
+            return Err(invalid("Diamond doesn't lead to its root"));
+        }
+        Ok(diamond)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashing::cheap_hash::CheapHash;
+    use crate::hashing::compression::{Compression, MdCompression};
+    use crate::hashing::md4::Md4Core;
+
+    fn temp_path() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("diamond-{}.bin", hex::encode(crate::rng::vec::rand_len(8))))
+    }
+
+    #[test]
+    fn test_diamond_paths() {
+        let hash = CheapHash::new(16);
+        let diamond = Diamond::build(&hash, 4);
+
+        assert_eq!(diamond.leaves.len(), 16);
+        assert_eq!(diamond.k(), 4);
+        assert!(diamond.verify(&hash));
+    }
+
+    #[test]
+    fn test_prefix_too_long() {
+        let hash = CheapHash::new(16);
+        let diamond = Diamond::build(&hash, 2);
+        let prediction = diamond.predict(&hash, 20);
+
+        assert!(diamond.herd(&hash, &prediction, &[0u8; 21]).is_err());
+    }
+
+    #[test]
+    fn test_unaligned_prefixes() {
+        let hash = CheapHash::new(16);
+        let diamond = Diamond::build(&hash, 4);
+        let prediction = diamond.predict(&hash, 20);
+
+        // Anything up to the predicted length, aligned or not
+        for &len in [0, 1, 15, 16, 17, 20].iter() {
+            let prefix = vec![b'x'; len];
+            let herded = diamond.herd(&hash, &prediction, &prefix).unwrap();
+
+            assert!(herded.message.starts_with(&prefix));
+            // 20 bytes round up to two blocks
+            assert!(herded.message[len..2 * hash.block_len()].iter().all(|&b| b == b' '));
+            assert_eq!(hash.hash(&herded.message), prediction.hash);
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let hash = CheapHash::new(20);
+        let diamond = Diamond::build(&hash, 5);
+        let path = temp_path();
+
+        diamond.save(&hash, &path).unwrap();
+        let loaded = Diamond::load(&hash, &path);
+
+        // Other hashes can't use it
+        let wrong_size = Diamond::load(&CheapHash::new(24), &path);
+        let wrong_hash = Diamond::load(&MdCompression::<Md4Core>::new(), &path);
+        let mut corrupt = fs::read(&path).unwrap();
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 1;
+        fs::write(&path, corrupt).unwrap();
+        let corrupted = Diamond::load(&hash, &path);
+        fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.leaves, diamond.leaves);
+        assert_eq!(loaded.root, diamond.root);
+        assert_eq!(loaded.path(7), diamond.path(7));
+        assert!(corrupted.is_err());
+        assert!(wrong_size.is_err());
+        assert!(wrong_hash.is_err());
+    }
+}
@@ -0,0 +1,347 @@
+// Wang et al.'s MD4 collision attack ("Cryptanalysis of the Hash Functions
+// MD4 and RIPEMD", EUROCRYPT 2005), challenge 55.
+//
+// Two blocks that differ by
+//
+//   m1 + 2^31, m2 + 2^31 - 2^28, m12 - 2^16
+//
+// collide whenever the intermediate states of the first one meet a long list
+// of bit conditions. We start from a random block and force it to meet them:
+//
+// - Single-step modification: every round 1 step uses a message word of its
+//   own, so we can fix the bits of the state the step produces and solve for
+//   the word that gives it. This gets all of round 1 for free.
+// - Multi-step modification: a round 2 step reuses a word from round 1. We
+//   nudge that word so the round 2 bit flips, recompute the round 1 state it
+//   feeds and re-solve the next four words so nothing after it changes.
+//
+// What's left is a dozen or so conditions we just hope for. That takes a
+// few hundred thousand tries, well under a second in a release build.
+use rand::Rng;
+use std::convert::TryInto;
+
+use crate::hashing::md4::*;
+
+// Condition on one bit of a step's output. Bits are numbered from 1, like in
+// the paper
+#[derive(Clone, Copy)]
+enum Condition {
+    Zero,
+    One,
+    // Same bit as the state `n` steps back
+    Same(usize),
+    Differ(usize)
+}
+
+use Condition::*;
+
+// Table 6 of the paper, by step: a1, d1, c1, b1, a2, ... b9 and a10 are in
+// round 3 and have to be left to chance
+fn conditions(step: usize) -> &'static [(u32, Condition)] {
+    match step {
+        0 => &[(7, Same(1))],
+        1 => &[(7, Zero), (8, Same(1)), (11, Same(1))],
+        2 => &[(7, One), (8, One), (11, Zero), (26, Same(1))],
+        3 => &[(7, One), (8, Zero), (11, Zero), (26, Zero)],
+        4 => &[(8, One), (11, One), (26, Zero), (14, Same(1))],
+        5 => &[(14, Zero), (19, Same(1)), (20, Same(1)), (21, Same(1)), (22, Same(1)), (26, One)],
+        6 => &[(13, Same(1)), (14, Zero), (15, Same(1)), (19, Zero), (20, Zero), (21, One), (22, Zero)],
+        7 => &[(13, One), (14, One), (15, Zero), (17, Same(1)), (19, Zero), (20, Zero), (21, Zero), (22, Zero)],
+        8 => &[
+            (13, One), (14, One), (15, One), (17, Zero), (19, Zero), (20, Zero), (21, Zero), (22, One),
+            (23, Same(1)), (26, Same(1))
+        ],
+        9 => &[
+            (13, One), (14, One), (15, One), (17, Zero), (20, Zero), (21, One), (22, One), (23, Zero),
+            (26, One), (30, Same(1))
+        ],
+        10 => &[(17, One), (20, Zero), (21, Zero), (22, Zero), (23, Zero), (26, Zero), (30, One), (32, Same(1))],
+        11 => &[(20, Zero), (21, One), (22, One), (23, Same(1)), (26, One), (30, Zero), (32, Zero)],
+        12 => &[(23, Zero), (26, Zero), (27, Same(1)), (29, Same(1)), (30, One), (32, Zero)],
+        13 => &[(23, Zero), (26, Zero), (27, One), (29, One), (30, Zero), (32, One)],
+        14 => &[(19, Same(1)), (23, One), (26, One), (27, Zero), (29, Zero), (30, Zero)],
+        15 => &[(19, Zero), (26, Same(1)), (27, One), (29, One), (30, Zero)],
+        16 => &[(19, Same(2)), (26, One), (27, Zero), (29, One), (32, One)],
+        17 => &[(19, Same(1)), (26, Same(2)), (27, Same(2)), (29, Same(2)), (32, Same(2))],
+        18 => &[(26, Same(1)), (27, Same(1)), (29, Same(1)), (30, Same(1)), (32, Same(1))],
+        19 => &[(29, Same(1)), (30, One), (32, Zero)],
+        20 => &[(29, One), (32, One)],
+        21 => &[(29, Same(2))],
+        22 => &[(29, Same(1)), (30, Differ(1)), (32, Differ(1))],
+        35 => &[(32, One)],
+        36 => &[(32, One)],
+        _ => &[]
+    }
+}
+
+// Round 2 steps we can correct with multi-step modification: a5, d5, c5
+const CORRECTED_STEPS: [usize; 3] = [16, 17, 18];
+const LAST_CONDITION_STEP: usize = 36;
+
+// Message word and rotation for each of the 48 steps
+const WORD: [usize; 48] = [
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+    0, 4, 8, 12, 1, 5, 9, 13, 2, 6, 10, 14, 3, 7, 11, 15,
+    0, 8, 4, 12, 2, 10, 6, 14, 1, 9, 5, 13, 3, 11, 7, 15
+];
+const SHIFT: [usize; 48] = [
+    3, 7, 11, 19, 3, 7, 11, 19, 3, 7, 11, 19, 3, 7, 11, 19,
+    3, 5, 9, 13, 3, 5, 9, 13, 3, 5, 9, 13, 3, 5, 9, 13,
+    3, 9, 11, 15, 3, 9, 11, 15, 3, 9, 11, 15, 3, 9, 11, 15
+];
+
+// The state words in the order they get computed: a0, d0, c0, b0 from the
+// chaining value, then one per step. Step t reads q[t..t + 4] and writes
+// q[t + 4]
+type States = [u32; 52];
+
+fn bit(x: u32, i: u32) -> u32 {
+    (x >> (i - 1)) & 1
+}
+
+fn holds(q: &States, index: usize, i: u32, condition: Condition) -> bool {
+    let x = bit(q[index], i);
+    match condition {
+        Zero => x == 0,
+        One => x == 1,
+        Same(n) => x == bit(q[index - n], i),
+        Differ(n) => x != bit(q[index - n], i)
+    }
+}
+
+fn step_holds(q: &States, step: usize) -> bool {
+    conditions(step).iter().all(|&(i, condition)| holds(q, step + 4, i, condition))
+}
+
+fn step(q: &States, m: &[u32; 16], t: usize) -> u32 {
+    let (a, b, c, d) = (q[t], q[t + 3], q[t + 2], q[t + 1]);
+    let k = m[WORD[t]];
+
+    match t / 16 {
+        0 => ff(a, b, c, d, k, SHIFT[t]),
+        1 => gg(a, b, c, d, k, SHIFT[t]),
+        _ => hh(a, b, c, d, k, SHIFT[t])
+    }
+}
+
+// The round 1 message word that makes step t output q[t + 4]
+fn solve_round1_word(q: &States, t: usize) -> u32 {
+    q[t + 4].rotate_right(SHIFT[t] as u32)
+        .wrapping_sub(q[t])
+        .wrapping_sub(f(q[t + 3], q[t + 2], q[t + 1]))
+}
+
+fn initial_states(iv: &[u32; 4]) -> States {
+    let mut q = [0u32; 52];
+    q[..4].copy_from_slice(&[iv[0], iv[3], iv[2], iv[1]]);
+    q
+}
+
+fn words_to_block(m: &[u32; 16]) -> Vec<u8> {
+    m.iter().flat_map(|x| x.to_le_bytes().to_vec()).collect()
+}
+
+fn block_to_words(block: &[u8]) -> [u32; 16] {
+    let mut m = [0u32; 16];
+    for (i, word) in block.chunks(4).enumerate() {
+        m[i] = u32::from_le_bytes(word.try_into().unwrap());
+    }
+    m
+}
+
+// Single-step modification for all of round 1
+fn fix_round1(q: &mut States, m: &mut [u32; 16]) {
+    for t in 0..16 {
+        q[t + 4] = step(q, m, t);
+
+        for &(i, condition) in conditions(t) {
+            let mask = 1 << (i - 1);
+            let target = match condition {
+                Zero => 0,
+                One => mask,
+                Same(n) => q[t + 4 - n] & mask,
+                Differ(n) => !q[t + 4 - n] & mask
+            };
+            q[t + 4] = (q[t + 4] & !mask) | target;
+        }
+
+        m[t] = solve_round1_word(q, t);
+    }
+}
+
+// Multi-step modification of round 2 step t. A bit that can't be fixed
+// without breaking round 1 is left alone
+fn fix_round2_step(q: &mut States, m: &mut [u32; 16], t: usize) {
+    let w = WORD[t];
+
+    for &(i, condition) in conditions(t) {
+        q[t + 4] = step(q, m, t);
+        if holds(q, t + 4, i, condition) {
+            continue;
+        }
+
+        // Adding or subtracting this before the rotation flips bit i of the
+        // output without carrying anywhere
+        let pre_rotation = (i - 1 + 32 - SHIFT[t] as u32) % 32;
+        let delta = 1u32 << pre_rotation;
+        let (saved_q, saved_m) = (*q, *m);
+
+        m[w] = if bit(q[t + 4], i) == 0 { m[w].wrapping_add(delta) } else { m[w].wrapping_sub(delta) };
+
+        // m[w] also made round 1 state q[w + 4]. Let that change and solve
+        // for the next four words so nothing else in round 1 does
+        q[w + 4] = step(q, m, w);
+        let end = (w + 5).min(16);
+        for (j, word) in m.iter_mut().enumerate().take(end).skip(w + 1) {
+            *word = solve_round1_word(q, j);
+        }
+
+        if !(w..end).all(|j| step_holds(q, j)) {
+            *q = saved_q;
+            *m = saved_m;
+        }
+    }
+
+    q[t + 4] = step(q, m, t);
+}
+
+// Runs all 48 steps and checks every condition we know of
+fn all_conditions_hold(q: &mut States, m: &[u32; 16]) -> bool {
+    for t in 0..=LAST_CONDITION_STEP {
+        q[t + 4] = step(q, m, t);
+        if !step_holds(q, t) {
+            return false;
+        }
+    }
+    true
+}
+
+// The other half of the pair
+pub fn apply_differential(block: &[u8]) -> Vec<u8> {
+    let mut m = block_to_words(block);
+    m[1] = m[1].wrapping_add(1 << 31);
+    m[2] = m[2].wrapping_add((1 << 31) - (1 << 28));
+    m[12] = m[12].wrapping_sub(1 << 16);
+    words_to_block(&m)
+}
+
+// Gets a random block to meet every round 1 condition and as many of round 2
+// as we can manage. Returns it along with whether the rest happened to hold
+pub fn modify_block<R: Rng>(iv: &[u32; 4], rng: &mut R) -> (Vec<u8>, bool) {
+    let mut q = initial_states(iv);
+    let mut m: [u32; 16] = rng.gen();
+
+    fix_round1(&mut q, &mut m);
+    for &t in CORRECTED_STEPS.iter() {
+        fix_round2_step(&mut q, &mut m, t);
+    }
+
+    let candidate = all_conditions_hold(&mut q, &m);
+    (words_to_block(&m), candidate)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Md4Collision {
+    pub block_1: Vec<u8>,
+    pub block_2: Vec<u8>,
+    // Blocks tried before one worked
+    pub attempts: usize
+}
+
+fn compress(iv: &[u32; 4], block: &[u8]) -> [u32; 4] {
+    let mut state = *iv;
+    md4_process_block(&mut state, block);
+    state
+}
+
+// Two different blocks that take `iv` to the same chaining value. With
+// MD4_INITIAL_STATE they're 64 byte messages with the same MD4
+pub fn find_collision_from<R: Rng>(iv: &[u32; 4], rng: &mut R) -> Md4Collision {
+    let mut attempts = 0;
+
+    loop {
+        attempts += 1;
+
+        let (block_1, candidate) = modify_block(iv, rng);
+        if !candidate {
+            continue;
+        }
+
+        let block_2 = apply_differential(&block_1);
+        if compress(iv, &block_1) == compress(iv, &block_2) {
+            return Md4Collision { block_1, block_2, attempts };
+        }
+    }
+}
+
+pub fn find_collision() -> Md4Collision {
+    find_collision_from(&MD4_INITIAL_STATE, &mut rand::thread_rng())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    // Wang's first collision from the paper, in little endian words
+    const PAPER_M: [u32; 16] = [
+        0x4d7a9c83, 0x56cb927a, 0xb9d5a578, 0x57a7a5ee, 0xde748a3c, 0xdcc366b3, 0xb683a020, 0x3b2a5d9f,
+        0xc69d71b3, 0xf9e99198, 0xd79f805e, 0xa63bb2e8, 0x45dd8e31, 0x97e31fe5, 0x2794bf08, 0xb9e8c3e9
+    ];
+
+    #[test]
+    fn test_steps_match_md4() {
+        let block = crate::rng::vec::rand_len(64);
+        let m = block_to_words(&block);
+        let mut q = initial_states(&MD4_INITIAL_STATE);
+
+        for t in 0..48 {
+            q[t + 4] = step(&q, &m, t);
+        }
+
+        let iv = MD4_INITIAL_STATE;
+        let by_steps = [
+            iv[0].wrapping_add(q[48]),
+            iv[1].wrapping_add(q[51]),
+            iv[2].wrapping_add(q[50]),
+            iv[3].wrapping_add(q[49])
+        ];
+        assert_eq!(by_steps, compress(&iv, &block));
+    }
+
+    #[test]
+    fn test_paper_collision() {
+        let block_1 = words_to_block(&PAPER_M);
+        let block_2 = apply_differential(&block_1);
+
+        assert_ne!(block_1, block_2);
+        assert_eq!(md4(&block_1), md4(&block_2));
+    }
+
+    #[test]
+    fn test_round1_always_holds() {
+        let mut rng = StdRng::seed_from_u64(55);
+
+        for _ in 0..100 {
+            let mut q = initial_states(&MD4_INITIAL_STATE);
+            let mut m: [u32; 16] = rng.gen();
+            fix_round1(&mut q, &mut m);
+
+            let mut check = initial_states(&MD4_INITIAL_STATE);
+            assert!((0..16).all(|t| {
+                check[t + 4] = step(&check, &m, t);
+                step_holds(&check, t)
+            }));
+        }
+    }
+
+    #[test]
+    fn test_collision_from_other_iv() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let iv = [0x01234567, 0x89abcdef, 0xfedcba98, 0x76543210];
+        let collision = find_collision_from(&iv, &mut rng);
+
+        assert_eq!(compress(&iv, &collision.block_1), compress(&iv, &collision.block_2));
+    }
+}
@@ -0,0 +1,12 @@
+mod c50;
+mod c51;
+mod c52;
+mod c53;
+mod c54;
+mod c55;
+pub mod cbc_mac_forgery;
+pub mod compression_oracle;
+pub mod expandable_message;
+pub mod herding;
+pub mod md4_collision;
+pub mod multicollision;
@@ -0,0 +1,160 @@
+// Joux multicollisions (challenge 52). If blocks a0 and a1 collide from the
+// IV into h1, and b0 and b1 collide from h1 into h2, then all four of a0b0,
+// a0b1, a1b0 and a1b1 hash to h2. k birthday searches give 2^k messages with
+// the same hash, all k blocks long.
+use std::collections::HashMap;
+
+use crate::hashing::compression::Compression;
+
+pub fn random_block<C: Compression>(hash: &C) -> Vec<u8> {
+    crate::rng::vec::rand_len(hash.block_len())
+}
+
+// Two different blocks that take `state` to the same place. Returns them,
+// the new state and how many compression calls it took
+pub fn find_block_collision<C: Compression>(hash: &C, state: C::State) -> (Vec<u8>, Vec<u8>, C::State, usize) {
+    let mut seen: HashMap<C::State, Vec<u8>> = HashMap::new();
+    let mut calls = 0;
+
+    loop {
+        let block = random_block(hash);
+        let next = hash.compress(state, &block);
+        calls += 1;
+
+        match seen.get(&next) {
+            Some(other) if *other != block => return (other.clone(), block, next, calls),
+            _ => {
+                seen.insert(next, block);
+            }
+        }
+    }
+}
+
+// A block from each of two different states so both land in the same
+// place. Returns the blocks, that state and how many compression calls it
+// took
+pub fn find_collision_between<C: Compression>(
+    hash: &C,
+    state_1: C::State,
+    state_2: C::State
+) -> (Vec<u8>, Vec<u8>, C::State, usize) {
+    let mut seen_1: HashMap<C::State, Vec<u8>> = HashMap::new();
+    let mut seen_2: HashMap<C::State, Vec<u8>> = HashMap::new();
+    let mut calls = 0;
+
+    loop {
+        let block = random_block(hash);
+        let next = hash.compress(state_1, &block);
+        calls += 1;
+        if let Some(other) = seen_2.get(&next) {
+            return (block, other.clone(), next, calls);
+        }
+        seen_1.insert(next, block);
+
+        let block = random_block(hash);
+        let next = hash.compress(state_2, &block);
+        calls += 1;
+        if let Some(other) = seen_1.get(&next) {
+            return (other.clone(), block, next, calls);
+        }
+        seen_2.insert(next, block);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Multicollision<S> {
+    // One colliding pair per block. Message i takes the second block of pair
+    // j wherever bit j of i is set
+    pub pairs: Vec<(Vec<u8>, Vec<u8>)>,
+    pub start: S,
+    // Where every message ends up
+    pub state: S,
+    // Compression calls spent finding it
+    pub calls: usize
+}
+
+impl<S: Copy> Multicollision<S> {
+    // 2^k messages colliding from `start`
+    pub fn find<C: Compression<State = S>>(hash: &C, start: S, k: usize) -> Multicollision<S> {
+        let mut res = Multicollision { pairs: vec![], start, state: start, calls: 0 };
+        for _ in 0..k {
+            res.extend(hash);
+        }
+        res
+    }
+
+    // Doubles the number of messages with one more collision search
+    pub fn extend<C: Compression<State = S>>(&mut self, hash: &C) {
+        let (block_0, block_1, state, calls) = find_block_collision(hash, self.state);
+        self.pairs.push((block_0, block_1));
+        self.state = state;
+        self.calls += calls;
+    }
+
+    pub fn blocks(&self) -> usize {
+        self.pairs.len()
+    }
+
+    // 2^blocks
+    pub fn count(&self) -> u64 {
+        1 << self.pairs.len()
+    }
+
+    pub fn message(&self, index: u64) -> Vec<u8> {
+        assert!(index < self.count(), "Only {} messages", self.count());
+
+        self.pairs.iter()
+            .enumerate()
+            .flat_map(|(j, (block_0, block_1))| {
+                if index >> j & 1 == 0 { block_0.clone() } else { block_1.clone() }
+            })
+            .collect()
+    }
+
+    pub fn messages(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        (0..self.count()).map(move |i| self.message(i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use crate::hashing::cheap_hash::{CheapHash, CHEAP_HASH_BLOCK_LEN_BYTES};
+
+    #[test]
+    fn test_block_collision() {
+        let hash = CheapHash::new(16);
+        let (block_0, block_1, state, calls) = find_block_collision(&hash, hash.iv());
+
+        assert_ne!(block_0, block_1);
+        assert_eq!(hash.compress(hash.iv(), &block_0), state);
+        assert_eq!(hash.compress(hash.iv(), &block_1), state);
+        assert!(calls > 1);
+    }
+
+    #[test]
+    fn test_collision_between() {
+        let hash = CheapHash::new(16);
+        let (block_1, block_2, state, _) = find_collision_between(&hash, 1, 2);
+
+        assert_eq!(hash.compress(1, &block_1), state);
+        assert_eq!(hash.compress(2, &block_2), state);
+    }
+
+    #[test]
+    fn test_multicollision() {
+        let hash = CheapHash::new(20);
+        let multi = Multicollision::find(&hash, hash.iv(), 6);
+
+        let messages: HashSet<Vec<u8>> = multi.messages().collect();
+        assert_eq!(messages.len(), 64);
+
+        for message in messages.iter() {
+            assert_eq!(message.len(), 6 * CHEAP_HASH_BLOCK_LEN_BYTES);
+            assert_eq!(hash.hash_blocks(hash.iv(), message), multi.state);
+            // Same length, so the padding doesn't change anything
+            assert_eq!(hash.hash(message), hash.hash(&multi.message(0)));
+        }
+    }
+}
@@ -110,6 +110,29 @@ pub fn aes_decrypt_nopad(key: &[u8], ciphertext: &[u8], iv: &[u8], cipher: Ciphe
     plaintext
 }
 
+pub fn aes_encrypt_nopad(key: &[u8], plaintext: &[u8], iv: &[u8], cipher: Cipher) -> Vec<u8> {
+    assert_eq!(plaintext.len() % AES_BLOCK_SIZE, 0);
+    let mut encrypter = Crypter::new(
+        cipher,
+        Mode::Encrypt,
+        key,
+        Some(iv)
+    ).unwrap();
+
+    // The caller is responsible for padding (e.g. SSLv3 records)
+    encrypter.pad(false);
+    let mut ciphertext = vec![0; plaintext.len() + AES_BLOCK_SIZE];
+
+    let mut count = encrypter.update(plaintext, &mut ciphertext).unwrap();
+    count += encrypter.finalize(&mut ciphertext[count..]).unwrap();
+    ciphertext.truncate(count);
+    ciphertext
+}
+
+pub fn aes_cbc_encrypt_nopad(key: &[u8], plaintext: &[u8], iv: &[u8]) -> Vec<u8> {
+    aes_encrypt_nopad(key, plaintext, iv, Cipher::aes_128_cbc())
+}
+
 pub fn aes_ecb_decrypt_nopad(key: &[u8], ciphertext: &[u8], iv: &[u8]) -> Vec<u8> {
     aes_decrypt_nopad(key, ciphertext, iv, Cipher::aes_128_ecb())
 }
@@ -0,0 +1,79 @@
+// CBC-MAC with AES-128: CBC encrypt the (PKCS#7 padded) message and keep
+// the last ciphertext block. Only a MAC as long as the key stays secret,
+// with the key in hand every step can be run backwards.
+use openssl::symm::Cipher;
+
+use crate::symmetric::aes::*;
+
+pub fn cbc_mac(key: &[u8], iv: &[u8; AES_BLOCK_SIZE], message: &[u8]) -> [u8; AES_BLOCK_SIZE] {
+    cbc_mac_state(key, iv, &cbc_mac_pad(message))
+}
+
+// PKCS#7, so a whole block of padding when the message is already aligned
+pub fn cbc_mac_pad(message: &[u8]) -> Vec<u8> {
+    let pad_len = AES_BLOCK_SIZE - message.len() % AES_BLOCK_SIZE;
+    let mut res = message.to_vec();
+    res.extend(vec![pad_len as u8; pad_len]);
+    res
+}
+
+// Chaining value after running `blocks` through from `state`, no padding
+pub fn cbc_mac_state(key: &[u8], state: &[u8; AES_BLOCK_SIZE], blocks: &[u8]) -> [u8; AES_BLOCK_SIZE] {
+    if blocks.is_empty() {
+        return *state;
+    }
+    last_block(&aes_cbc_encrypt_nopad(key, blocks, state))
+}
+
+// The state we need before `blocks` so that running them ends in `state`
+pub fn cbc_mac_state_before(key: &[u8], blocks: &[u8], state: &[u8; AES_BLOCK_SIZE]) -> [u8; AES_BLOCK_SIZE] {
+    assert_eq!(blocks.len() % AES_BLOCK_SIZE, 0);
+    let mut res = *state;
+    for block in blocks.chunks(AES_BLOCK_SIZE).rev() {
+        let decrypted = aes_decrypt_nopad(key, &res, &[], Cipher::aes_128_ecb());
+        for i in 0..AES_BLOCK_SIZE {
+            res[i] = decrypted[i] ^ block[i];
+        }
+    }
+    res
+}
+
+fn last_block(ciphertext: &[u8]) -> [u8; AES_BLOCK_SIZE] {
+    let mut res = [0u8; AES_BLOCK_SIZE];
+    res.copy_from_slice(&ciphertext[ciphertext.len() - AES_BLOCK_SIZE..]);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::symmetric::aes::{aes_cbc_encrypt, gen_random_16_bytes, AES_BLOCK_SIZE};
+    use crate::symmetric::cbc_mac::*;
+
+    #[test]
+    fn test_cbc_mac() {
+        // From challenge 50
+        let mac = cbc_mac(b"YELLOW SUBMARINE", &[0u8; AES_BLOCK_SIZE], b"alert('MZA who was that?');\n");
+        assert_eq!(hex::encode(mac), "296b8d7cb78a243dda4d0a61d33bbdd1");
+    }
+
+    #[test]
+    fn test_pad() {
+        assert_eq!(cbc_mac_pad(b"abc").len(), AES_BLOCK_SIZE);
+        assert_eq!(cbc_mac_pad(&[0u8; AES_BLOCK_SIZE])[AES_BLOCK_SIZE..], [16u8; AES_BLOCK_SIZE]);
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let key = gen_random_16_bytes();
+        let iv = gen_random_16_bytes();
+        let message = crate::rng::vec::rand_len(50);
+        let padded = cbc_mac_pad(&message);
+
+        let mac = cbc_mac(&key, &iv, &message);
+        assert_eq!(aes_cbc_encrypt(&key, &message, Some(iv))[48..], mac);
+        assert_eq!(cbc_mac_state(&key, &iv, &padded), mac);
+        assert_eq!(cbc_mac_state(&key, &cbc_mac_state(&key, &iv, &padded[..32]), &padded[32..]), mac);
+        assert_eq!(cbc_mac_state_before(&key, &padded, &mac), iv);
+        assert_eq!(cbc_mac_state_before(&key, &padded[32..], &mac), cbc_mac_state(&key, &iv, &padded[..32]));
+    }
+}
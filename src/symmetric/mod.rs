@@ -1 +1,3 @@
 pub mod aes;
+pub mod cbc_mac;
+pub mod sslv3;
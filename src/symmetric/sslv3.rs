@@ -0,0 +1,116 @@
+// A stripped down SSLv3 record layer (https://tools.ietf.org/html/rfc6101#section-5.2.3)
+// Records are MAC-then-encrypt with AES-128-CBC and HMAC-SHA1. Unlike the
+// real protocol we send a fresh IV with every record instead of chaining
+// IVs between records. None of the attacks care about this.
+use crate::hashing::hmac::hmac;
+use crate::hashing::sha1::{Sha1, SHA1_LEN_BYTES};
+use crate::symmetric::aes::*;
+
+pub const SSLV3_CONTENT_APPLICATION_DATA: u8 = 23;
+
+// Both ends of a connection start with a copy of the same session
+#[derive(Clone)]
+pub struct Sslv3Session {
+    enc_key: [u8; AES_BLOCK_SIZE],
+    mac_key: Vec<u8>,
+    seq_num: u64
+}
+
+impl Sslv3Session {
+    pub fn new() -> Sslv3Session {
+        Sslv3Session {
+            enc_key: gen_random_16_bytes(),
+            mac_key: crate::rng::vec::rand_len(SHA1_LEN_BYTES),
+            seq_num: 0
+        }
+    }
+
+    fn mac(&self, content: &[u8]) -> Vec<u8> {
+        let mut to_mac = self.seq_num.to_be_bytes().to_vec();
+        to_mac.push(SSLV3_CONTENT_APPLICATION_DATA);
+        to_mac.extend_from_slice(&(content.len() as u16).to_be_bytes());
+        to_mac.extend_from_slice(content);
+        hmac::<Sha1>(&self.mac_key, &to_mac)
+    }
+
+    // Returns IV || E(content || MAC || padding)
+    pub fn seal(&mut self, content: &[u8]) -> Vec<u8> {
+        let mut pt = content.to_vec();
+        pt.extend_from_slice(&self.mac(content));
+
+        // SSLv3 only specifies the last byte (the padding length). The
+        // other padding bytes can be anything, so we leave them random.
+        let padding_len = AES_BLOCK_SIZE - 1 - pt.len() % AES_BLOCK_SIZE;
+        pt.extend_from_slice(&crate::rng::vec::rand_len(padding_len));
+        pt.push(padding_len as u8);
+
+        let iv = gen_random_16_bytes();
+        let mut res = iv.to_vec();
+        res.extend_from_slice(&aes_cbc_encrypt_nopad(&self.enc_key, &pt, &iv));
+
+        self.seq_num += 1;
+        res
+    }
+
+    pub fn open(&mut self, record: &[u8]) -> Result<Vec<u8>, &'static str> {
+        if !record.len().is_multiple_of(AES_BLOCK_SIZE) || record.len() < 2 * AES_BLOCK_SIZE {
+            return Err("Record length is not a valid number of blocks");
+        }
+
+        let mut pt = aes_cbc_decrypt_nopad(
+            &self.enc_key,
+            &record[AES_BLOCK_SIZE..],
+            &record[0..AES_BLOCK_SIZE]
+        );
+
+        // This is the bug. Only the length byte is checked, the padding
+        // contents are never looked at.
+        let padding_len = *pt.last().unwrap() as usize;
+        if padding_len >= AES_BLOCK_SIZE {
+            return Err("Invalid padding");
+        }
+
+        let pt_len = pt.len();
+        if padding_len + 1 + SHA1_LEN_BYTES > pt_len {
+            return Err("Record too short to contain a MAC");
+        }
+        pt.truncate(pt_len - padding_len - 1);
+
+        let mac_start = pt.len() - SHA1_LEN_BYTES;
+        let received_mac = pt.split_off(mac_start);
+        if self.mac(&pt) != received_mac {
+            return Err("Bad record MAC");
+        }
+
+        self.seq_num += 1;
+        Ok(pt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sslv3_round_trip() {
+        let mut client = Sslv3Session::new();
+        let mut server = client.clone();
+
+        for i in 0..100 {
+            let data = crate::rng::vec::rand_len(i);
+            let record = client.seal(&data);
+            assert_eq!(record.len() % AES_BLOCK_SIZE, 0);
+            assert_eq!(server.open(&record).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_sslv3_rejects_tampering() {
+        let mut client = Sslv3Session::new();
+        let mut server = client.clone();
+
+        let mut record = client.seal(b"GET / HTTP/1.1\r\n\r\n");
+        record[AES_BLOCK_SIZE] ^= 1;
+        assert!(server.open(&record).is_err());
+    }
+}
@@ -0,0 +1,313 @@
+// Raw DEFLATE (RFC 1951), just enough to get realistic compression ratios
+// without pulling in zlib.
+//
+// Greedy LZ77 over a 32K window (hash chains on 3 byte prefixes, longest
+// match wins, nearest one on ties), coded as a single final block with the
+// fixed Huffman tables. No dynamic tables and no stored blocks, so the
+// output is never the smallest possible, but any inflater will read it and
+// the size reacts to repeated substrings the same way zlib's does.
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+// How many earlier positions we look at before settling for what we have
+const MAX_CHAIN: usize = 256;
+
+// Hash table size scales with the input, short requests are the common case
+const MIN_HASH_BITS: u32 = 8;
+const MAX_HASH_BITS: u32 = 15;
+const NO_POS: usize = usize::MAX;
+
+const END_OF_BLOCK: u16 = 256;
+
+// Length codes 257..=285
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0
+];
+
+// Distance codes 0..=29
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Token {
+    Literal(u8),
+    // Copy `len` bytes starting `dist` bytes back
+    Match { len: u16, dist: u16 }
+}
+
+// DEFLATE packs everything starting from the least significant bit of each
+// byte, except Huffman codes which go in most significant bit first
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u32,
+    bits: u32
+}
+
+impl BitWriter {
+    fn new() -> BitWriter {
+        BitWriter { out: Vec::new(), acc: 0, bits: 0 }
+    }
+
+    fn write_bits(&mut self, value: u32, count: u32) {
+        self.acc |= value << self.bits;
+        self.bits += count;
+        while self.bits >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn write_code(&mut self, code: u32, len: u32) {
+        let reversed = code.reverse_bits() >> (32 - len);
+        self.write_bits(reversed, len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+fn hash3(data: &[u8], hash_bits: u32) -> usize {
+    let v = (data[0] as usize) << 16 | (data[1] as usize) << 8 | data[2] as usize;
+    (v.wrapping_mul(2654435761) >> 8) & ((1 << hash_bits) - 1)
+}
+
+fn insert(data: &[u8], pos: usize, head: &mut [usize], prev: &mut [usize], hash_bits: u32) {
+    if pos + MIN_MATCH <= data.len() {
+        let h = hash3(&data[pos..], hash_bits);
+        prev[pos] = head[h];
+        head[h] = pos;
+    }
+}
+
+pub fn lz77(data: &[u8]) -> Vec<Token> {
+    let hash_bits = (usize::BITS - data.len().leading_zeros()).clamp(MIN_HASH_BITS, MAX_HASH_BITS);
+    let mut head = vec![NO_POS; 1 << hash_bits];
+    let mut prev = vec![NO_POS; data.len()];
+    let mut tokens = Vec::new();
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let max_len = MAX_MATCH.min(data.len() - pos);
+        let mut best_len = 0;
+        let mut best_dist = 0;
+
+        if max_len >= MIN_MATCH {
+            let mut candidate = head[hash3(&data[pos..], hash_bits)];
+            let mut chain = 0;
+            while candidate != NO_POS && pos - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+                let len = data[candidate..]
+                    .iter()
+                    .zip(&data[pos..pos + max_len])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best_len {
+                    best_len = len;
+                    best_dist = pos - candidate;
+                    if len == max_len {
+                        break;
+                    }
+                }
+                candidate = prev[candidate];
+                chain += 1;
+            }
+        }
+
+        if best_len >= MIN_MATCH {
+            tokens.push(Token::Match { len: best_len as u16, dist: best_dist as u16 });
+            for p in pos..pos + best_len {
+                insert(data, p, &mut head, &mut prev, hash_bits);
+            }
+            pos += best_len;
+        } else {
+            tokens.push(Token::Literal(data[pos]));
+            insert(data, pos, &mut head, &mut prev, hash_bits);
+            pos += 1;
+        }
+    }
+    tokens
+}
+
+// Fixed literal/length code from RFC 1951 3.2.6
+fn write_literal_length(writer: &mut BitWriter, symbol: u16) {
+    let symbol = symbol as u32;
+    match symbol {
+        0..=143 => writer.write_code(0x30 + symbol, 8),
+        144..=255 => writer.write_code(0x190 + symbol - 144, 9),
+        256..=279 => writer.write_code(symbol - 256, 7),
+        _ => writer.write_code(0xc0 + symbol - 280, 8)
+    }
+}
+
+// Index of the last base that's <= value
+fn find_code(bases: &[u16], value: u16) -> usize {
+    bases.iter().rposition(|&base| base <= value).unwrap()
+}
+
+fn write_match(writer: &mut BitWriter, len: u16, dist: u16) {
+    let len_ind = find_code(&LENGTH_BASE, len);
+    write_literal_length(writer, 257 + len_ind as u16);
+    writer.write_bits((len - LENGTH_BASE[len_ind]) as u32, LENGTH_EXTRA[len_ind] as u32);
+
+    let dist_ind = find_code(&DIST_BASE, dist);
+    writer.write_code(dist_ind as u32, 5);
+    writer.write_bits((dist - DIST_BASE[dist_ind]) as u32, DIST_EXTRA[dist_ind] as u32);
+}
+
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    // BFINAL = 1, BTYPE = 01 (fixed Huffman)
+    writer.write_bits(1, 1);
+    writer.write_bits(1, 2);
+
+    for token in lz77(data) {
+        match token {
+            Token::Literal(b) => write_literal_length(&mut writer, b as u16),
+            Token::Match { len, dist } => write_match(&mut writer, len, dist)
+        }
+    }
+
+    write_literal_length(&mut writer, END_OF_BLOCK);
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct BitReader<'a> {
+        data: &'a [u8],
+        pos: usize
+    }
+
+    impl<'a> BitReader<'a> {
+        fn bit(&mut self) -> u32 {
+            let b = (self.data[self.pos / 8] >> (self.pos % 8)) & 1;
+            self.pos += 1;
+            b as u32
+        }
+
+        fn bits(&mut self, count: u8) -> u32 {
+            (0..count as u32).fold(0, |acc, i| acc | self.bit() << i)
+        }
+
+        fn code(&mut self, len: u32) -> u32 {
+            (0..len).fold(0, |acc, _| acc << 1 | self.bit())
+        }
+
+        fn literal_length(&mut self) -> u16 {
+            let mut code = self.code(7);
+            if code <= 0x17 {
+                return 256 + code as u16;
+            }
+            code = code << 1 | self.bit();
+            match code {
+                0x30..=0xbf => (code - 0x30) as u16,
+                0xc0..=0xc7 => (280 + code - 0xc0) as u16,
+                _ => (144 + (code << 1 | self.bit()) - 0x190) as u16
+            }
+        }
+    }
+
+    // Only understands single fixed Huffman blocks, which is all we emit
+    fn inflate_fixed(data: &[u8]) -> Vec<u8> {
+        let mut reader = BitReader { data, pos: 0 };
+        assert_eq!(reader.bits(1), 1);
+        assert_eq!(reader.bits(2), 1);
+
+        let mut out: Vec<u8> = Vec::new();
+        loop {
+            let symbol = reader.literal_length();
+            if symbol < 256 {
+                out.push(symbol as u8);
+            } else if symbol == END_OF_BLOCK {
+                return out;
+            } else {
+                let len_ind = (symbol - 257) as usize;
+                let len = LENGTH_BASE[len_ind] as usize + reader.bits(LENGTH_EXTRA[len_ind]) as usize;
+                let dist_ind = reader.code(5) as usize;
+                let dist = DIST_BASE[dist_ind] as usize + reader.bits(DIST_EXTRA[dist_ind]) as usize;
+                let start = out.len() - dist;
+                for i in 0..len {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_inflate_zlib_output() {
+        // zlib.compressobj(9, zlib.DEFLATED, -15, 9, zlib.Z_FIXED)
+        let compressed = hex::decode(ZLIB_FIXED).unwrap();
+        assert_eq!(inflate_fixed(&compressed), ZLIB_PLAINTEXT.to_vec());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut inputs = vec![
+            b"".to_vec(),
+            b"a".to_vec(),
+            b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".to_vec(),
+            ZLIB_PLAINTEXT.to_vec(),
+            vec![0u8; 100_000],
+            crate::rng::vec::rand_len(5000)
+        ];
+        // Lots of long distance matches
+        let chunk = crate::rng::vec::rand_len(1000);
+        inputs.push(chunk.iter().cycle().take(70_000).cloned().collect());
+
+        for input in inputs {
+            assert_eq!(inflate_fixed(&deflate(&input)), input);
+        }
+    }
+
+    #[test]
+    fn test_matches_zlib() {
+        // zlib makes the same greedy choices on inputs this small
+        assert_eq!(hex::encode(deflate(ZLIB_PLAINTEXT)), ZLIB_FIXED);
+    }
+
+    #[test]
+    fn test_repeats_compress() {
+        let secret = b"sessionid=TmV2ZXIgcmV2ZWFs";
+        let mut with_repeat = secret.to_vec();
+        with_repeat.extend_from_slice(b"sessionid=TmV2");
+        let mut without_repeat = secret.to_vec();
+        without_repeat.extend_from_slice(b"xyzwvutsrqponm");
+        assert!(deflate(&with_repeat).len() < deflate(&without_repeat).len());
+    }
+
+    #[test]
+    fn test_lz77_matches() {
+        assert_eq!(lz77(b"abcabcabcd"), vec![
+            Token::Literal(b'a'),
+            Token::Literal(b'b'),
+            Token::Literal(b'c'),
+            Token::Match { len: 6, dist: 3 },
+            Token::Literal(b'd')
+        ]);
+    }
+
+    const ZLIB_PLAINTEXT: &[u8] = b"POST / HTTP/1.1\r\nHost: hapless.com\r\n\
+        Cookie: sessionid=TmV2ZXIgcmV2ZWFsIHRoZSBXdS1UYW5nIFNlY3JldCE=\r\n\
+        Content-Length: 34\r\n\r\nsessionid=TmV2ZXIgcmV2ZWFsIHRoZSBX";
+    const ZLIB_FIXED: &str = "0bf00f0e51d057f0080909d037d433e4e5f2c82f2eb152c8482cc8492d2ed64bcecfe5e572cecf\
+        cfce4cb55228068a64e6e765a6d886e48619454578a62783e870b7624f8fa0fca860a7889460c3d0c870d33c4f37bf9c\
+        4863af9c1467575b90febc92d4bc125d9fd4bcf4920c2b0563135e2e5e2ec2a60100";
+}